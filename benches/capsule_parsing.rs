@@ -0,0 +1,113 @@
+//! Parsing measurement answers into nodes: capsules, ultra capsules and HQ nodes
+//!
+//! Run with `cargo bench --bench capsule_parsing`. Throughput is reported in measured samples,
+//! an A3 measures up to 16000 samples per second.
+
+#[macro_use]
+extern crate criterion;
+extern crate byteorder;
+extern crate crc;
+extern crate rplidar_drv;
+
+mod corpus;
+
+use criterion::{black_box, Criterion, Throughput};
+use rplidar_drv::rplidar_core::answers::*;
+use rplidar_drv::rplidar_core::internals::*;
+use rplidar_drv::rplidar_core::{parse_answer, CachedPrevCapsule, MeasurementDecoder};
+
+fn capsuled(c: &mut Criterion) {
+    let capsules: Vec<RplidarResponseCapsuleMeasurementNodes> =
+        corpus::capsules(2).iter().map(|capsule| parse_answer(capsule).unwrap()).collect();
+
+    let mut group = c.benchmark_group("capsuled");
+    group.throughput(Throughput::Elements((capsules.len() * corpus::CAPSULED_ANSWER_SAMPLES) as u64));
+    group.bench_function("parse_capsuled", |b| {
+        b.iter(|| {
+            let mut cached = CachedPrevCapsule::None;
+            for capsule in capsules.iter() {
+                cached = parse_capsuled(&cached, *capsule, &mut |node| {
+                    black_box(node);
+                });
+            }
+        })
+    });
+    group.finish();
+}
+
+fn ultra_capsuled(c: &mut Criterion) {
+    let capsules: Vec<RplidarResponseUltraCapsuleMeasurementNodes> =
+        corpus::ultra_capsules(2).iter().map(|capsule| parse_answer(capsule).unwrap()).collect();
+
+    let mut group = c.benchmark_group("ultra_capsuled");
+    group.throughput(Throughput::Elements((capsules.len() * corpus::ULTRA_CAPSULED_ANSWER_SAMPLES) as u64));
+    group.bench_function("parse_ultra_capsuled", |b| {
+        b.iter(|| {
+            let mut cached = CachedPrevCapsule::None;
+            for capsule in capsules.iter() {
+                cached = parse_ultra_capsuled(&cached, *capsule, &mut |node| {
+                    black_box(node);
+                });
+            }
+        })
+    });
+
+    // every 12 bits major distance
+    group.throughput(Throughput::Elements(4096));
+    group.bench_function("varbit_scale_decode", |b| {
+        b.iter(|| {
+            for scaled in 0..4096u32 {
+                black_box(varbit_scale_decode(black_box(scaled)));
+            }
+        })
+    });
+
+    // distances up to 16 m, in q2
+    group.throughput(Throughput::Elements(4096));
+    group.bench_function("calc_angle_offset_q16", |b| {
+        b.iter(|| {
+            for dist_q2 in (0..16000u32 * 4).step_by(16) {
+                black_box(calc_angle_offset_q16(black_box(dist_q2)));
+            }
+        })
+    });
+    group.finish();
+}
+
+/// all measurement answer types through `MeasurementDecoder`, checksums included
+fn measurement_decoder(c: &mut Criterion) {
+    let mut group = c.benchmark_group("measurement_decoder");
+
+    let answers: Vec<(&str, u8, Vec<Vec<u8>>, usize)> = vec![
+        ("legacy", RPLIDAR_ANS_TYPE_MEASUREMENT, corpus::legacy_nodes(2).iter().map(|a| a.to_vec()).collect(), 1),
+        ("capsuled", RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, corpus::capsules(2).iter().map(|a| a.to_vec()).collect(), corpus::CAPSULED_ANSWER_SAMPLES),
+        (
+            "ultra_capsuled",
+            RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA,
+            corpus::ultra_capsules(2).iter().map(|a| a.to_vec()).collect(),
+            corpus::ULTRA_CAPSULED_ANSWER_SAMPLES,
+        ),
+        ("hq", RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, corpus::hq_capsules(2).iter().map(|a| a.to_vec()).collect(), corpus::HQ_ANSWER_SAMPLES),
+    ];
+
+    for (name, ans_type, answers, samples) in answers.iter() {
+        group.throughput(Throughput::Elements((answers.len() * samples) as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut decoder = MeasurementDecoder::new();
+                for answer in answers.iter() {
+                    decoder
+                        .decode(*ans_type, answer, |point| {
+                            black_box(point);
+                        })
+                        .unwrap();
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, capsuled, ultra_capsuled, measurement_decoder);
+criterion_main!(benches);
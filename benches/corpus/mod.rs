@@ -0,0 +1,192 @@
+//! Generated answers of a LIDAR scanning a 4 x 6 m room, shared by the benchmarks
+
+#![allow(dead_code)]
+
+use byteorder::{ByteOrder, LittleEndian};
+use rplidar_drv::rplidar_core::answers::*;
+use rplidar_drv::rplidar_core::{encode_answer_header, Checksum, ScanPoint, RPLIDAR_ANS_HEADER_LEN};
+
+/// samples per rotation of each answer type, as measured by an A3 at 10 Hz
+pub const LEGACY_SAMPLES: usize = 400;
+pub const CAPSULED_SAMPLES: usize = 800;
+pub const ULTRA_CAPSULED_SAMPLES: usize = 1600;
+pub const HQ_SAMPLES: usize = 1600;
+
+/// samples in an answer of each type
+pub const CAPSULED_ANSWER_SAMPLES: usize = 32;
+pub const ULTRA_CAPSULED_ANSWER_SAMPLES: usize = 96;
+pub const HQ_ANSWER_SAMPLES: usize = 16;
+
+pub const CAPSULE_SIZE: usize = 84;
+pub const ULTRA_CAPSULE_SIZE: usize = 132;
+pub const HQ_CAPSULE_SIZE: usize = 141;
+
+/// distance to the walls of the room around the LIDAR, in mm
+pub fn room_distance_mm(angle_deg: f64) -> u32 {
+    let (half_width, half_depth) = (2000f64, 3000f64);
+    let (sin, cos) = angle_deg.to_radians().sin_cos();
+
+    let to_side = if sin.abs() > 1e-9 { half_width / sin.abs() } else { f64::MAX };
+    let to_front = if cos.abs() > 1e-9 { half_depth / cos.abs() } else { f64::MAX };
+    to_side.min(to_front) as u32
+}
+
+fn sample_angle_deg(sample: usize, samples_per_rotation: usize) -> f64 {
+    (sample % samples_per_rotation) as f64 * 360f64 / samples_per_rotation as f64
+}
+
+fn sample_distance_mm(sample: usize, samples_per_rotation: usize) -> u32 {
+    room_distance_mm(sample_angle_deg(sample, samples_per_rotation))
+}
+
+fn start_angle_q6(sample: usize, samples_per_rotation: usize, start: bool) -> u16 {
+    let angle_q6 = (sample_angle_deg(sample, samples_per_rotation) * 64f64) as u16;
+    if start {
+        angle_q6 | 0x8000
+    } else {
+        angle_q6
+    }
+}
+
+/// sync bits and checksum of express and ultra capsules
+fn seal(capsule: &mut [u8]) {
+    let mut checksum = Checksum::new();
+    checksum.push_slice(&capsule[2..]);
+    let checksum = checksum.checksum();
+
+    capsule[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum & 0xf);
+    capsule[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum >> 4);
+}
+
+/// legacy measurement nodes of `rotations` rotations
+pub fn legacy_nodes(rotations: usize) -> Vec<[u8; 5]> {
+    (0..rotations * LEGACY_SAMPLES)
+        .map(|sample| {
+            let sync_bits = if sample % LEGACY_SAMPLES == 0 { 0b01 } else { 0b10 };
+            let angle_q6 = (sample_angle_deg(sample, LEGACY_SAMPLES) * 64f64) as u16;
+            let dist_q2 = (sample_distance_mm(sample, LEGACY_SAMPLES) * 4).min(0xFFFF) as u16;
+
+            let mut node = [(47 << 2) | sync_bits, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut node[1..3], (angle_q6 << 1) | 1);
+            LittleEndian::write_u16(&mut node[3..5], dist_q2);
+            node
+        })
+        .collect()
+}
+
+/// express scan capsules (32 samples each) of `rotations` rotations
+pub fn capsules(rotations: usize) -> Vec<[u8; CAPSULE_SIZE]> {
+    (0..rotations * CAPSULED_SAMPLES / CAPSULED_ANSWER_SAMPLES)
+        .map(|i| {
+            let first = i * CAPSULED_ANSWER_SAMPLES;
+            let mut capsule = [0u8; CAPSULE_SIZE];
+            LittleEndian::write_u16(&mut capsule[2..4], start_angle_q6(first, CAPSULED_SAMPLES, i == 0));
+
+            for (j, cabin) in capsule[4..].chunks_mut(5).enumerate() {
+                let dist1 = sample_distance_mm(first + 2 * j, CAPSULED_SAMPLES).min(0x3FFF) as u16;
+                let dist2 = sample_distance_mm(first + 2 * j + 1, CAPSULED_SAMPLES).min(0x3FFF) as u16;
+                LittleEndian::write_u16(&mut cabin[0..2], dist1 << 2);
+                LittleEndian::write_u16(&mut cabin[2..4], dist2 << 2);
+            }
+
+            seal(&mut capsule);
+            capsule
+        })
+        .collect()
+}
+
+/// scale a distance (mm) into the 12 bits major distance of ultra capsules, returns it and the scale level
+fn varbit_scale_encode(dist: u32) -> (u32, u32) {
+    match dist {
+        d if d >= 1 << 14 => ((3328 + ((d - (1 << 14)) >> 4)).min(0xFFF), 4),
+        d if d >= 1 << 12 => (1792 + ((d - (1 << 12)) >> 3), 3),
+        d if d >= 1 << 11 => (1280 + ((d - (1 << 11)) >> 2), 2),
+        d if d >= 1 << 9 => (512 + ((d - (1 << 9)) >> 1), 1),
+        d => (d, 0),
+    }
+}
+
+/// distance predicted from `base`, 10 bits signed without the reserved values
+fn predict(base: u32, dist: u32, scale_level: u32) -> u32 {
+    let predict = ((dist as i32 - base as i32) >> scale_level).clamp(-511, 510);
+    (predict as u32) & 0x3FF
+}
+
+/// ultra capsules (96 samples each) of `rotations` rotations
+pub fn ultra_capsules(rotations: usize) -> Vec<[u8; ULTRA_CAPSULE_SIZE]> {
+    (0..rotations * ULTRA_CAPSULED_SAMPLES / ULTRA_CAPSULED_ANSWER_SAMPLES)
+        .map(|i| {
+            let first = i * ULTRA_CAPSULED_ANSWER_SAMPLES;
+            let mut capsule = [0u8; ULTRA_CAPSULE_SIZE];
+            LittleEndian::write_u16(&mut capsule[2..4], start_angle_q6(first, ULTRA_CAPSULED_SAMPLES, i == 0));
+
+            for (j, cabin) in capsule[4..].chunks_mut(4).enumerate() {
+                let sample = first + 3 * j;
+                let dist0 = sample_distance_mm(sample, ULTRA_CAPSULED_SAMPLES);
+                let (major, scale_level) = varbit_scale_encode(dist0);
+
+                let predict1 = predict(dist0, sample_distance_mm(sample + 1, ULTRA_CAPSULED_SAMPLES), scale_level);
+                let predict2 = predict(dist0, sample_distance_mm(sample + 2, ULTRA_CAPSULED_SAMPLES), scale_level);
+                LittleEndian::write_u32(cabin, major | (predict1 << 12) | (predict2 << 22));
+            }
+
+            seal(&mut capsule);
+            capsule
+        })
+        .collect()
+}
+
+/// HQ capsules (16 samples each) of `rotations` rotations
+pub fn hq_capsules(rotations: usize) -> Vec<[u8; HQ_CAPSULE_SIZE]> {
+    (0..rotations * HQ_SAMPLES / HQ_ANSWER_SAMPLES)
+        .map(|i| {
+            let first = i * HQ_ANSWER_SAMPLES;
+            let mut capsule = [0u8; HQ_CAPSULE_SIZE];
+            capsule[0] = RPLIDAR_RESP_MEASUREMENT_HQ_SYNC;
+            LittleEndian::write_u64(&mut capsule[1..9], first as u64 * 100);
+
+            for (j, node) in capsule[9..HQ_CAPSULE_SIZE - 4].chunks_mut(8).enumerate() {
+                let sample = first + j;
+                let angle_z_q14 = (sample_angle_deg(sample, HQ_SAMPLES) * 16384f64 / 90f64) as u16;
+                LittleEndian::write_u16(&mut node[0..2], angle_z_q14);
+                LittleEndian::write_u32(&mut node[2..6], sample_distance_mm(sample, HQ_SAMPLES) * 4);
+                node[6] = 190;
+                node[7] = if sample % HQ_SAMPLES == 0 { RPLIDAR_RESP_HQ_FLAG_SYNCBIT } else { 0 };
+            }
+
+            let crc = crc::crc32::checksum_ieee(&capsule[..HQ_CAPSULE_SIZE - 4]);
+            LittleEndian::write_u32(&mut capsule[HQ_CAPSULE_SIZE - 4..], crc);
+            capsule
+        })
+        .collect()
+}
+
+/// byte stream of a loop answer: the header, then `answers`
+pub fn loop_answer<A: AsRef<[u8]>>(ans_type: u8, answers: &[A]) -> Vec<u8> {
+    let size = answers.first().map_or(0, |answer| answer.as_ref().len());
+    let mut bytes = vec![0u8; RPLIDAR_ANS_HEADER_LEN];
+    encode_answer_header(ans_type, size, true, &mut bytes).unwrap();
+
+    for answer in answers {
+        bytes.extend_from_slice(answer.as_ref());
+    }
+    bytes
+}
+
+/// one rotation of scan points, starting at `start_deg` like unsorted scans do
+pub fn scan(points: usize, start_deg: f64) -> Vec<ScanPoint> {
+    (0..points)
+        .map(|i| {
+            // some points without echo, which get interpolated angles
+            let valid = i % 50 != 7;
+            let angle_deg = (start_deg + i as f64 * 360f64 / points as f64) % 360f64;
+            let node = RplidarResponseMeasurementNodeHq {
+                angle_z_q14: if valid { (angle_deg * 16384f64 / 90f64) as u16 } else { 0 },
+                dist_mm_q2: if valid { room_distance_mm(angle_deg) * 4 } else { 0 },
+                quality: if valid { 190 } else { 0 },
+                flag: if i == 0 { RPLIDAR_RESP_HQ_FLAG_SYNCBIT } else { 0 },
+            };
+            ScanPoint::from(node)
+        })
+        .collect()
+}
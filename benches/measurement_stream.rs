@@ -0,0 +1,193 @@
+//! Decoding express scan streams: owned messages against messages borrowed from the decoder
+//!
+//! Run with `cargo bench --bench measurement_stream`. Before the timings, the number of heap
+//! allocations per capsule and per scan of each path is printed.
+
+#[macro_use]
+extern crate criterion;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+
+use criterion::{Criterion, Throughput};
+use rplidar_drv::rplidar_core::cmds::RPLIDAR_CMD_EXPRESS_SCAN;
+use rplidar_drv::rplidar_core::{encode_command, MeasurementDecoder, ScanPoint};
+use rplidar_drv::simulator::{SimulatedLidar, SimulatorConfig};
+use rplidar_drv::{RplidarDevice, RplidarHostProtocol, TimedScan};
+use rpos_drv::Channel;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// System allocator counting allocations
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Express scan recorded from the simulator, replayed endlessly
+struct Replay {
+    header: Vec<u8>,
+    capsules: Vec<u8>,
+    pos: usize,
+}
+
+impl Replay {
+    /// one second of express scan (mode 1) at 10 Hz
+    fn express_scan() -> Replay {
+        let mut lidar = SimulatedLidar::new(SimulatorConfig::default()).unwrap();
+        let mut command = [0u8; 16];
+        let len = encode_command(RPLIDAR_CMD_EXPRESS_SCAN, &[1, 0, 0, 0, 0], &mut command).unwrap();
+
+        let started_at = Instant::now();
+        lidar.receive(&command[..len]);
+
+        let mut recorded = Vec::new();
+        let mut buf = [0u8; 4096];
+        for ms in 1..=1000 {
+            let len = lidar.transmit(started_at + Duration::from_millis(ms), &mut buf);
+            recorded.extend_from_slice(&buf[..len]);
+        }
+
+        // the first capsule starts the scan, replay the others
+        let capsule_size = recorded[2] as usize;
+        let end = (recorded.len() - 7) / capsule_size * capsule_size + 7;
+        Replay {
+            header: recorded[..7].to_vec(),
+            capsules: recorded[7 + capsule_size..end].to_vec(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.header.is_empty() {
+            let len = buf.len().min(self.header.len());
+            buf[..len].copy_from_slice(&self.header[..len]);
+            self.header.drain(..len);
+            return Ok(len);
+        }
+
+        let len = buf.len().min(self.capsules.len() - self.pos);
+        buf[..len].copy_from_slice(&self.capsules[self.pos..self.pos + len]);
+        self.pos = (self.pos + len) % self.capsules.len();
+        Ok(len)
+    }
+}
+
+impl Write for Replay {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+type ReplayChannel = Channel<RplidarHostProtocol, Replay>;
+
+fn channel() -> ReplayChannel {
+    Channel::new(RplidarHostProtocol::new(), Box::new(Replay::express_scan()))
+}
+
+/// decode a capsule copied out of the decoder, like `Channel::read`
+fn owned_capsule(channel: &mut ReplayChannel, decoder: &mut MeasurementDecoder, points: &mut VecDeque<ScanPoint>) {
+    let msg = channel.read_until(TIMEOUT).unwrap().unwrap();
+    decoder.decode(msg.cmd, &msg.data, |point| points.push_back(point)).unwrap();
+    points.clear();
+}
+
+/// decode a capsule in the buffer of the decoder
+fn borrowed_capsule(channel: &mut ReplayChannel, decoder: &mut MeasurementDecoder, points: &mut VecDeque<ScanPoint>) {
+    let msg = channel.read_ref_until(TIMEOUT).unwrap().unwrap();
+    decoder.decode(msg.cmd, msg.data, |point| points.push_back(point)).unwrap();
+    points.clear();
+}
+
+/// allocations of `f` per call, after warming up
+fn allocations<F: FnMut()>(mut f: F) -> f64 {
+    const CALLS: usize = 1000;
+
+    for _ in 0..CALLS {
+        f();
+    }
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CALLS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / CALLS as f64
+}
+
+fn report_allocations() {
+    let (mut channel, mut decoder, mut points) = (channel(), MeasurementDecoder::new(), VecDeque::with_capacity(64));
+    let owned = allocations(|| owned_capsule(&mut channel, &mut decoder, &mut points));
+
+    let (mut channel, mut decoder) = (self::channel(), MeasurementDecoder::new());
+    let borrowed = allocations(|| borrowed_capsule(&mut channel, &mut decoder, &mut points));
+
+    let mut rplidar = RplidarDevice::with_stream(Box::new(Replay::express_scan()));
+    let grab_timed_scan = allocations(|| {
+        rplidar.grab_timed_scan().unwrap();
+    });
+
+    let mut scan = TimedScan { points: Vec::new(), timestamps: Vec::new() };
+    let grab_timed_scan_into = allocations(|| rplidar.grab_timed_scan_into(&mut scan).unwrap());
+
+    eprintln!("allocations per capsule: owned {}, borrowed {}", owned, borrowed);
+    eprintln!("allocations per scan: grab_timed_scan {}, grab_timed_scan_into {}", grab_timed_scan, grab_timed_scan_into);
+}
+
+fn capsules(c: &mut Criterion) {
+    report_allocations();
+
+    let mut group = c.benchmark_group("express_capsule");
+    group.throughput(Throughput::Elements(32));
+
+    let (mut channel, mut decoder, mut points) = (channel(), MeasurementDecoder::new(), VecDeque::with_capacity(64));
+    group.bench_function("owned", |b| b.iter(|| owned_capsule(&mut channel, &mut decoder, &mut points)));
+
+    let (mut channel, mut decoder) = (self::channel(), MeasurementDecoder::new());
+    group.bench_function("borrowed", |b| b.iter(|| borrowed_capsule(&mut channel, &mut decoder, &mut points)));
+
+    group.finish();
+}
+
+fn scans(c: &mut Criterion) {
+    let mut group = c.benchmark_group("express_scan");
+    group.throughput(Throughput::Elements(400));
+
+    let mut rplidar = RplidarDevice::with_stream(Box::new(Replay::express_scan()));
+    group.bench_function("grab_timed_scan", |b| b.iter(|| rplidar.grab_timed_scan().unwrap()));
+
+    let mut scan = TimedScan { points: Vec::new(), timestamps: Vec::new() };
+    group.bench_function("grab_timed_scan_into", |b| b.iter(|| rplidar.grab_timed_scan_into(&mut scan).unwrap()));
+
+    group.finish();
+}
+
+criterion_group!(benches, capsules, scans);
+criterion_main!(benches);
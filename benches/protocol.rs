@@ -0,0 +1,117 @@
+//! Decoding answer streams with `RplidarHostProtocol`, and moving them through `RingByteBuffer`
+//!
+//! Run with `cargo bench --bench protocol`. Throughput is reported in bytes, the serial link of
+//! an A3 carries 256000 baud, about 25 KB/s.
+
+#[macro_use]
+extern crate criterion;
+extern crate byteorder;
+extern crate crc;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+
+mod corpus;
+
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use rplidar_drv::rplidar_core::answers::*;
+use rplidar_drv::RplidarHostProtocol;
+use rpos_drv::{BufferedProtocolDecoder, ProtocolDecoder, RingByteBuffer};
+use std::io::{Read, Write};
+
+/// bytes handed to the decoder at once, like the reads of a serial port
+const READ_SIZE: usize = 64;
+
+/// answer streams of 10 rotations
+fn streams() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("legacy", corpus::loop_answer(RPLIDAR_ANS_TYPE_MEASUREMENT, &corpus::legacy_nodes(10))),
+        ("capsuled", corpus::loop_answer(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &corpus::capsules(10))),
+        ("ultra_capsuled", corpus::loop_answer(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, &corpus::ultra_capsules(10))),
+        ("hq", corpus::loop_answer(RPLIDAR_ANS_TYPE_MEASUREMENT_HQ, &corpus::hq_capsules(10))),
+    ]
+}
+
+/// decode `stream` in reads of `READ_SIZE` bytes, returns the number of answers
+fn decode_owned(stream: &[u8]) -> usize {
+    let mut protocol = RplidarHostProtocol::new();
+    let mut answers = 0;
+
+    for chunk in stream.chunks(READ_SIZE) {
+        let mut pos = 0;
+        while pos < chunk.len() {
+            let (read, msg) = protocol.decode(&chunk[pos..]).unwrap();
+            answers += msg.is_some() as usize;
+            pos += read;
+        }
+    }
+    answers
+}
+
+/// decode `stream` without copying the answers out of the decoder
+fn decode_borrowed(stream: &[u8]) -> usize {
+    let mut protocol = RplidarHostProtocol::new();
+    let mut answers = 0;
+
+    for chunk in stream.chunks(READ_SIZE) {
+        let mut pos = 0;
+        while pos < chunk.len() {
+            let (read, completed) = protocol.decode_buffered(&chunk[pos..]).unwrap();
+            if completed {
+                answers += black_box(protocol.message()).is_some() as usize;
+            }
+            pos += read;
+        }
+    }
+    answers
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("host_protocol_decode");
+
+    for (name, stream) in streams().iter() {
+        assert!(decode_owned(stream) > 0 && decode_owned(stream) == decode_borrowed(stream));
+
+        group.throughput(Throughput::Bytes(stream.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", name), stream, |b, stream| b.iter(|| decode_owned(stream)));
+        group.bench_with_input(BenchmarkId::new("borrowed", name), stream, |b, stream| b.iter(|| decode_borrowed(stream)));
+    }
+
+    group.finish();
+}
+
+fn ring_byte_buffer(c: &mut Criterion) {
+    let stream = corpus::loop_answer(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &corpus::capsules(10));
+    let mut group = c.benchmark_group("ring_byte_buffer");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+
+    // the channel buffer, filled from the port and drained by the decoder
+    group.bench_function("read_from", |b| {
+        let mut buffer = RingByteBuffer::with_capacity(1024);
+        b.iter(|| {
+            let mut port = &stream[..];
+            while !port.is_empty() {
+                buffer.read_from(&mut port).unwrap();
+                let len = buffer.current_read_slice().len().min(READ_SIZE);
+                buffer.skip_bytes(black_box(len));
+            }
+            buffer.skip_bytes(buffer.len());
+        })
+    });
+
+    group.bench_function("write_read", |b| {
+        let mut buffer = RingByteBuffer::with_capacity(1024);
+        let mut out = [0u8; READ_SIZE];
+        b.iter(|| {
+            for chunk in stream.chunks(READ_SIZE) {
+                buffer.write_all(chunk).unwrap();
+                buffer.read_exact(&mut out[..chunk.len()]).unwrap();
+            }
+            black_box(&out);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode, ring_byte_buffer);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+//! Processing complete scans: `sort_scan`
+//!
+//! Run with `cargo bench --bench scan_processing`. Throughput is reported in scan points.
+
+#[macro_use]
+extern crate criterion;
+extern crate byteorder;
+extern crate crc;
+extern crate rplidar_drv;
+
+mod corpus;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+use rplidar_drv::utils::sort_scan;
+
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_scan");
+
+    // one rotation of the legacy, express and ultra/HQ scan modes at 10 Hz
+    for points in [corpus::LEGACY_SAMPLES, corpus::CAPSULED_SAMPLES, corpus::ULTRA_CAPSULED_SAMPLES].iter() {
+        let scan = corpus::scan(*points, 271.5);
+
+        group.throughput(Throughput::Elements(*points as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(points), &scan, |b, scan| {
+            b.iter_batched_ref(|| scan.clone(), |scan| sort_scan(scan).unwrap(), BatchSize::SmallInput)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, sort);
+criterion_main!(benches);
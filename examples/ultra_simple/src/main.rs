@@ -5,8 +5,8 @@ extern crate serialport;
 
 use hex_slice::AsHex;
 
-use rplidar_drv::{Health, RplidarDevice, RplidarHostProtocol};
-use rpos_drv::{Channel, RposError};
+use rplidar_drv::{Health, RplidarDevice};
+use rpos_drv::RposError;
 use serialport::prelude::*;
 use std::time::Duration;
 
@@ -42,12 +42,7 @@ fn main() {
         .write_data_terminal_ready(false)
         .expect("failed to clear DTR");
 
-    let channel = Channel::<RplidarHostProtocol, serialport::SerialPort>::new(
-        RplidarHostProtocol::new(),
-        serial_port,
-    );
-
-    let mut rplidar = RplidarDevice::new(channel);
+    let mut rplidar = RplidarDevice::<serialport::SerialPort>::new(serial_port);
 
     let device_info = rplidar
         .get_device_info()
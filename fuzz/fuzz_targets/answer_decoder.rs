@@ -0,0 +1,38 @@
+//! Arbitrary bytes, in arbitrary pieces, through the answer decoders
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rplidar_core::{AnswerDecoder, ArrayBuffer};
+use rplidar_drv::RplidarHostProtocol;
+use rpos_drv::ProtocolDecoder;
+
+fuzz_target!(|data: &[u8]| {
+    // the first byte chooses how the bytes are split, like reads of a serial port
+    let (piece_len, bytes) = match data.split_first() {
+        Some((len, bytes)) => ((*len as usize).max(1), bytes),
+        None => return,
+    };
+
+    let mut decoder = AnswerDecoder::new(ArrayBuffer::<256>::new());
+    let mut protocol = RplidarHostProtocol::new();
+
+    for piece in bytes.chunks(piece_len) {
+        let mut i = 0;
+        while i < piece.len() {
+            match decoder.decode(&piece[i..]) {
+                Ok((read, _)) => i += read,
+                // the decoder resets itself, skip the byte which caused it
+                Err(_) => i += 1,
+            }
+        }
+
+        let mut i = 0;
+        while i < piece.len() {
+            match protocol.decode(&piece[i..]) {
+                Ok((read, _)) => i += read.max(1),
+                Err(_) => i += 1,
+            }
+        }
+    }
+});
@@ -0,0 +1,35 @@
+//! Capsules with valid sync bits and checksum but arbitrary content through `parse_capsuled`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rplidar_core::answers::*;
+use rplidar_core::{Checksum, MeasurementDecoder};
+
+const CAPSULE_SIZE: usize = 84;
+
+/// make the capsule pass the sync and checksum checks
+fn seal(capsule: &mut [u8]) {
+    let mut checksum = Checksum::new();
+    checksum.push_slice(&capsule[2..]);
+    let checksum = checksum.checksum();
+
+    capsule[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum & 0xf);
+    capsule[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum >> 4);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = MeasurementDecoder::new();
+
+    for chunk in data.chunks_exact(CAPSULE_SIZE) {
+        let mut capsule = [0u8; CAPSULE_SIZE];
+        capsule.copy_from_slice(chunk);
+        seal(&mut capsule);
+
+        decoder
+            .decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &capsule, |point| {
+                let _ = (point.angle(), point.distance());
+            })
+            .unwrap();
+    }
+});
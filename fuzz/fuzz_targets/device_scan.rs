@@ -0,0 +1,51 @@
+//! Arbitrary bytes received by `RplidarDevice` while it is grabbing scans
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rplidar_drv::RplidarDevice;
+use rpos_drv::RposError;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// the fuzzed bytes as the received data, in reads of varying size
+struct FuzzedStream {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for FuzzedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.data.len() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        let len = buf.len().min(self.data.len() - self.pos).min(1 + self.data[self.pos] as usize % 64);
+        buf[..len].copy_from_slice(&self.data[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for FuzzedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let stream = FuzzedStream { data: data.to_vec(), pos: 0 };
+    let mut rplidar = RplidarDevice::with_stream(Box::new(stream));
+
+    // every byte is available immediately, a timeout means all of them are consumed
+    loop {
+        match rplidar.grab_scan_with_timeout(Duration::from_millis(1)) {
+            Err(err) if matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationTimeout)) => break,
+            _ => {}
+        }
+    }
+});
@@ -0,0 +1,32 @@
+//! Arbitrary measurement answers of every type through `MeasurementDecoder`
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rplidar_core::answers::*;
+use rplidar_core::MeasurementDecoder;
+
+const ANS_TYPES: [u8; 5] = [
+    RPLIDAR_ANS_TYPE_MEASUREMENT,
+    RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED,
+    0x83,
+    RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA,
+    RPLIDAR_ANS_TYPE_MEASUREMENT_HQ,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = MeasurementDecoder::new();
+    let mut rest = data;
+
+    // answers of `type index, length, data...`
+    while rest.len() >= 2 {
+        let ans_type = ANS_TYPES[rest[0] as usize % ANS_TYPES.len()];
+        let len = (rest[1] as usize).min(rest.len() - 2);
+        let answer = &rest[2..2 + len];
+        rest = &rest[2 + len..];
+
+        let _ = decoder.decode(ans_type, answer, |point| {
+            let _ = (point.angle(), point.distance(), point.is_valid());
+        });
+    }
+});
@@ -0,0 +1,109 @@
+use rplidar_drv::ScanPoint;
+
+/// Dot bits of a braille character, indexed by `[y][x]` inside the 2x4 cell
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// First braille character, without any dot
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// Text canvas drawing 2x4 dots per character with braille patterns
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrailleCanvas {
+    cols: usize,
+    rows: usize,
+    cells: Vec<u8>,
+}
+
+impl BrailleCanvas {
+    /// create an empty canvas of `cols` x `rows` characters
+    pub fn new(cols: usize, rows: usize) -> BrailleCanvas {
+        BrailleCanvas { cols, rows, cells: vec![0u8; cols * rows] }
+    }
+
+    /// width in dots
+    pub fn width(&self) -> usize {
+        self.cols * 2
+    }
+
+    /// height in dots
+    pub fn height(&self) -> usize {
+        self.rows * 4
+    }
+
+    /// set a dot, dots outside of the canvas are ignored
+    pub fn set(&mut self, x: i64, y: i64) {
+        if x < 0 || y < 0 || x >= self.width() as i64 || y >= self.height() as i64 {
+            return;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        self.cells[(y / 4) * self.cols + x / 2] |= BRAILLE_DOTS[y % 4][x % 2];
+    }
+
+    /// render the canvas, one string per row
+    pub fn lines(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.cols.max(1))
+            .take(self.rows)
+            .map(|row| {
+                row.iter()
+                    .map(|&dots| std::char::from_u32(BRAILLE_BLANK + dots as u32).unwrap_or(' '))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// plot a scan top-down with the front of the LIDAR up, `range` meters from the center to the nearest border
+pub fn plot_scan(points: &[ScanPoint], range: f32, cols: usize, rows: usize) -> Vec<String> {
+    let mut canvas = BrailleCanvas::new(cols, rows);
+    let (cx, cy) = ((canvas.width() / 2) as i64, (canvas.height() / 2) as i64);
+
+    // braille dots are about square in usual terminal fonts
+    let dots_per_meter = (canvas.width().min(canvas.height()) / 2) as f32 / range;
+
+    for point in points.iter().filter(|p| p.is_valid()) {
+        let (sin, cos) = point.angle().sin_cos();
+        let x = point.distance() * sin * dots_per_meter;
+        let y = point.distance() * cos * dots_per_meter;
+        canvas.set(cx + x.round() as i64, cy - y.round() as i64);
+    }
+
+    // the LIDAR, with a tick towards its front
+    for dy in 0..2 {
+        canvas.set(cx, cy - dy);
+    }
+
+    canvas.lines()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braille_dots() {
+        let mut canvas = BrailleCanvas::new(2, 1);
+        canvas.set(0, 0);
+        canvas.set(3, 3);
+        canvas.set(4, 0);
+        canvas.set(-1, 2);
+        assert_eq!(canvas.lines(), vec!["\u{2801}\u{2880}".to_owned()]);
+    }
+
+    #[test]
+    fn plot_front_and_right() {
+        let front = ScanPoint { angle_z_q14: 0, dist_mm_q2: 4000, quality: 47, flag: 0 };
+        let right = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 47, flag: 0 };
+
+        // 8x8 dots, center at (4, 4), 2 dots per meter
+        let mut expected = BrailleCanvas::new(4, 2);
+        for dy in 0..2 {
+            expected.set(4, 4 - dy);
+        }
+        expected.set(4, 2);
+        expected.set(6, 4);
+
+        assert_eq!(plot_scan(&[front, right], 2f32, 4, 2), expected.lines());
+    }
+}
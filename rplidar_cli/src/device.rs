@@ -0,0 +1,92 @@
+use rplidar_drv::{NetworkConfig, RplidarDevice};
+use rpos_drv::Result;
+use serialport::prelude::*;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Byte stream connected to a LIDAR, `Send` so the LIDAR can be driven from another thread
+pub trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send + ?Sized> Stream for T {}
+
+/// LIDAR connected through any stream
+pub type Lidar = RplidarDevice<dyn Stream>;
+
+/// Where the LIDAR is connected
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSpec {
+    /// Serial port path, e.g. `/dev/ttyUSB0` or `COM3`
+    Serial { path: String, baud_rate: u32 },
+
+    /// Network LIDAR or serial-to-TCP adapter, e.g. `udp://192.168.11.2:8089` or `tcp://192.168.11.2:20108`
+    Network(NetworkConfig),
+}
+
+impl DeviceSpec {
+    /// parse `udp://host[:port]`, `tcp://host[:port]`, `serial://path` or a plain serial port path
+    pub fn parse(device: &str, baud_rate: u32) -> Result<DeviceSpec> {
+        if device.starts_with("udp://") || device.starts_with("tcp://") {
+            Ok(DeviceSpec::Network(NetworkConfig::parse(device)?))
+        } else {
+            let path = device.strip_prefix("serial://").unwrap_or(device);
+            Ok(DeviceSpec::Serial { path: path.to_owned(), baud_rate })
+        }
+    }
+
+    /// open the stream
+    pub fn open_stream(&self) -> Result<Box<dyn Stream>> {
+        Ok(match self {
+            DeviceSpec::Serial { path, baud_rate } => Box::new(open_serial_port(path, *baud_rate)?),
+            DeviceSpec::Network(config) => Box::new(config.connect()?),
+        })
+    }
+
+    /// open the stream and create the driver
+    pub fn open(&self) -> Result<Lidar> {
+        Ok(RplidarDevice::with_stream(self.open_stream()?))
+    }
+}
+
+fn open_serial_port(path: &str, baud_rate: u32) -> Result<Box<dyn SerialPort>> {
+    let settings = SerialPortSettings {
+        baud_rate,
+        data_bits: DataBits::Eight,
+        flow_control: FlowControl::None,
+        parity: Parity::None,
+        stop_bits: StopBits::One,
+        timeout: Duration::from_millis(1),
+    };
+
+    let mut serial_port = serialport::open_with_settings(path, &settings)?;
+
+    // DTR starts the motor of A1 / A2 LIDARs without accessory board, pseudo terminals
+    // (e.g. `rplidar-sim`) have no DTR line
+    let _ = serial_port.write_data_terminal_ready(false);
+
+    Ok(serial_port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_device_spec() {
+        assert_eq!(
+            DeviceSpec::parse("/dev/ttyUSB0", 115200).unwrap(),
+            DeviceSpec::Serial { path: "/dev/ttyUSB0".to_owned(), baud_rate: 115200 }
+        );
+        assert_eq!(
+            DeviceSpec::parse("serial://COM3", 256000).unwrap(),
+            DeviceSpec::Serial { path: "COM3".to_owned(), baud_rate: 256000 }
+        );
+        assert_eq!(
+            DeviceSpec::parse("tcp://192.168.11.2:20108", 115200).unwrap(),
+            DeviceSpec::Network(NetworkConfig::tcp("192.168.11.2:20108").unwrap())
+        );
+        assert_eq!(
+            DeviceSpec::parse("udp://192.168.11.2", 115200).unwrap(),
+            DeviceSpec::Network(NetworkConfig::udp("192.168.11.2:8089").unwrap())
+        );
+    }
+}
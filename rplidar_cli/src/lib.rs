@@ -0,0 +1,13 @@
+//! Shared parts of the `rplidar` command-line tool and the `rplidar-view` terminal viewer
+
+extern crate clap;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+extern crate serde;
+extern crate serde_json;
+extern crate serialport;
+
+pub mod device;
+pub mod output;
+pub mod record;
+pub mod canvas;
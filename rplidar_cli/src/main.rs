@@ -0,0 +1,330 @@
+//! `rplidar` command-line tool
+//!
+//! ```text
+//! rplidar --device /dev/ttyUSB0 info
+//! rplidar --device tcp://192.168.11.2:20108 modes --json
+//! rplidar --device COM3 scan --mode 2 --count 10 --format json
+//! rplidar --device /dev/ttyUSB0 record scans.mcap --duration 60
+//! rplidar replay scans.jsonl --format csv
+//! ```
+
+extern crate clap;
+#[cfg(unix)]
+extern crate libc;
+extern crate rplidar_cli;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+extern crate serde_json;
+
+use clap::{ArgGroup, Parser, Subcommand};
+use rplidar_cli::device::{DeviceSpec, Lidar};
+use rplidar_cli::output::{AccessoryBoard, DeviceSummary, Format, ScanModes, ScanPrinter};
+use rplidar_cli::record::{read_recording, Recorder};
+use rplidar_drv::{ScanOptions, TimedScan};
+use rpos_drv::{Result, RposError};
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Command-line tool for Slamtec RPLIDAR
+#[derive(Debug, Parser)]
+#[command(name = "rplidar", version)]
+struct Cli {
+    /// LIDAR to connect: serial port path (e.g. /dev/ttyUSB0, COM3), udp://host[:port] or tcp://host[:port]
+    #[arg(short, long, global = true)]
+    device: Option<String>,
+
+    /// Baudrate of the serial port
+    #[arg(short, long, global = true, default_value_t = 115200)]
+    baudrate: u32,
+
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show device info, health and accessory board
+    Info,
+
+    /// List supported scan modes
+    Modes,
+
+    /// Print scans as CSV or JSON lines
+    Scan {
+        /// Scan mode id (typical scan mode by default)
+        #[arg(short, long)]
+        mode: Option<u16>,
+
+        /// Number of scans, 0 to scan until interrupted (Ctrl-C stops scanning and the motor)
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: usize,
+
+        /// Output format (JSON if --json is given, CSV otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+    },
+
+    /// Record scans into a .mcap file, or a JSON lines file for any other extension (only those can be replayed)
+    Record {
+        /// Output file
+        output: PathBuf,
+
+        /// Scan mode id (typical scan mode by default)
+        #[arg(short, long)]
+        mode: Option<u16>,
+
+        /// Number of scans, 0 for no limit (Ctrl-C ends the recording)
+        #[arg(short = 'n', long, default_value_t = 0)]
+        count: usize,
+
+        /// Stop recording after this many seconds
+        #[arg(long)]
+        duration: Option<f64>,
+    },
+
+    /// Print scans of a JSON lines recording, MCAP recordings are not supported
+    Replay {
+        /// JSON lines recording written by `record`
+        input: PathBuf,
+
+        /// Output format (JSON if --json is given, CSV otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+
+        /// Print scans at the pace they were recorded
+        #[arg(long)]
+        realtime: bool,
+    },
+
+    /// Set motor PWM (accessory board) or speed (LIDARs controlling the motor themselves)
+    #[command(group(ArgGroup::new("speed").required(true).args(["pwm", "rpm"])))]
+    Motor {
+        /// PWM duty, 0 stops the motor
+        #[arg(long)]
+        pwm: Option<u16>,
+
+        /// Rotations per minute, 0 stops the motor
+        #[arg(long)]
+        rpm: Option<u16>,
+    },
+
+    /// Reset the LIDAR core
+    Reset,
+}
+
+impl Cli {
+    fn open(&self) -> Result<Lidar> {
+        match self.device {
+            Some(ref device) => DeviceSpec::parse(device, self.baudrate)?.open(),
+            None => Err(RposError::OperationFail { description: "--device is required".to_owned() }.into()),
+        }
+    }
+
+    fn format(&self, format: Option<Format>) -> Format {
+        format.unwrap_or(if self.json { Format::Json } else { Format::Csv })
+    }
+}
+
+/// Set by SIGINT and SIGTERM, ends `grab_scans`
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// let Ctrl-C end `grab_scans`, which stops the scan and the motor before exiting
+#[cfg(unix)]
+fn stop_on_signals() {
+    extern "C" fn on_signal(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_on_signals() {}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(&cli) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    match cli.command {
+        Command::Info => info(cli),
+        Command::Modes => modes(cli),
+        Command::Scan { mode, count, format } => scan(cli, mode, count, cli.format(format)),
+        Command::Record { ref output, mode, count, duration } => record(cli, output, mode, count, duration),
+        Command::Replay { ref input, format, realtime } => replay(input, cli.format(format), realtime),
+        Command::Motor { pwm, rpm } => {
+            let mut lidar = cli.open()?;
+            match (pwm, rpm) {
+                (Some(pwm), _) => lidar.set_motor_pwm(pwm),
+                (_, Some(rpm)) => lidar.set_motor_speed(rpm),
+                _ => Ok(()),
+            }
+        }
+        Command::Reset => cli.open()?.core_reset(),
+    }
+}
+
+fn info(cli: &Cli) -> Result<()> {
+    let mut lidar = cli.open()?;
+    let info = lidar.get_device_info()?;
+    let health = lidar.get_device_health()?;
+
+    // LIDARs without accessory board don't answer
+    let accessory_board = match lidar.check_motor_ctrl_support() {
+        Ok(motor_control) => AccessoryBoard { detected: true, motor_control },
+        Err(_) => AccessoryBoard { detected: false, motor_control: false },
+    };
+
+    let summary = DeviceSummary::new(&info, health, accessory_board);
+
+    if cli.json {
+        serde_json::to_writer_pretty(stdout(), &summary)?;
+        println!();
+        Ok(())
+    } else {
+        summary.print_text(&mut stdout())
+    }
+}
+
+fn modes(cli: &Cli) -> Result<()> {
+    let mut lidar = cli.open()?;
+    let modes = ScanModes {
+        typical: lidar.get_typical_scan_mode()?,
+        modes: lidar.get_all_supported_scan_modes()?,
+    };
+
+    if cli.json {
+        serde_json::to_writer_pretty(stdout(), &modes)?;
+        println!();
+        Ok(())
+    } else {
+        modes.print_text(&mut stdout())
+    }
+}
+
+/// start the motor and scanning, then pass scans with their time since the start to `on_scan` until it returns false
+/// or the process is interrupted
+fn grab_scans<F>(lidar: &mut Lidar, mode: Option<u16>, mut on_scan: F) -> Result<()>
+where
+    F: FnMut(&TimedScan, f64) -> Result<bool>,
+{
+    let motor_ctrl = lidar.check_motor_ctrl_support().unwrap_or(false);
+    if motor_ctrl {
+        lidar.start_motor()?;
+    }
+
+    let options = match mode {
+        Some(mode) => ScanOptions::with_mode(mode),
+        None => ScanOptions::default(),
+    };
+    stop_on_signals();
+    lidar.start_scan_with_options(&options)?;
+
+    let started = Instant::now();
+    let result = loop {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+
+        let scan = match lidar.grab_timed_scan() {
+            Ok(scan) => scan,
+            Err(err) => match err.downcast_ref::<RposError>() {
+                Some(RposError::OperationTimeout) => continue,
+                _ => break Err(err),
+            },
+        };
+
+        let timestamp = scan.start_time().unwrap_or_else(Instant::now).saturating_duration_since(started);
+
+        match on_scan(&scan, timestamp.as_secs_f64()) {
+            Ok(true) => {}
+            Ok(false) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+
+    lidar.stop()?;
+    if motor_ctrl {
+        lidar.stop_motor()?;
+    }
+
+    result
+}
+
+fn scan(cli: &Cli, mode: Option<u16>, count: usize, format: Format) -> Result<()> {
+    let mut lidar = cli.open()?;
+    let mut printer = ScanPrinter::new(stdout(), format);
+    let mut scans = 0;
+
+    grab_scans(&mut lidar, mode, |scan, timestamp| {
+        printer.print_scan(&scan.points, timestamp)?;
+        scans += 1;
+        Ok(count == 0 || scans < count)
+    })
+}
+
+fn record(cli: &Cli, output: &Path, mode: Option<u16>, count: usize, duration: Option<f64>) -> Result<()> {
+    let mut lidar = cli.open()?;
+    let info = lidar.get_device_info()?;
+    let scan_mode_id = match mode {
+        Some(mode) => mode,
+        None => lidar.get_typical_scan_mode()?,
+    };
+    let scan_mode = lidar
+        .get_all_supported_scan_modes()?
+        .into_iter()
+        .find(|m| m.id == scan_mode_id)
+        .ok_or(RposError::OperationNotSupport)?;
+
+    let mut recorder = Recorder::create(output, &info, &scan_mode)?;
+    let mut scans = 0;
+
+    grab_scans(&mut lidar, Some(scan_mode_id), |scan, timestamp| {
+        recorder.write_scan(&scan.points, timestamp)?;
+        scans += 1;
+        eprint!("\r{} scans recorded", scans);
+
+        let enough_scans = count != 0 && scans >= count;
+        let enough_time = duration.is_some_and(|duration| timestamp >= duration);
+        Ok(!enough_scans && !enough_time)
+    })?;
+
+    eprintln!();
+    recorder.finish()
+}
+
+fn replay(input: &Path, format: Format, realtime: bool) -> Result<()> {
+    let scans = read_recording(input)?;
+    let mut printer = ScanPrinter::new(stdout(), format);
+    let started = Instant::now();
+
+    for scan in scans.iter() {
+        if realtime {
+            let due = Duration::from_secs_f64(scan.timestamp.max(0f64));
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                sleep(wait);
+            }
+        }
+
+        printer.print_scan(&scan.points, scan.timestamp)?;
+    }
+
+    stdout().flush()?;
+    Ok(())
+}
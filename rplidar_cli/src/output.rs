@@ -0,0 +1,201 @@
+use clap::ValueEnum;
+use rplidar_drv::{Health, RplidarResponseDeviceInfo, ScanMode, ScanPoint};
+use rpos_drv::Result;
+use serde::Serialize;
+use std::io::Write;
+
+/// Output format of scans
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    /// one line per point with a header line
+    Csv,
+
+    /// one JSON object per scan and line
+    Json,
+}
+
+/// Device information printed by `info`
+#[derive(Debug, Serialize)]
+pub struct DeviceSummary {
+    pub model: u8,
+    pub firmware_version: String,
+    pub hardware_version: u8,
+    pub serial_number: String,
+    pub health: Health,
+    pub accessory_board: AccessoryBoard,
+}
+
+/// Accessory board (A2 / A3 kits), detected if it answers
+#[derive(Debug, Serialize)]
+pub struct AccessoryBoard {
+    pub detected: bool,
+    pub motor_control: bool,
+}
+
+impl DeviceSummary {
+    pub fn new(info: &RplidarResponseDeviceInfo, health: Health, accessory_board: AccessoryBoard) -> DeviceSummary {
+        let firmware_version = info.firmware_version;
+        let serial_number = info.serialnum;
+
+        DeviceSummary {
+            model: info.model,
+            firmware_version: format!("{}.{:02}", firmware_version >> 8, firmware_version & 0xff),
+            hardware_version: info.hardware_version,
+            serial_number: serial_number.iter().map(|b| format!("{:02X}", b)).collect(),
+            health,
+            accessory_board,
+        }
+    }
+
+    pub fn print_text<W: Write>(&self, dest: &mut W) -> Result<()> {
+        writeln!(dest, "Model:            {}", self.model)?;
+        writeln!(dest, "Firmware Version: {}", self.firmware_version)?;
+        writeln!(dest, "Hardware Version: {}", self.hardware_version)?;
+        writeln!(dest, "Serial Number:    {}", self.serial_number)?;
+
+        match self.health {
+            Health::Healthy => writeln!(dest, "Health:           healthy")?,
+            Health::Warning(code) => writeln!(dest, "Health:           warning {:04X}", code)?,
+            Health::Error(code) => writeln!(dest, "Health:           error {:04X}", code)?,
+        }
+
+        let board = match (self.accessory_board.detected, self.accessory_board.motor_control) {
+            (false, _) => "not detected",
+            (true, false) => "detected, without motor control",
+            (true, true) => "detected, with motor control",
+        };
+        writeln!(dest, "Accessory Board:  {}", board)?;
+        Ok(())
+    }
+}
+
+/// Scan modes printed by `modes`
+#[derive(Debug, Serialize)]
+pub struct ScanModes {
+    pub typical: u16,
+    pub modes: Vec<ScanMode>,
+}
+
+impl ScanModes {
+    pub fn print_text<W: Write>(&self, dest: &mut W) -> Result<()> {
+        writeln!(dest, "{:>3}  {:16} {:>12} {:>10} {:>8}", "id", "name", "max distance", "us/sample", "ans type")?;
+
+        for mode in self.modes.iter() {
+            writeln!(
+                dest,
+                "{:>3}{} {:16} {:>11.2}m {:>10.2} {:>8}",
+                mode.id,
+                if mode.id == self.typical { '*' } else { ' ' },
+                mode.name,
+                mode.max_distance,
+                mode.us_per_sample,
+                format!("0x{:02X}", mode.ans_type)
+            )?;
+        }
+
+        writeln!(dest, "* typical scan mode")?;
+        Ok(())
+    }
+}
+
+/// A scan point as printed, angle in degrees and distance in meters
+#[derive(Debug, Serialize)]
+struct PointRecord {
+    angle: f32,
+    distance: f32,
+    quality: u8,
+    sync: bool,
+}
+
+impl From<&ScanPoint> for PointRecord {
+    fn from(point: &ScanPoint) -> PointRecord {
+        PointRecord {
+            angle: point.angle().to_degrees(),
+            distance: point.distance(),
+            quality: point.quality,
+            sync: point.is_sync(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ScanRecord {
+    scan: usize,
+    timestamp: f64,
+    points: Vec<PointRecord>,
+}
+
+/// Writes scans as CSV or JSON lines
+#[derive(Debug)]
+pub struct ScanPrinter<W: Write> {
+    dest: W,
+    format: Format,
+    scans: usize,
+}
+
+impl<W: Write> ScanPrinter<W> {
+    pub fn new(dest: W, format: Format) -> ScanPrinter<W> {
+        ScanPrinter { dest, format, scans: 0 }
+    }
+
+    /// print a scan taken `timestamp` seconds after the start
+    pub fn print_scan(&mut self, points: &[ScanPoint], timestamp: f64) -> Result<()> {
+        let scan = self.scans;
+        self.scans += 1;
+
+        match self.format {
+            Format::Csv => {
+                if scan == 0 {
+                    writeln!(self.dest, "scan,timestamp,angle,distance,quality,sync")?;
+                }
+
+                for point in points.iter().map(PointRecord::from) {
+                    writeln!(
+                        self.dest,
+                        "{},{:.6},{:.4},{:.4},{},{}",
+                        scan, timestamp, point.angle, point.distance, point.quality, point.sync as u8
+                    )?;
+                }
+            }
+            Format::Json => {
+                let record = ScanRecord {
+                    scan,
+                    timestamp,
+                    points: points.iter().map(PointRecord::from).collect(),
+                };
+                serde_json::to_writer(&mut self.dest, &record)?;
+                writeln!(self.dest)?;
+            }
+        }
+
+        self.dest.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_scans() {
+        let points = [ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 47, flag: 1 }];
+
+        let mut csv = ScanPrinter::new(Vec::new(), Format::Csv);
+        csv.print_scan(&points, 0.0).unwrap();
+        csv.print_scan(&points, 0.1).unwrap();
+        assert_eq!(
+            String::from_utf8(csv.dest).unwrap(),
+            "scan,timestamp,angle,distance,quality,sync\n\
+             0,0.000000,90.0000,1.0000,47,1\n\
+             1,0.100000,90.0000,1.0000,47,1\n"
+        );
+
+        let mut json = ScanPrinter::new(Vec::new(), Format::Json);
+        json.print_scan(&points, 0.5).unwrap();
+        assert_eq!(
+            String::from_utf8(json.dest).unwrap(),
+            "{\"scan\":0,\"timestamp\":0.5,\"points\":[{\"angle\":90.0,\"distance\":1.0,\"quality\":47,\"sync\":true}]}\n"
+        );
+    }
+}
@@ -0,0 +1,687 @@
+use rplidar_cli::device::Stream;
+use rplidar_drv::rplidar_core::answers::*;
+use rplidar_drv::rplidar_core::cmds::*;
+use rplidar_drv::rplidar_core::{encode_answer_header, CommandDecoder, RPLIDAR_ANS_HEADER_LEN};
+use rplidar_drv::{RplidarHostProtocol, RposError};
+use rpos_drv::{Channel, Message, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Timeout of queries forwarded to the LIDAR
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long to wait for measurements before serving clients again
+const SCAN_READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// How long to wait for client commands while not scanning
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Clients not reading measurements for this long are disconnected
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Answers and measurements queued per client, clients falling further behind are disconnected
+const CLIENT_QUEUE_DEPTH: usize = 1024;
+
+pub type ClientId = usize;
+
+/// Events from the client threads to the hub
+#[derive(Debug)]
+pub enum Event {
+    Connected(ClientId, TcpStream),
+    Command(ClientId, Request),
+    Disconnected(ClientId),
+}
+
+/// A command as sent by a client
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Request {
+    pub cmd: u8,
+    pub payload: Vec<u8>,
+}
+
+impl Request {
+    pub fn new(cmd: u8, payload: &[u8]) -> Request {
+        Request { cmd, payload: payload.to_vec() }
+    }
+
+    fn to_message(&self) -> Message {
+        Message::with_data(self.cmd, &self.payload)
+    }
+}
+
+/// Bytes sent to clients, measurements are shared by all subscribed clients
+type Bytes = Arc<Vec<u8>>;
+
+/// A client, written to by its own thread so a slow client doesn't hold up the others
+#[derive(Debug)]
+struct Client {
+    queue: SyncSender<Bytes>,
+    subscribed: bool,
+    header_sent: bool,
+}
+
+fn is_measurement(ans_type: u8) -> bool {
+    matches!(
+        ans_type,
+        RPLIDAR_ANS_TYPE_MEASUREMENT
+            | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED
+            | RPLIDAR_ANS_TYPE_MEASUREMENT_HQ
+            | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA
+    )
+}
+
+fn is_query(cmd: u8) -> bool {
+    matches!(
+        cmd,
+        RPLIDAR_CMD_GET_DEVICE_INFO | RPLIDAR_CMD_GET_DEVICE_HEALTH | RPLIDAR_CMD_GET_LIDAR_CONF | RPLIDAR_CMD_GET_ACC_BOARD_FLAG
+    )
+}
+
+fn is_scan(cmd: u8) -> bool {
+    matches!(cmd, RPLIDAR_CMD_SCAN | RPLIDAR_CMD_FORCE_SCAN | RPLIDAR_CMD_EXPRESS_SCAN)
+}
+
+/// commands the LIDAR doesn't answer
+fn is_unanswered(cmd: u8) -> bool {
+    matches!(cmd, RPLIDAR_CMD_STOP | RPLIDAR_CMD_RESET | RPLIDAR_CMD_SET_MOTOR_PWM | RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL)
+}
+
+/// Owns the LIDAR and serves the commands of all clients one after another
+///
+/// * queries are forwarded while the LIDAR is idle, and answered from the answers seen
+///   before while it is scanning, as the LIDAR doesn't answer them while scanning
+/// * the first scan request starts scanning, later clients requesting the same scan
+///   receive the same measurements, other scan requests are refused
+/// * stop requests only unsubscribe the client, the LIDAR stops with the last one
+/// * other commands (motor, reset, sample rate) are refused while other clients are scanning,
+///   answered ones are forwarded like queries but their answers are not kept
+///
+/// Refused commands are not answered, like commands the LIDAR doesn't support.
+pub struct Hub {
+    channel: Channel<RplidarHostProtocol, dyn Stream>,
+    clients: HashMap<ClientId, Client>,
+    scan: Option<Request>,
+    answers: HashMap<Request, Message>,
+}
+
+impl Hub {
+    pub fn new(stream: Box<dyn Stream>) -> Hub {
+        Hub {
+            channel: Channel::new(RplidarHostProtocol::new(), stream),
+            clients: HashMap::new(),
+            scan: None,
+            answers: HashMap::new(),
+        }
+    }
+
+    /// query the device info, health and scan modes, so they can be answered while scanning
+    pub fn warm_up(&mut self) -> Result<()> {
+        let info = self
+            .query(&Request::new(RPLIDAR_CMD_GET_DEVICE_INFO, &[]))?
+            .ok_or(RposError::OperationTimeout)?;
+        self.query(&Request::new(RPLIDAR_CMD_GET_DEVICE_HEALTH, &[]))?;
+        self.query(&Request::new(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &[0; 4]))?;
+
+        let firmware_version = if info.data.len() >= 3 { LittleEndian::read_u16(&info.data[1..3]) } else { 0 };
+        if firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
+            return Ok(());
+        }
+
+        self.query(&conf_request(RPLIDAR_CONF_SCAN_MODE_TYPICAL, None))?;
+        let count = match self.query(&conf_request(RPLIDAR_CONF_SCAN_MODE_COUNT, None))? {
+            Some(answer) if answer.data.len() == 6 => LittleEndian::read_u16(&answer.data[4..6]),
+            _ => 0,
+        };
+
+        for mode in 0..count {
+            for &config_type in [
+                RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE,
+                RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE,
+                RPLIDAR_CONF_SCAN_MODE_ANS_TYPE,
+                RPLIDAR_CONF_SCAN_MODE_NAME,
+            ]
+            .iter()
+            {
+                self.query(&conf_request(config_type, Some(mode)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// serve events until all senders are gone, or the LIDAR fails
+    pub fn run(mut self, events: Receiver<Event>) -> Result<()> {
+        loop {
+            let timeout = if self.scan.is_some() { Duration::from_millis(0) } else { IDLE_POLL_INTERVAL };
+
+            match events.recv_timeout(timeout) {
+                Ok(event) => self.handle(event)?,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+
+            while let Ok(event) = events.try_recv() {
+                self.handle(event)?;
+            }
+
+            if self.scan.is_some() {
+                if let Some(message) = self.read(SCAN_READ_TIMEOUT)? {
+                    if is_measurement(message.cmd) {
+                        self.fan_out(&message)?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::Connected(id, stream) => {
+                stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))?;
+                let (queue, bytes) = sync_channel(CLIENT_QUEUE_DEPTH);
+                thread::spawn(move || write_to_client(stream, bytes));
+
+                self.clients.insert(id, Client { queue, subscribed: false, header_sent: false });
+                Ok(())
+            }
+            Event::Command(id, request) => self.on_command(id, request),
+            Event::Disconnected(id) => self.disconnect(id),
+        }
+    }
+
+    fn on_command(&mut self, id: ClientId, request: Request) -> Result<()> {
+        if !self.clients.contains_key(&id) {
+            return Ok(());
+        }
+
+        if is_query(request.cmd) {
+            match self.query(&request)? {
+                Some(answer) => self.reply(id, &answer)?,
+                None => refuse(id, &request, "no answer known while scanning"),
+            }
+            return Ok(());
+        }
+
+        let others_scanning = self.clients.iter().any(|(&other, client)| other != id && client.subscribed);
+
+        if is_scan(request.cmd) {
+            if self.scan.as_ref() == Some(&request) {
+                self.subscribe(id);
+            } else if others_scanning {
+                refuse(id, &request, "another scan is running");
+            } else {
+                self.start_scan(request)?;
+                self.subscribe(id);
+            }
+        } else if request.cmd == RPLIDAR_CMD_STOP {
+            if others_scanning {
+                self.unsubscribe(id)?;
+            } else {
+                self.stop_scan()?;
+            }
+        } else if others_scanning {
+            refuse(id, &request, "other clients are scanning");
+        } else if is_unanswered(request.cmd) {
+            self.channel.write(&request.to_message())?;
+
+            if request.cmd == RPLIDAR_CMD_RESET {
+                self.scan = None;
+                self.answers.clear();
+                self.clients.values_mut().for_each(|client| client.subscribed = false);
+            }
+        } else if self.scan.is_some() {
+            refuse(id, &request, "not answered while scanning");
+        } else {
+            match self.forward(&request)? {
+                Some(answer) => self.reply(id, &answer)?,
+                None => refuse(id, &request, "no answer from the LIDAR"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the answer of a query, from the LIDAR if it isn't scanning
+    fn query(&mut self, request: &Request) -> Result<Option<Message>> {
+        if self.scan.is_some() {
+            return Ok(self.answers.get(request).cloned());
+        }
+
+        let answer = self.forward(request)?;
+        if let Some(ref answer) = answer {
+            self.answers.insert(request.clone(), answer.clone());
+        }
+
+        Ok(answer)
+    }
+
+    /// send a command to the idle LIDAR and wait for its answer
+    fn forward(&mut self, request: &Request) -> Result<Option<Message>> {
+        // drop answers of commands nobody waits for
+        self.channel.reset();
+        while self.read(Duration::from_millis(0))?.is_some() {}
+
+        self.channel.write(&request.to_message())?;
+
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            match self.read(timeout)? {
+                Some(answer) if !is_measurement(answer.cmd) => return Ok(Some(answer)),
+                Some(_) => {}
+                None => break,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// read a message, `None` on timeout
+    fn read(&mut self, timeout: Duration) -> Result<Option<Message>> {
+        if timeout == Duration::from_millis(0) {
+            return self.channel.read();
+        }
+
+        match self.channel.read_until(timeout) {
+            Err(err) => match err.downcast_ref::<RposError>() {
+                Some(RposError::OperationTimeout) => Ok(None),
+                _ => Err(err),
+            },
+            result => result,
+        }
+    }
+
+    fn start_scan(&mut self, request: Request) -> Result<()> {
+        self.channel.write(&request.to_message())?;
+        self.channel.reset();
+        self.scan = Some(request);
+
+        // the answer header of the new scan
+        self.clients.values_mut().for_each(|client| client.header_sent = false);
+        Ok(())
+    }
+
+    fn stop_scan(&mut self) -> Result<()> {
+        self.channel.write(&Message::new(RPLIDAR_CMD_STOP))?;
+        self.scan = None;
+        self.clients.values_mut().for_each(|client| client.subscribed = false);
+        Ok(())
+    }
+
+    fn subscribe(&mut self, id: ClientId) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            if !client.subscribed {
+                client.subscribed = true;
+                client.header_sent = false;
+            }
+        }
+    }
+
+    /// unsubscribe a client, the scan stops with the last one
+    fn unsubscribe(&mut self, id: ClientId) -> Result<()> {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.subscribed = false;
+        }
+
+        if self.scan.is_some() && !self.clients.values().any(|client| client.subscribed) {
+            self.stop_scan()?;
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, id: ClientId) -> Result<()> {
+        self.unsubscribe(id)?;
+        self.clients.remove(&id);
+        Ok(())
+    }
+
+    fn reply(&mut self, id: ClientId, answer: &Message) -> Result<()> {
+        let mut bytes = vec![0u8; RPLIDAR_ANS_HEADER_LEN];
+        encode_answer_header(answer.cmd, answer.data.len(), false, &mut bytes).map_err(protocol_error)?;
+        bytes.extend_from_slice(&answer.data);
+
+        let sent = match self.clients.get(&id) {
+            Some(client) => client.queue.try_send(Arc::new(bytes)).is_ok(),
+            None => true,
+        };
+
+        if !sent {
+            eprintln!("client {}: not reading, disconnected", id);
+            self.disconnect(id)?;
+        }
+        Ok(())
+    }
+
+    fn fan_out(&mut self, measurement: &Message) -> Result<()> {
+        let mut header = vec![0u8; RPLIDAR_ANS_HEADER_LEN];
+        encode_answer_header(measurement.cmd, measurement.data.len(), true, &mut header).map_err(protocol_error)?;
+
+        let header = Arc::new(header);
+        let data = Arc::new(measurement.data.clone());
+        let mut failed = Vec::new();
+
+        for (&id, client) in self.clients.iter_mut().filter(|(_, client)| client.subscribed) {
+            let sent = if client.header_sent {
+                client.queue.try_send(data.clone())
+            } else {
+                client.header_sent = true;
+                client.queue.try_send(header.clone()).and_then(|_| client.queue.try_send(data.clone()))
+            };
+
+            if let Err(err) = sent {
+                if let TrySendError::Full(_) = err {
+                    eprintln!("client {}: not reading, disconnected", id);
+                }
+                failed.push(id);
+            }
+        }
+
+        for id in failed {
+            self.disconnect(id)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// write the queued bytes to a client until the hub drops it or writing fails
+fn write_to_client(mut stream: TcpStream, bytes: Receiver<Bytes>) {
+    for bytes in bytes.iter() {
+        if stream.write_all(&bytes).is_err() {
+            break;
+        }
+    }
+
+    // also ends the thread reading the commands of the client
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn conf_request(config_type: u32, mode: Option<u16>) -> Request {
+    let mut payload = vec![0u8; 4];
+    LittleEndian::write_u32(&mut payload, config_type);
+
+    if let Some(mode) = mode {
+        let mut param = [0u8; 2];
+        LittleEndian::write_u16(&mut param, mode);
+        payload.extend_from_slice(&param);
+    }
+
+    Request { cmd: RPLIDAR_CMD_GET_LIDAR_CONF, payload }
+}
+
+fn refuse(id: ClientId, request: &Request, reason: &str) {
+    eprintln!("client {}: refused command 0x{:02X}, {}", id, request.cmd, reason);
+}
+
+fn protocol_error(err: rplidar_drv::rplidar_core::Error) -> rpos_drv::Error {
+    RposError::ProtocolError { description: err.to_string() }.into()
+}
+
+/// accept clients, each served by its own thread
+pub fn accept_clients(listener: TcpListener, events: Sender<Event>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        let writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => continue,
+        };
+
+        let _ = stream.set_nodelay(true);
+        if events.send(Event::Connected(id, writer)).is_err() {
+            return;
+        }
+
+        let events = events.clone();
+        thread::spawn(move || read_commands(id, stream, events));
+    }
+}
+
+/// decode the commands of a client until it disconnects
+fn read_commands(id: ClientId, mut stream: TcpStream, events: Sender<Event>) {
+    let mut decoder = CommandDecoder::new();
+    let mut buf = [0u8; 256];
+
+    while let Ok(len) = stream.read(&mut buf) {
+        if len == 0 {
+            break;
+        }
+
+        let mut i = 0;
+        while i < len {
+            let (read, command) = decoder.decode(&buf[i..len]);
+            i += read;
+
+            if let Some(command) = command {
+                if events.send(Event::Command(id, Request::new(command.cmd, command.payload))).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    let _ = events.send(Event::Disconnected(id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rplidar_drv::{RplidarDevice, ScanOptions, TcpTransport};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::mpsc::channel;
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct FakeState {
+        scanning: AtomicBool,
+        scan_requests: AtomicUsize,
+        motor_stops: AtomicUsize,
+    }
+
+    fn answer(ans_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0u8; RPLIDAR_ANS_HEADER_LEN];
+        encode_answer_header(ans_type, data.len(), false, &mut bytes).unwrap();
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn conf_answer(payload: &[u8]) -> Vec<u8> {
+        let mut data = payload[0..4].to_vec();
+        match LittleEndian::read_u32(&payload[0..4]) {
+            RPLIDAR_CONF_SCAN_MODE_COUNT => data.extend_from_slice(&[1, 0]),
+            RPLIDAR_CONF_SCAN_MODE_TYPICAL => data.extend_from_slice(&[0, 0]),
+            RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE => data.extend_from_slice(&(500u32 << 8).to_le_bytes()),
+            RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE => data.extend_from_slice(&(12u32 << 8).to_le_bytes()),
+            RPLIDAR_CONF_SCAN_MODE_ANS_TYPE => data.push(RPLIDAR_ANS_TYPE_MEASUREMENT),
+            _ => data.extend_from_slice(b"Standard\0"),
+        }
+        answer(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, &data)
+    }
+
+    /// a revolution of 36 legacy measurement nodes at 1 m
+    fn revolution() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for i in 0..36u16 {
+            let sync = if i == 0 { 0b01 } else { 0b10 };
+            bytes.push((47 << 2) | sync);
+            bytes.extend_from_slice(&(((i * 10) << 7) | 1).to_le_bytes());
+            bytes.extend_from_slice(&(4000u16).to_le_bytes());
+        }
+        bytes
+    }
+
+    /// LIDAR with one scan mode, streaming a revolution every millisecond while scanning
+    fn fake_lidar(listener: TcpListener, state: Arc<FakeState>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+
+        let mut decoder = CommandDecoder::new();
+        let mut buf = [0u8; 256];
+
+        loop {
+            let len = match stream.read(&mut buf) {
+                Ok(0) => return,
+                Ok(len) => len,
+                Err(_) => 0,
+            };
+
+            let mut i = 0;
+            while i < len {
+                let (read, command) = decoder.decode(&buf[i..len]);
+                i += read;
+
+                let reply = match command.map(|c| (c.cmd, c.payload.to_vec())) {
+                    Some((RPLIDAR_CMD_GET_DEVICE_INFO, _)) => {
+                        let mut info = vec![0x18, 0x1D, 0x01, 5];
+                        info.extend_from_slice(&[0xAB; 16]);
+                        answer(RPLIDAR_ANS_TYPE_DEVINFO, &info)
+                    }
+                    Some((RPLIDAR_CMD_GET_DEVICE_HEALTH, _)) => answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[0, 0, 0]),
+                    Some((RPLIDAR_CMD_GET_ACC_BOARD_FLAG, _)) => answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &[1, 0, 0, 0]),
+                    Some((RPLIDAR_CMD_GET_SAMPLERATE, _)) => answer(RPLIDAR_ANS_TYPE_SAMPLE_RATE, &[0xF4, 0x01, 0xFA, 0x00]),
+                    Some((RPLIDAR_CMD_GET_LIDAR_CONF, payload)) => conf_answer(&payload),
+                    Some((RPLIDAR_CMD_SCAN, _)) | Some((RPLIDAR_CMD_FORCE_SCAN, _)) => {
+                        state.scan_requests.fetch_add(1, Ordering::SeqCst);
+                        state.scanning.store(true, Ordering::SeqCst);
+                        let mut header = vec![0u8; RPLIDAR_ANS_HEADER_LEN];
+                        encode_answer_header(RPLIDAR_ANS_TYPE_MEASUREMENT, 5, true, &mut header).unwrap();
+                        header
+                    }
+                    Some((RPLIDAR_CMD_STOP, _)) => {
+                        state.scanning.store(false, Ordering::SeqCst);
+                        Vec::new()
+                    }
+                    Some((RPLIDAR_CMD_SET_MOTOR_PWM, payload)) if payload == [0, 0] => {
+                        state.motor_stops.fetch_add(1, Ordering::SeqCst);
+                        Vec::new()
+                    }
+                    _ => Vec::new(),
+                };
+                stream.write_all(&reply).unwrap();
+            }
+
+            if state.scanning.load(Ordering::SeqCst) {
+                stream.write_all(&revolution()).unwrap();
+            }
+        }
+    }
+
+    fn connect(addr: std::net::SocketAddr) -> RplidarDevice<TcpTransport> {
+        RplidarDevice::with_stream(Box::new(TcpTransport::connect(addr).unwrap()))
+    }
+
+    /// points of a full revolution, the first scan may start anywhere
+    fn grab_full_scan(client: &mut RplidarDevice<TcpTransport>) -> usize {
+        client.grab_scan().unwrap();
+        client.grab_scan().unwrap().len()
+    }
+
+    /// proxy of a fake LIDAR, returns its address
+    fn start_proxy(state: Arc<FakeState>) -> std::net::SocketAddr {
+        let lidar_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let lidar_addr = lidar_listener.local_addr().unwrap();
+        thread::spawn(move || fake_lidar(lidar_listener, state));
+
+        let mut hub = Hub::new(Box::new(TcpTransport::connect(lidar_addr).unwrap()));
+        hub.warm_up().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let (events, event_rx) = channel();
+        thread::spawn(move || accept_clients(listener, events));
+        thread::spawn(move || hub.run(event_rx));
+        proxy_addr
+    }
+
+    #[test]
+    fn share_lidar() {
+        let state = Arc::new(FakeState::default());
+        let proxy_addr = start_proxy(state.clone());
+
+        let mut navigation = connect(proxy_addr);
+        let mut logging = connect(proxy_addr);
+
+        assert_eq!({ navigation.get_device_info().unwrap().model }, 0x18);
+        assert_eq!(navigation.start_scan().unwrap().name, "Standard");
+        assert_eq!(grab_full_scan(&mut navigation), 36);
+
+        // answered while scanning
+        assert_eq!({ logging.get_device_info().unwrap().serialnum }, [0xAB; 16]);
+        assert_eq!(logging.start_scan().unwrap().id, 0);
+        assert_eq!(grab_full_scan(&mut logging), 36);
+
+        // refused while others are scanning
+        let mut safety = connect(proxy_addr);
+        safety.stop_motor().unwrap();
+        safety
+            .start_scan_with_options(&ScanOptions { force_scan: true, ..ScanOptions::with_mode(0) })
+            .unwrap();
+
+        // only unsubscribes
+        logging.stop().unwrap();
+
+        assert_eq!(grab_full_scan(&mut navigation), 36);
+        assert_eq!(state.scan_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(state.motor_stops.load(Ordering::SeqCst), 0);
+        assert!(state.scanning.load(Ordering::SeqCst));
+
+        // the last client stops the LIDAR
+        navigation.stop().unwrap();
+        navigation.stop_motor().unwrap();
+
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        while state.motor_stops.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!state.scanning.load(Ordering::SeqCst));
+        assert_eq!(state.motor_stops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn relay_answers_of_other_commands() {
+        let proxy_addr = start_proxy(Arc::new(FakeState::default()));
+
+        let mut client = TcpStream::connect(proxy_addr).unwrap();
+        client.set_read_timeout(Some(QUERY_TIMEOUT)).unwrap();
+        client.write_all(&[0xA5, RPLIDAR_CMD_GET_SAMPLERATE]).unwrap();
+
+        let mut sample_rate = [0u8; RPLIDAR_ANS_HEADER_LEN + 4];
+        client.read_exact(&mut sample_rate).unwrap();
+        assert_eq!(sample_rate[..], answer(RPLIDAR_ANS_TYPE_SAMPLE_RATE, &[0xF4, 0x01, 0xFA, 0x00])[..]);
+
+        // queries are still answered afterwards
+        let mut rplidar = connect(proxy_addr);
+        assert_eq!({ rplidar.get_device_info().unwrap().model }, 0x18);
+    }
+
+    #[test]
+    fn stalled_client_does_not_block() {
+        let mut hub = Hub::new(Box::new(std::io::Cursor::new(Vec::new())));
+
+        // a client which never reads
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _stalled = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        hub.handle(Event::Connected(0, stream)).unwrap();
+        hub.subscribe(0);
+
+        // more than the socket buffers and the queue hold
+        let measurement = Message::with_data(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &[0u8; 16 * 1024]);
+        let started = Instant::now();
+        for _ in 0..4 * CLIENT_QUEUE_DEPTH {
+            hub.fan_out(&measurement).unwrap();
+        }
+
+        assert!(started.elapsed() < CLIENT_WRITE_TIMEOUT, "fan out took {:?}", started.elapsed());
+        assert!(!hub.clients.contains_key(&0));
+    }
+}
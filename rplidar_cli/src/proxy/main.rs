@@ -0,0 +1,68 @@
+//! `rplidar-proxy` shares one LIDAR with several processes
+//!
+//! Owns the LIDAR and serves the RPLIDAR protocol over TCP, so every client connects
+//! with `TcpTransport` as if it was the only one:
+//!
+//! ```text
+//! rplidar-proxy --device /dev/ttyUSB0 --listen 127.0.0.1:20108
+//! rplidar --device tcp://127.0.0.1:20108 scan
+//! ```
+//!
+//! Clients requesting the running scan share its measurements, conflicting scan modes
+//! and motor commands are refused while other clients are scanning.
+
+extern crate byteorder;
+extern crate clap;
+extern crate rplidar_cli;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+
+mod hub;
+
+use clap::Parser;
+use hub::{accept_clients, Hub};
+use rplidar_cli::device::DeviceSpec;
+use rpos_drv::Result;
+use std::net::TcpListener;
+use std::process::exit;
+use std::sync::mpsc::channel;
+use std::thread;
+
+/// Shares one Slamtec RPLIDAR with several clients over TCP
+#[derive(Debug, Parser)]
+#[command(name = "rplidar-proxy", version)]
+struct Cli {
+    /// LIDAR to share: serial port path (e.g. /dev/ttyUSB0, COM3), udp://host[:port] or tcp://host[:port]
+    #[arg(short, long)]
+    device: String,
+
+    /// Baudrate of the serial port
+    #[arg(short, long, default_value_t = 115200)]
+    baudrate: u32,
+
+    /// Address to accept clients on
+    #[arg(short, long, default_value = "127.0.0.1:20108")]
+    listen: String,
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let mut hub = Hub::new(DeviceSpec::parse(&cli.device, cli.baudrate)?.open_stream()?);
+    hub.warm_up()?;
+
+    let listener = TcpListener::bind(cli.listen.as_str())?;
+    eprintln!("serving {} on {}", cli.device, listener.local_addr()?);
+
+    let (events, event_rx) = channel();
+    thread::spawn(move || accept_clients(listener, events));
+
+    hub.run(event_rx)
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(&cli) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
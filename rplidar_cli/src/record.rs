@@ -0,0 +1,125 @@
+use rplidar_drv::mcap::McapWriter;
+use rplidar_drv::{RplidarResponseDeviceInfo, ScanMode, ScanPoint};
+use rpos_drv::{Result, RposError};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Scan stored in JSON lines recordings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedScan {
+    /// seconds since the start of the recording
+    pub timestamp: f64,
+
+    /// points of the scan
+    pub points: Vec<ScanPoint>,
+}
+
+/// Writes scans into a `.mcap` file, or a JSON lines file for any other extension
+pub enum Recorder {
+    JsonLines(BufWriter<File>),
+    Mcap(McapWriter<BufWriter<File>>, u64),
+}
+
+fn is_mcap(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mcap"))
+}
+
+impl Recorder {
+    pub fn create(path: &Path, info: &RplidarResponseDeviceInfo, mode: &ScanMode) -> Result<Recorder> {
+        let dest = BufWriter::new(File::create(path)?);
+
+        if is_mcap(path) {
+            let mut writer = McapWriter::new(dest, "/scan", "laser")?;
+            writer.write_device_info(info)?;
+            writer.write_scan_mode(mode)?;
+
+            let started_ns = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+            Ok(Recorder::Mcap(writer, started_ns))
+        } else {
+            Ok(Recorder::JsonLines(dest))
+        }
+    }
+
+    /// write a scan taken `timestamp` seconds after the start of the recording
+    pub fn write_scan(&mut self, points: &[ScanPoint], timestamp: f64) -> Result<()> {
+        match self {
+            Recorder::JsonLines(dest) => {
+                let scan = RecordedScan { timestamp, points: points.to_vec() };
+                serde_json::to_writer(&mut *dest, &scan)?;
+                writeln!(dest)?;
+                dest.flush()?;
+            }
+            Recorder::Mcap(writer, started_ns) => {
+                writer.write_scan(points, *started_ns + (timestamp * 1e9) as u64)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Recorder::JsonLines(mut dest) => dest.flush()?,
+            Recorder::Mcap(writer, _) => writer.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// read the scans of a JSON lines recording
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedScan>> {
+    if is_mcap(path) {
+        return Err(RposError::OperationFail {
+            description: format!(
+                "{}: MCAP recordings can't be replayed, open them with an MCAP viewer or record into a JSON lines file",
+                path.display()
+            ),
+        }
+        .into());
+    }
+
+    let mut scans = Vec::new();
+
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            scans.push(serde_json::from_str::<RecordedScan>(&line)?);
+        }
+    }
+
+    Ok(scans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+
+    #[test]
+    fn record_and_read_json_lines() {
+        let path = temp_dir().join(format!("rplidar_cli_record_{}.jsonl", std::process::id()));
+        let info = RplidarResponseDeviceInfo { model: 0x18, firmware_version: 0x0119, hardware_version: 5, serialnum: [0; 16] };
+        let mode = ScanMode { id: 0, us_per_sample: 500f32, max_distance: 12f32, ans_type: 0x81, name: "Standard".to_owned() };
+        let points = vec![ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 47, flag: 1 }];
+
+        let mut recorder = Recorder::create(&path, &info, &mode).unwrap();
+        recorder.write_scan(&points, 0.0).unwrap();
+        recorder.write_scan(&points, 0.1).unwrap();
+        recorder.finish().unwrap();
+
+        let scans = read_recording(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        assert_eq!(scans.len(), 2);
+        assert_eq!(scans[1], RecordedScan { timestamp: 0.1, points });
+    }
+
+    #[test]
+    fn explain_mcap_replay() {
+        let err = read_recording(Path::new("scans.mcap")).unwrap_err();
+        assert!(err.to_string().contains("MCAP recordings can't be replayed"));
+    }
+}
@@ -0,0 +1,213 @@
+//! `rplidar-sim` virtual serial port
+//!
+//! Runs a simulated RPLIDAR behind a pseudo terminal, so programs talk to it like to a
+//! LIDAR on a serial port, without hardware:
+//!
+//! ```text
+//! rplidar-sim --link /tmp/vlidar
+//! rplidar --device /tmp/vlidar scan
+//! ```
+
+extern crate clap;
+#[cfg(unix)]
+extern crate libc;
+extern crate rplidar_drv;
+
+#[cfg(unix)]
+mod pty;
+
+use clap::Parser;
+use rplidar_drv::simulator::SimulatorConfig;
+use std::path::PathBuf;
+use std::process::exit;
+
+/// Simulated Slamtec RPLIDAR on a virtual serial port
+#[derive(Debug, Parser)]
+#[command(name = "rplidar-sim", version)]
+struct Cli {
+    /// Symlink to the virtual serial port
+    #[arg(short, long, default_value = "/tmp/vlidar")]
+    link: PathBuf,
+
+    /// Model reported in the device info, e.g. 0x18 for an A2
+    #[arg(short, long, default_value = "0x18", value_parser = parse_model)]
+    model: u8,
+
+    /// Rotations per second
+    #[arg(short, long, default_value_t = 10f32, value_parser = parse_frequency)]
+    frequency: f32,
+
+    /// Width of the room around the LIDAR (meters)
+    #[arg(long, default_value_t = 4f32)]
+    room_width: f32,
+
+    /// Depth of the room around the LIDAR (meters)
+    #[arg(long, default_value_t = 6f32)]
+    room_depth: f32,
+}
+
+impl Cli {
+    fn simulator_config(&self) -> SimulatorConfig {
+        SimulatorConfig {
+            model: self.model,
+            scan_frequency: self.frequency,
+            room_size: (self.room_width, self.room_depth),
+            ..SimulatorConfig::default()
+        }
+    }
+}
+
+fn parse_model(arg: &str) -> std::result::Result<u8, String> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => arg.parse(),
+    }
+    .map_err(|err| err.to_string())
+}
+
+fn parse_frequency(arg: &str) -> std::result::Result<f32, String> {
+    match arg.parse::<f32>() {
+        Ok(frequency) if frequency > 0f32 && frequency.is_finite() => Ok(frequency),
+        Ok(_) => Err("must be positive".to_owned()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(unix)]
+mod serve {
+    use super::pty::PseudoTerminal;
+    use super::Cli;
+    use rplidar_drv::simulator::{SimulatedLidar, SimulatorConfig};
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// Bytes kept while the slave buffer is full
+    const MAX_PENDING: usize = 64 * 1024;
+
+    /// Set by SIGINT and SIGTERM
+    static STOP: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        STOP.store(true, Ordering::SeqCst);
+    }
+
+    /// answer the commands written to the slave until `stop` is set
+    pub fn serve(pty: &mut PseudoTerminal, config: SimulatorConfig, stop: &AtomicBool) -> io::Result<()> {
+        let mut lidar = SimulatedLidar::new(config).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        let mut received = [0u8; 256];
+        let mut pending = Vec::new();
+        let mut transmitted = [0u8; 4096];
+
+        while !stop.load(Ordering::SeqCst) {
+            if pty.wait_readable(Duration::from_millis(1))? {
+                let len = pty.read(&mut received)?;
+                lidar.receive(&received[..len]);
+            }
+
+            let len = lidar.transmit(Instant::now(), &mut transmitted);
+            pending.extend_from_slice(&transmitted[..len]);
+
+            let written = pty.write(&pending)?;
+            pending.drain(..written);
+
+            // nobody reads the port, measurements are lost like on a real serial port
+            if pending.len() > MAX_PENDING {
+                pending.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn link(slave_path: &Path, link: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(link).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            fs::remove_file(link)?;
+        }
+        symlink(slave_path, link)
+    }
+
+    pub fn run(cli: &Cli) -> io::Result<()> {
+        let mut pty = PseudoTerminal::open()?;
+        link(pty.slave_path(), &cli.link)?;
+
+        unsafe {
+            libc::signal(libc::SIGINT, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+
+        eprintln!("simulated LIDAR on {} ({})", cli.link.display(), pty.slave_path().display());
+        let result = serve(&mut pty, cli.simulator_config(), &STOP);
+
+        let _ = fs::remove_file(&cli.link);
+        result
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::fs::OpenOptions;
+        use std::io::{Read, Write};
+        use std::sync::Arc;
+        use std::thread;
+
+        #[test]
+        fn device_info_through_pty() {
+            let mut pty = PseudoTerminal::open().unwrap();
+            let slave_path = pty.slave_path().to_owned();
+            let stop = Arc::new(AtomicBool::new(false));
+
+            let server = {
+                let stop = stop.clone();
+                thread::spawn(move || serve(&mut pty, SimulatorConfig::default(), &stop))
+            };
+
+            let mut port = OpenOptions::new().read(true).write(true).open(slave_path).unwrap();
+            port.write_all(&[0xA5, 0x50]).unwrap();
+
+            let mut answer = [0u8; 27];
+            port.read_exact(&mut answer).unwrap();
+            assert_eq!(answer[..7], [0xA5, 0x5A, 20, 0, 0, 0, 0x04]);
+            assert_eq!(answer[7], 0x18);
+            assert_eq!(answer[11..27], *b"SIMULATED-RPLDR\0");
+
+            stop.store(true, Ordering::SeqCst);
+            server.join().unwrap().unwrap();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = serve::run(&cli) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    let _ = Cli::parse();
+
+    eprintln!("error: rplidar-sim needs pseudo terminals, which are only available on unix");
+    exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_non_positive_frequency() {
+        assert_eq!(Cli::try_parse_from(["rplidar-sim", "-f", "5.5"]).unwrap().frequency, 5.5f32);
+
+        for frequency in ["0", "-10", "NaN", "inf"] {
+            assert!(Cli::try_parse_from(["rplidar-sim", "--frequency", frequency]).is_err(), "{}", frequency);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Pseudo terminal pairs
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A pseudo terminal, programs open `slave_path()` like a serial port
+#[derive(Debug)]
+pub struct PseudoTerminal {
+    master: File,
+
+    /// kept open, so reading the master doesn't fail while no program has the port open
+    _slave: File,
+
+    slave_path: PathBuf,
+}
+
+impl PseudoTerminal {
+    /// create a pseudo terminal in raw mode
+    pub fn open() -> io::Result<PseudoTerminal> {
+        unsafe {
+            let master_fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+            let master = File::from_raw_fd(master_fd);
+
+            check(libc::grantpt(master_fd))?;
+            check(libc::unlockpt(master_fd))?;
+
+            let name = libc::ptsname(master_fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let slave_path = PathBuf::from(CStr::from_ptr(name).to_string_lossy().into_owned());
+
+            let slave_fd = check(libc::open(name, libc::O_RDWR | libc::O_NOCTTY))?;
+            let slave = File::from_raw_fd(slave_fd);
+            make_raw(slave_fd)?;
+
+            // a full buffer drops bytes like a serial port nobody reads, instead of blocking
+            let flags = check(libc::fcntl(master_fd, libc::F_GETFL))?;
+            check(libc::fcntl(master_fd, libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+
+            Ok(PseudoTerminal { master, _slave: slave, slave_path })
+        }
+    }
+
+    /// path of the slave device, e.g. /dev/pts/3
+    pub fn slave_path(&self) -> &Path {
+        &self.slave_path
+    }
+
+    /// wait up to `timeout` for bytes written to the slave
+    pub fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fd = libc::pollfd { fd: self.master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+
+        match unsafe { libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int) } {
+            -1 => match io::Error::last_os_error() {
+                ref err if err.kind() == io::ErrorKind::Interrupted => Ok(false),
+                err => Err(err),
+            },
+            0 => Ok(false),
+            _ => Ok(fd.revents & libc::POLLIN != 0),
+        }
+    }
+
+    /// read bytes written to the slave
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.master.read(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            result => result,
+        }
+    }
+
+    /// write bytes to the slave, returns how many fit into its buffer
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.master.write(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            result => result,
+        }
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn make_raw(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut termios))?;
+        libc::cfmakeraw(&mut termios);
+        check(libc::tcsetattr(fd, libc::TCSANOW, &termios))?;
+    }
+    Ok(())
+}
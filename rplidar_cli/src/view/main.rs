@@ -0,0 +1,283 @@
+//! `rplidar-view` terminal viewer
+//!
+//! Plots the latest scan top-down with the front of the LIDAR up, e.g. over SSH:
+//!
+//! ```text
+//! rplidar-view --device /dev/ttyUSB0
+//! ```
+//!
+//! Keys: `+` / `-` zoom, `m` next scan mode, `space` toggle motor and scanning, `q` quit.
+
+extern crate clap;
+extern crate crossterm;
+extern crate rplidar_cli;
+extern crate rplidar_drv;
+extern crate rpos_drv;
+
+mod worker;
+
+use clap::Parser;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use rplidar_cli::canvas::plot_scan;
+use rplidar_cli::device::DeviceSpec;
+use rplidar_drv::{Health, ScanMode, ScanPoint};
+use rpos_drv::Result;
+use std::collections::VecDeque;
+use std::io::{stdout, Write};
+use std::process::exit;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use worker::{Request, Update, Worker};
+
+/// Lines below the plot
+const STATUS_LINES: u16 = 3;
+
+/// Scans used to measure the scan frequency
+const FREQUENCY_WINDOW: usize = 10;
+
+/// Live terminal viewer for Slamtec RPLIDAR
+#[derive(Debug, Parser)]
+#[command(name = "rplidar-view", version)]
+struct Cli {
+    /// LIDAR to connect: serial port path (e.g. /dev/ttyUSB0, COM3), udp://host[:port] or tcp://host[:port]
+    #[arg(short, long)]
+    device: String,
+
+    /// Baudrate of the serial port
+    #[arg(short, long, default_value_t = 115200)]
+    baudrate: u32,
+
+    /// Initial range from the center to the border of the plot (meters)
+    #[arg(short, long, default_value_t = 8f32)]
+    range: f32,
+}
+
+/// What the viewer shows
+#[derive(Debug, Default)]
+struct ViewState {
+    modes: Vec<ScanMode>,
+    mode: Option<ScanMode>,
+    health: Option<Health>,
+    scanning: bool,
+    scan: Vec<ScanPoint>,
+    scan_times: VecDeque<Instant>,
+    timeouts: usize,
+    errors: usize,
+    last_error: Option<String>,
+    range: f32,
+}
+
+impl ViewState {
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Connected { modes, health } => {
+                self.modes = modes;
+                self.health = Some(health);
+            }
+            Update::Started(mode) => {
+                self.mode = Some(mode);
+                self.scanning = true;
+                self.scan_times.clear();
+            }
+            Update::Stopped => {
+                self.scanning = false;
+                self.scan_times.clear();
+            }
+            Update::Scan { points, received_at } => {
+                self.scan = points;
+                if self.scan_times.len() == FREQUENCY_WINDOW {
+                    self.scan_times.pop_front();
+                }
+                self.scan_times.push_back(received_at);
+            }
+            Update::Timeout => self.timeouts += 1,
+            Update::Error(err) => {
+                self.errors += 1;
+                self.last_error = Some(err);
+            }
+        }
+    }
+
+    /// scans per second over the last scans
+    fn frequency(&self) -> Option<f32> {
+        match (self.scan_times.front(), self.scan_times.back()) {
+            (Some(first), Some(last)) if self.scan_times.len() > 1 && last > first => {
+                Some((self.scan_times.len() - 1) as f32 / (*last - *first).as_secs_f32())
+            }
+            _ => None,
+        }
+    }
+
+    /// the scan mode after the current one
+    fn next_mode(&self) -> Option<u16> {
+        let current = self.mode.as_ref().and_then(|mode| self.modes.iter().position(|m| m.id == mode.id));
+        let next = current.map_or(0, |i| (i + 1) % self.modes.len().max(1));
+        self.modes.get(next).map(|mode| mode.id)
+    }
+
+    fn status_lines(&self) -> [String; STATUS_LINES as usize] {
+        let mode = match self.mode {
+            Some(ref mode) => format!("{} (id {}, {:.0} us/sample)", mode.name, mode.id, mode.us_per_sample),
+            None => "-".to_owned(),
+        };
+        let frequency = match self.frequency() {
+            Some(frequency) => format!("{:.1} Hz", frequency),
+            None => "- Hz".to_owned(),
+        };
+        let health = match self.health {
+            Some(Health::Healthy) => "healthy".to_owned(),
+            Some(Health::Warning(code)) => format!("warning {:04X}", code),
+            Some(Health::Error(code)) => format!("error {:04X}", code),
+            None => "-".to_owned(),
+        };
+
+        [
+            format!(
+                "mode: {} | {} | {} points | health: {} | {}",
+                mode,
+                frequency,
+                self.scan.len(),
+                health,
+                if self.scanning { "scanning" } else { "stopped" }
+            ),
+            format!(
+                "timeouts: {} | errors: {} | last error: {}",
+                self.timeouts,
+                self.errors,
+                self.last_error.as_deref().unwrap_or("-")
+            ),
+            format!("range: {} m | [+/-] zoom  [m] next mode  [space] motor  [q] quit", self.range),
+        ]
+    }
+}
+
+/// Restores the terminal when dropped, also on errors
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw(state: &ViewState) -> Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let plot_rows = rows.saturating_sub(STATUS_LINES);
+    let mut out = stdout();
+
+    let plot = plot_scan(&state.scan, state.range, cols as usize, plot_rows as usize);
+    let status = state.status_lines();
+
+    for (row, line) in plot.iter().chain(status.iter()).enumerate() {
+        let line: String = line.chars().take(cols as usize).collect();
+        queue!(out, cursor::MoveTo(0, row as u16), Print(line), Clear(ClearType::UntilNewLine))?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// handle a key, returns false to quit
+fn on_key(state: &mut ViewState, requests: &Sender<Request>, code: KeyCode, modifiers: KeyModifiers) -> bool {
+    let request = match code {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            state.range = (state.range / 2f32).max(0.5f32);
+            None
+        }
+        KeyCode::Char('-') => {
+            state.range = (state.range * 2f32).min(64f32);
+            None
+        }
+        KeyCode::Char('m') => state.next_mode().map(Request::SwitchMode),
+        KeyCode::Char(' ') => Some(Request::ToggleMotor),
+        _ => None,
+    };
+
+    match request {
+        Some(request) => requests.send(request).is_ok(),
+        None => true,
+    }
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let lidar = DeviceSpec::parse(&cli.device, cli.baudrate)?.open()?;
+
+    let (update_tx, updates) = channel();
+    let (requests, request_rx) = channel();
+    let worker = thread::spawn(move || Worker::new(lidar, update_tx).run(request_rx));
+
+    let _guard = TerminalGuard::enter()?;
+    let mut state = ViewState { range: cli.range, ..ViewState::default() };
+
+    loop {
+        while let Ok(update) = updates.try_recv() {
+            state.apply(update);
+        }
+
+        draw(&state)?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !on_key(&mut state, &requests, key.code, key.modifiers) {
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = requests.send(Request::Quit);
+    let _ = worker.join();
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    if let Err(err) = run(&cli) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(id: u16) -> ScanMode {
+        ScanMode { id, us_per_sample: 125f32, max_distance: 12f32, ans_type: 0x82, name: format!("mode{}", id) }
+    }
+
+    #[test]
+    fn frequency_and_next_mode() {
+        let mut state = ViewState::default();
+        state.apply(Update::Connected { modes: vec![mode(0), mode(1), mode(2)], health: Health::Healthy });
+        assert_eq!(state.next_mode(), Some(0));
+
+        state.apply(Update::Started(mode(2)));
+        assert_eq!(state.next_mode(), Some(0));
+
+        let start = Instant::now();
+        for i in 0..3 {
+            state.apply(Update::Scan { points: Vec::new(), received_at: start + Duration::from_millis(100 * i) });
+        }
+        assert_eq!(state.frequency(), Some(10f32));
+
+        state.apply(Update::Stopped);
+        assert_eq!(state.frequency(), None);
+    }
+}
@@ -0,0 +1,210 @@
+use rplidar_cli::device::Lidar;
+use rplidar_drv::{Health, RposError, ScanMode, ScanOptions, ScanPoint};
+use rpos_drv::Result;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Requests from the UI to the worker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    SwitchMode(u16),
+    ToggleMotor,
+    Quit,
+}
+
+/// Updates from the worker to the UI
+#[derive(Debug, Clone)]
+pub enum Update {
+    Connected { modes: Vec<ScanMode>, health: Health },
+    Started(ScanMode),
+    Stopped,
+    Scan { points: Vec<ScanPoint>, received_at: Instant },
+    Timeout,
+    Error(String),
+}
+
+/// Owns the LIDAR, grabs scans and serves requests until `Request::Quit` or the UI is gone
+pub struct Worker {
+    lidar: Lidar,
+    updates: Sender<Update>,
+    motor_ctrl: bool,
+    scan_mode: Option<u16>,
+    scanning: bool,
+}
+
+impl Worker {
+    pub fn new(lidar: Lidar, updates: Sender<Update>) -> Worker {
+        Worker { lidar, updates, motor_ctrl: false, scan_mode: None, scanning: false }
+    }
+
+    pub fn run(mut self, requests: Receiver<Request>) {
+        if let Err(err) = self.connect() {
+            self.send(Update::Error(err.to_string()));
+            return;
+        }
+
+        loop {
+            let result = match requests.try_recv() {
+                Ok(Request::SwitchMode(mode)) => {
+                    self.scan_mode = Some(mode);
+
+                    // LIDARs don't answer the scan mode queries of `start` while scanning
+                    if self.scanning {
+                        self.stop().and_then(|_| self.start())
+                    } else {
+                        self.start()
+                    }
+                }
+                Ok(Request::ToggleMotor) if self.scanning => self.stop(),
+                Ok(Request::ToggleMotor) => self.start(),
+                Ok(Request::Quit) | Err(TryRecvError::Disconnected) => {
+                    let _ = self.stop();
+                    return;
+                }
+                Err(TryRecvError::Empty) => Ok(()),
+            };
+
+            if let Err(err) = result {
+                self.send(Update::Error(err.to_string()));
+            }
+
+            if !self.scanning {
+                sleep(Duration::from_millis(50));
+                continue;
+            }
+
+            let update = match self.lidar.grab_scan() {
+                Ok(points) => Update::Scan { points, received_at: Instant::now() },
+                Err(err) => match err.downcast_ref::<RposError>() {
+                    Some(RposError::OperationTimeout) => Update::Timeout,
+                    _ => Update::Error(err.to_string()),
+                },
+            };
+
+            if !self.send(update) {
+                let _ = self.stop();
+                return;
+            }
+        }
+    }
+
+    /// send an update, returns false if the UI is gone
+    fn send(&self, update: Update) -> bool {
+        self.updates.send(update).is_ok()
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        let modes = self.lidar.get_all_supported_scan_modes()?;
+        let health = self.lidar.get_device_health()?;
+        self.motor_ctrl = self.lidar.check_motor_ctrl_support().unwrap_or(false);
+        self.send(Update::Connected { modes, health });
+
+        self.start()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.motor_ctrl {
+            self.lidar.start_motor()?;
+        }
+
+        let options = match self.scan_mode {
+            Some(mode) => ScanOptions::with_mode(mode),
+            None => ScanOptions::default(),
+        };
+        let mode = self.lidar.start_scan_with_options(&options)?;
+
+        self.scan_mode = Some(mode.id);
+        self.scanning = true;
+        self.send(Update::Started(mode));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.scanning = false;
+        self.lidar.stop()?;
+        if self.motor_ctrl {
+            self.lidar.stop_motor()?;
+        }
+
+        self.send(Update::Stopped);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rplidar_cli::device::Stream;
+    use rplidar_drv::rplidar_core::cmds::*;
+    use rplidar_drv::simulator::{SimulatedLidar, SimulatorConfig};
+    use rplidar_drv::RplidarDevice;
+    use std::io::{self, Read, Write};
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    /// the simulator, ignoring queries while scanning like real LIDARs do
+    struct QuietWhileScanning(SimulatedLidar);
+
+    impl Read for QuietWhileScanning {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for QuietWhileScanning {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let query = matches!(
+                buf.get(1).copied(),
+                Some(RPLIDAR_CMD_GET_DEVICE_INFO)
+                    | Some(RPLIDAR_CMD_GET_DEVICE_HEALTH)
+                    | Some(RPLIDAR_CMD_GET_SAMPLERATE)
+                    | Some(RPLIDAR_CMD_GET_LIDAR_CONF)
+                    | Some(RPLIDAR_CMD_GET_ACC_BOARD_FLAG)
+            );
+            if query && self.0.is_scanning() {
+                return Ok(buf.len());
+            }
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    /// the id of the next started scan mode
+    fn started(updates: &Receiver<Update>) -> u16 {
+        loop {
+            match updates.recv_timeout(Duration::from_secs(5)).expect("no scan started") {
+                Update::Started(mode) => return mode.id,
+                Update::Error(err) => panic!("{}", err),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn switch_mode_while_scanning() {
+        let stream: Box<dyn Stream> = Box::new(QuietWhileScanning(SimulatedLidar::new(SimulatorConfig::default()).unwrap()));
+        let (updates, received) = channel();
+        let (requests, worker_requests) = channel();
+        let worker = thread::spawn(move || Worker::new(RplidarDevice::with_stream(stream), updates).run(worker_requests));
+
+        let mode = started(&received);
+        let next_mode = if mode == 0 { 1 } else { 0 };
+        requests.send(Request::SwitchMode(next_mode)).unwrap();
+        assert_eq!(started(&received), next_mode);
+
+        loop {
+            match received.recv_timeout(Duration::from_secs(5)).expect("no scan received") {
+                Update::Scan { points, .. } if !points.is_empty() => break,
+                Update::Error(err) => panic!("{}", err),
+                _ => {}
+            }
+        }
+
+        requests.send(Request::Quit).unwrap();
+        worker.join().unwrap();
+    }
+}
@@ -1,8 +1,13 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Device info response
 pub const RPLIDAR_ANS_TYPE_DEVINFO : u8 = 0x4;
 
 /// Rplidar device info data strcture
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "DeviceInfoFields", from = "DeviceInfoFields"))]
 #[repr(packed)]
 #[repr(C)]
 pub struct RplidarResponseDeviceInfo {
@@ -12,6 +17,41 @@ pub struct RplidarResponseDeviceInfo {
     pub serialnum: [u8;16]
 }
 
+/// Aligned copy of `RplidarResponseDeviceInfo`, so serde never borrows packed fields
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "RplidarResponseDeviceInfo")]
+struct DeviceInfoFields {
+    model: u8,
+    firmware_version: u16,
+    hardware_version: u8,
+    serialnum: [u8;16]
+}
+
+#[cfg(feature = "serde")]
+impl From<RplidarResponseDeviceInfo> for DeviceInfoFields {
+    fn from(info: RplidarResponseDeviceInfo) -> DeviceInfoFields {
+        DeviceInfoFields {
+            model: info.model,
+            firmware_version: info.firmware_version,
+            hardware_version: info.hardware_version,
+            serialnum: info.serialnum
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<DeviceInfoFields> for RplidarResponseDeviceInfo {
+    fn from(fields: DeviceInfoFields) -> RplidarResponseDeviceInfo {
+        RplidarResponseDeviceInfo {
+            model: fields.model,
+            firmware_version: fields.firmware_version,
+            hardware_version: fields.hardware_version,
+            serialnum: fields.serialnum
+        }
+    }
+}
+
 
 /// Device health
 pub const RPLIDAR_ANS_TYPE_DEVHEALTH : u8 = 0x6;
@@ -54,9 +94,12 @@ pub struct RplidarResponseMeasurementNode {
 }
 
 pub const RPLIDAR_RESP_MEASUREMENT_SYNCBIT : u8 = 1;
+/// Always the inverse of the sync bit
+pub const RPLIDAR_RESP_MEASUREMENT_INVERSE_SYNCBIT : u8 = 1 << 1;
 pub const RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT : usize = 2;
 pub const RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT : usize = 1;
-// pub const RPLIDAR_RESP_MEASUREMENT_CHECKBIT : u8 = 1;
+/// Always set in `angle_q6_checkbit`
+pub const RPLIDAR_RESP_MEASUREMENT_CHECKBIT : u16 = 1;
 
 /// Capsuled measurement answer (32pts per response)
 /// Added in FW ver 1.17
@@ -113,13 +156,17 @@ pub struct RplidarResponseHqCapsuledMeasurementNodes {
 pub const RPLIDAR_RESP_HQ_FLAG_SYNCBIT : u8 = 1;
 pub const RPLIDAR_RESP_MEASUREMENT_HQ_SYNC : u8 = 0xA5;
 
-// Added in FW ver 1.17
-// pub const RPLIDAR_ANS_TYPE_SAMPLE_RATE : u8 = 0x15;
+/// Sample duration answer (`std_sample_duration_us`, `express_sample_duration_us`)
+/// Added in FW ver 1.17
+pub const RPLIDAR_ANS_TYPE_SAMPLE_RATE : u8 = 0x15;
 
 /// Ultra Capsuled measurement answer (96pts per response)
 /// added in FW ver 1.23alpha
 pub const RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA : u8 = 0x84;
 
+/// Dense capsuled measurement answer (40pts per response), not decoded yet
+pub const RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED : u8 = 0x85;
+
 /// The data structure for each response packet of ultra capsuled measurements
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(packed)]
@@ -0,0 +1,192 @@
+//! Async driver over `embedded_io_async` transports
+//!
+//! Reads are raced against the `Clock`, the task is woken again while a read is pending so
+//! the deadline is checked even if the transport stays silent.
+//!
+//! # Example
+//! ```ignore
+//! let mut lidar = AsyncRplidarDriver::new(uart, || timer.now_us());
+//!
+//! lidar.start_motor().await?;
+//! lidar.start_scan(1000).await?;
+//!
+//! loop {
+//!     lidar.grab_scan_points(1000, |point| handle(point)).await?;
+//! }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::Poll;
+use embedded_io_async::{Read, Write};
+use super::answers::*;
+use super::clock::Clock;
+use super::cmds::*;
+use super::decoder::{Answer, AnswerBuffer, AnswerSizeLimits, ArrayBuffer, RPLIDAR_MAX_CONF_ANSWER_SIZE};
+use super::driver::*;
+use super::health::Health;
+use super::scan_mode::{ScanModeInfo, ScanOptions};
+use super::scan_point::ScanPoint;
+
+/// Async rplidar driver for `embedded_io_async` serial ports
+///
+/// Answers are stored in `B`, by default an array large enough for every standard answer.
+#[derive(Debug)]
+pub struct AsyncRplidarDriver<T, C, B = ArrayBuffer<RPLIDAR_MAX_CONF_ANSWER_SIZE>> {
+    transport: T,
+    clock: C,
+    core: DriverCore<B>,
+}
+
+impl<T, C> AsyncRplidarDriver<T, C>
+where
+    T: Read + Write,
+    C: Clock,
+{
+    /// create a driver with the serial port and the clock for timeouts
+    pub fn new(transport: T, clock: C) -> AsyncRplidarDriver<T, C> {
+        AsyncRplidarDriver::with_buffer(transport, clock, ArrayBuffer::new())
+    }
+}
+
+impl<T, C, B> AsyncRplidarDriver<T, C, B>
+where
+    T: Read + Write,
+    C: Clock,
+    B: AnswerBuffer,
+{
+    /// create a driver storing answers in `buffer`, e.g. a `Vec` with the `alloc` feature
+    pub fn with_buffer(transport: T, clock: C, buffer: B) -> AsyncRplidarDriver<T, C, B> {
+        AsyncRplidarDriver {
+            transport,
+            clock,
+            core: DriverCore::new(buffer),
+        }
+    }
+
+    /// release the serial port and the clock
+    pub fn release(self) -> (T, C) {
+        (self.transport, self.clock)
+    }
+
+    /// send a command
+    async fn send(&mut self, cmd: u8, payload: &[u8]) -> DriverResult<(), T::Error> {
+        let mut bytes = [0u8; TX_BUFFER_SIZE];
+        let size = DriverCore::<B>::encode(cmd, payload, &mut bytes)?;
+        self.transport.write_all(&bytes[0..size]).await.map_err(DriverError::Io)?;
+        self.transport.flush().await.map_err(DriverError::Io)
+    }
+
+    /// wait until received bytes are pending, a read still pending at the deadline is dropped
+    async fn fill(&mut self, deadline: &Deadline) -> DriverResult<(), T::Error> {
+        while !self.core.has_pending() {
+            let size = {
+                let clock = &self.clock;
+                let mut read = pin!(self.transport.read(self.core.rx_space()));
+
+                poll_fn(|cx| match read.as_mut().poll(cx) {
+                    Poll::Ready(result) => Poll::Ready(result.map_err(DriverError::Io)),
+                    Poll::Pending if deadline.is_expired(clock) => Poll::Ready(Err(DriverError::Timeout)),
+                    Poll::Pending => {
+                        // the clock can't wake the task, poll again until the deadline
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await?
+            };
+
+            self.core.received(size);
+        }
+        Ok(())
+    }
+
+    driver_commands!(async);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{answer, capsule, conf, device_info};
+    use core::convert::Infallible;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// in-memory serial port, each flushed command is answered with the next reply
+    ///
+    /// Reads never complete while nothing is received, like a silent UART.
+    struct MockSerial {
+        replies: Vec<Vec<u8>>,
+        rx: Vec<u8>,
+        tx: Vec<u8>,
+    }
+
+    impl embedded_io_async::ErrorType for MockSerial {
+        type Error = Infallible;
+    }
+
+    impl Read for MockSerial {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            if self.rx.is_empty() {
+                core::future::pending::<()>().await;
+            }
+
+            let size = buf.len().min(self.rx.len());
+            buf[0..size].copy_from_slice(&self.rx[0..size]);
+            self.rx.drain(0..size);
+            Ok(size)
+        }
+    }
+
+    impl Write for MockSerial {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        async fn flush(&mut self) -> Result<(), Infallible> {
+            if !self.replies.is_empty() {
+                let reply = self.replies.remove(0);
+                self.rx.extend(reply);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn scan_with_async_driver() {
+        let mut scan = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, 1, &capsule(0, 4000));
+        scan.extend_from_slice(&capsule(8 << 6, 4000));
+        let replies = vec![
+            device_info(),
+            conf(RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, &(250u32 << 8).to_le_bytes()),
+            conf(RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, &(16u32 << 8).to_le_bytes()),
+            conf(RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, &[RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED]),
+            conf(RPLIDAR_CONF_SCAN_MODE_NAME, b"Express\0"),
+            scan,
+        ];
+
+        let now = core::cell::Cell::new(0u64);
+        let clock = || {
+            now.set(now.get() + 1);
+            now.get()
+        };
+
+        let mut lidar = AsyncRplidarDriver::new(MockSerial { replies, rx: Vec::new(), tx: Vec::new() }, clock);
+
+        pollster::block_on(async {
+            let info = lidar.get_device_info(100).await.unwrap();
+            assert_eq!({ info.model }, 0x18);
+
+            let mode = lidar.start_scan_with_options(&ScanOptions::with_mode(2), 100).await.unwrap();
+            assert_eq!(mode.name(), "Express");
+            assert_eq!(lidar.grab_scan_points(100, |_| {}).await, Ok(0));
+            assert_eq!(lidar.grab_scan_points(100, |_| {}).await, Ok(32));
+
+            // the read never completes, the clock ends it
+            assert_eq!(lidar.grab_scan_points(100, |_| {}).await, Err(DriverError::Timeout));
+            assert_eq!(lidar.get_device_health(100).await, Err(DriverError::Timeout));
+        });
+    }
+}
@@ -0,0 +1,221 @@
+//! Blocking driver over `Transport` serial ports
+//!
+//! # Example
+//! ```ignore
+//! let mut lidar = RplidarDriver::new(uart, || timer.now_us());
+//!
+//! let info = lidar.get_device_info(1000)?;
+//! lidar.start_motor()?;
+//! lidar.start_scan(1000)?;
+//!
+//! loop {
+//!     lidar.grab_scan_points(1000, |point| handle(point))?;
+//! }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use super::answers::*;
+use super::clock::Clock;
+use super::cmds::*;
+use super::decoder::{Answer, AnswerBuffer, AnswerSizeLimits, ArrayBuffer, RPLIDAR_MAX_CONF_ANSWER_SIZE};
+use super::driver::*;
+use super::health::Health;
+use super::scan_mode::{ScanModeInfo, ScanOptions};
+use super::scan_point::ScanPoint;
+use super::transport::Transport;
+
+/// Rplidar driver for blocking serial ports
+///
+/// Answers are stored in `B`, by default an array large enough for every standard answer.
+#[derive(Debug)]
+pub struct RplidarDriver<T, C, B = ArrayBuffer<RPLIDAR_MAX_CONF_ANSWER_SIZE>> {
+    transport: T,
+    clock: C,
+    core: DriverCore<B>,
+}
+
+impl<T, C> RplidarDriver<T, C>
+where
+    T: Transport,
+    C: Clock,
+{
+    /// create a driver with the serial port and the clock for timeouts
+    pub fn new(transport: T, clock: C) -> RplidarDriver<T, C> {
+        RplidarDriver::with_buffer(transport, clock, ArrayBuffer::new())
+    }
+}
+
+impl<T, C, B> RplidarDriver<T, C, B>
+where
+    T: Transport,
+    C: Clock,
+    B: AnswerBuffer,
+{
+    /// create a driver storing answers in `buffer`, e.g. a `Vec` with the `alloc` feature
+    pub fn with_buffer(transport: T, clock: C, buffer: B) -> RplidarDriver<T, C, B> {
+        RplidarDriver {
+            transport,
+            clock,
+            core: DriverCore::new(buffer),
+        }
+    }
+
+    /// release the serial port and the clock
+    pub fn release(self) -> (T, C) {
+        (self.transport, self.clock)
+    }
+
+    /// send a command
+    fn send(&mut self, cmd: u8, payload: &[u8]) -> DriverResult<(), T::Error> {
+        let mut bytes = [0u8; TX_BUFFER_SIZE];
+        let size = DriverCore::<B>::encode(cmd, payload, &mut bytes)?;
+        self.transport.write_all(&bytes[0..size]).map_err(DriverError::Io)
+    }
+
+    /// wait until received bytes are pending
+    fn fill(&mut self, deadline: &Deadline) -> DriverResult<(), T::Error> {
+        while !self.core.has_pending() {
+            if deadline.is_expired(&self.clock) {
+                return Err(DriverError::Timeout);
+            }
+
+            let size = self.transport.read(self.core.rx_space()).map_err(DriverError::Io)?;
+            self.core.received(size);
+        }
+        Ok(())
+    }
+
+    driver_commands!(blocking);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{answer, capsule, conf, device_info};
+    use core::cell::Cell;
+    use core::convert::Infallible;
+    use std::collections::VecDeque;
+    use std::vec;
+    use std::vec::Vec;
+
+    /// in-memory serial port, each command is answered with the next reply
+    struct MockSerial {
+        replies: VecDeque<Vec<u8>>,
+        rx: VecDeque<u8>,
+        tx: Vec<u8>,
+    }
+
+    impl MockSerial {
+        fn new(replies: Vec<Vec<u8>>) -> MockSerial {
+            MockSerial { replies: replies.into(), rx: VecDeque::new(), tx: Vec::new() }
+        }
+    }
+
+    impl Transport for MockSerial {
+        type Error = Infallible;
+
+        fn write_all(&mut self, bytes: &[u8]) -> Result<(), Infallible> {
+            self.tx.extend_from_slice(bytes);
+            if let Some(reply) = self.replies.pop_front() {
+                self.rx.extend(reply);
+            }
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            // hand out a few bytes at a time like a UART would
+            let size = buf.len().min(self.rx.len()).min(7);
+            for b in buf[0..size].iter_mut() {
+                *b = self.rx.pop_front().unwrap();
+            }
+            Ok(size)
+        }
+    }
+
+    /// clock starting at 1 s, advancing 1 us on every reading
+    fn ticking_clock() -> impl Fn() -> u64 {
+        let now = Cell::new(1_000_000u64);
+        move || {
+            now.set(now.get() + 1);
+            now.get()
+        }
+    }
+
+    /// answers of the queries of scan mode 2
+    fn scan_mode_answers() -> Vec<Vec<u8>> {
+        vec![
+            conf(RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, &(250u32 << 8).to_le_bytes()),
+            conf(RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, &(16u32 << 8).to_le_bytes()),
+            conf(RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, &[RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED]),
+            conf(RPLIDAR_CONF_SCAN_MODE_NAME, b"Express\0"),
+        ]
+    }
+
+    #[test]
+    fn device_info_and_timeout() {
+        let mut lidar = RplidarDriver::new(MockSerial::new(vec![device_info()]), ticking_clock());
+
+        let info = lidar.get_device_info(100).unwrap();
+        assert_eq!({ info.model }, 0x18);
+        assert_eq!(lidar.get_device_health(100), Err(DriverError::Timeout));
+
+        let (serial, _) = lidar.release();
+        assert_eq!(serial.tx, [0xA5, RPLIDAR_CMD_GET_DEVICE_INFO, 0xA5, RPLIDAR_CMD_GET_DEVICE_HEALTH]);
+    }
+
+    #[test]
+    fn typical_mode_and_timed_scan() {
+        let mut scan = answer(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, 1, &capsule(0, 4000));
+        scan.extend_from_slice(&capsule(8 << 6, 4000));
+
+        let mut replies = vec![device_info(), conf(RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[2, 0])];
+        replies.extend(scan_mode_answers());
+        replies.push(scan);
+
+        let mut lidar = RplidarDriver::new(MockSerial::new(replies), ticking_clock());
+        let info = lidar.start_scan(100).unwrap();
+        assert_eq!((info.id, info.us_per_sample, info.max_distance, info.name()), (2, 250f32, 16f32, "Express"));
+        assert_eq!(lidar.grab_scan_points(100, |_| {}), Ok(0));
+
+        let mut points = Vec::new();
+        assert_eq!(lidar.grab_timed_scan_points(100, |p, t| points.push((p, t))), Ok(32));
+        assert!(points.iter().all(|(p, _)| p.dist_mm_q2 == 4000));
+
+        // the points of the previous capsule, 250 us apart and measured before it was received
+        let end = points[31].1;
+        assert!(points.iter().enumerate().all(|(i, (_, t))| end - t == (31 - i as u64) * 250));
+        assert!(end < 1_000_000);
+
+        let (serial, _) = lidar.release();
+        assert_eq!(serial.tx[serial.tx.len() - 9..], [0xA5, 0x82, 5, 2, 0, 0, 0, 0, 0x20]);
+    }
+
+    #[test]
+    fn scan_modes_and_motor_control() {
+        let mut old_a2 = device_info();
+        old_a2[7] = 0x28;
+        old_a2[8] = 0x11;
+        let replies = vec![old_a2, answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, 0, &[1, 0, 0, 0]), device_info()];
+        let mut lidar = RplidarDriver::new(MockSerial::new(replies), ticking_clock());
+
+        let mut names = Vec::new();
+        assert_eq!(lidar.get_all_supported_scan_modes(100, |mode| names.push(mode.name().to_owned())), Ok(2));
+        assert_eq!(names, ["Standard", "Express"]);
+        assert_eq!(lidar.check_motor_ctrl_support(100), Ok(true));
+
+        // the first answer decides
+        assert_eq!(lidar.get_device_health(100), Err(DriverError::AnswerMismatch));
+    }
+
+    #[test]
+    fn start_scan_with_options() {
+        let mut lidar = RplidarDriver::new(MockSerial::new(scan_mode_answers()), ticking_clock());
+
+        let options = ScanOptions::force_scan_with_mode(2);
+        assert_eq!(lidar.start_scan_with_options(&options, 100).unwrap().ans_type, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED);
+        lidar.stop().unwrap();
+
+        let (serial, _) = lidar.release();
+        assert_eq!(serial.tx[serial.tx.len() - 11..], [0xA5, 0x82, 5, 2, 0, 0, 0, 0, 0x20, 0xA5, RPLIDAR_CMD_STOP]);
+    }
+}
@@ -1,4 +1,4 @@
-use super::CachedPrevCapsule;
+use super::measurement::CachedPrevCapsule;
 use super::answers::*;
 
 const ANGLE_360_Q8: u32 = (360u32 << 8);
@@ -9,6 +9,9 @@ fn get_start_angle_q8(nodes: &RplidarResponseCapsuleMeasurementNodes) -> u32 {
 }
 
 pub fn angle_diff_q8(prev_q8: u32, cur_q8: u32) -> u32 {
+    // start angles have 15 bits and may exceed 360 degrees in damaged capsules
+    let (prev_q8, cur_q8) = (prev_q8 % ANGLE_360_Q8, cur_q8 % ANGLE_360_Q8);
+
     if prev_q8 > cur_q8 {
         ANGLE_360_Q8 + cur_q8 - prev_q8
     } else {
@@ -63,7 +66,9 @@ pub fn generate_flag(sync: bool) -> u8 {
 }
 
 pub fn to_hq(node: &ParsedNode, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> RplidarResponseMeasurementNodeHq {
-    let angle_q6 = cur_angle_raw_q16 - ((node.angle_offset_q3 << 13) >> 10);
+    // the offset is subtracted in q16 before converting to q6, a full turn is added so
+    // samples just after the sync don't underflow
+    let angle_q6 = ((cur_angle_raw_q16 + ANGLE_360_Q16 - (node.angle_offset_q3 << 13)) >> 10) % (360u32 << 6);
     let sync = check_sync(cur_angle_raw_q16, angle_inc_q16);
 
     RplidarResponseMeasurementNodeHq {
@@ -74,10 +79,9 @@ pub fn to_hq(node: &ParsedNode, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> R
     }
 }
 
-pub fn parse_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCapsuleMeasurementNodes) -> (Vec<RplidarResponseMeasurementNodeHq>, CachedPrevCapsule) {
+/// parse the previous capsule, passing each node to `on_node`, and return the new cached capsule
+pub fn parse_capsuled<F: FnMut(RplidarResponseMeasurementNodeHq)>(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCapsuleMeasurementNodes, on_node: &mut F) -> CachedPrevCapsule {
     if let CachedPrevCapsule::Capsuled(prev_capsule) = cached_prev {
-        let mut output_nodes : Vec<RplidarResponseMeasurementNodeHq> = Vec::with_capacity(32);
-
         let cur_start_angle_q8 = get_start_angle_q8(&nodes);
         let prev_start_angle_q8 = get_start_angle_q8(&prev_capsule);
 
@@ -90,13 +94,11 @@ pub fn parse_capsuled(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseCap
             let parsed_nodes = parse_cabin(cabin);
 
             for node in parsed_nodes.iter() {
-                output_nodes.push(to_hq(&node, cur_angle_raw_q16, angle_inc_q16));
+                on_node(to_hq(&node, cur_angle_raw_q16, angle_inc_q16));
                 cur_angle_raw_q16 += angle_inc_q16;
             }
         }
-
-        return (output_nodes, CachedPrevCapsule::Capsuled(nodes));
-    } else {
-        return (Vec::new(), CachedPrevCapsule::Capsuled(nodes));
     }
+
+    return CachedPrevCapsule::Capsuled(nodes);
 }
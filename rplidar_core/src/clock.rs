@@ -0,0 +1,14 @@
+/// Monotonic time source used for timeouts and measurement timestamps
+///
+/// On microcontrollers this is usually backed by a hardware timer or the tick
+/// counter of the RTOS.
+pub trait Clock {
+    /// microseconds elapsed since an arbitrary fixed point, must never decrease
+    fn now_us(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now_us(&self) -> u64 {
+        self()
+    }
+}
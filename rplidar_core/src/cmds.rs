@@ -21,9 +21,11 @@ pub const RPLIDAR_CMD_GET_DEVICE_INFO : u8 = 0x50;
 /// Get device health info
 pub const RPLIDAR_CMD_GET_DEVICE_HEALTH : u8 = 0x52;
 
-// pub const RPLIDAR_CMD_GET_SAMPLERATE : u8 = 0x59; //added in fw 1.17
+/// Get sample duration of standard and express scans
+pub const RPLIDAR_CMD_GET_SAMPLERATE : u8 = 0x59; //added in fw 1.17
 
-// pub const RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL : u8 = 0xA8;
+/// Set motor speed in RPM (LIDARs controlling the motor themselves, e.g. S series)
+pub const RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL : u8 = 0xA8;
 
 // Commands with payload and have response
 
@@ -60,6 +62,12 @@ pub const RPLIDAR_CMD_SET_MOTOR_PWM : u8 = 0xF0;
 /// Get capability of accessory board
 pub const RPLIDAR_CMD_GET_ACC_BOARD_FLAG : u8 = 0xFF;
 
+/// Default motor PWM
+pub const RPLIDAR_DEFAULT_MOTOR_PWM: u16 = 600;
+
+/// First firmware version supporting `RPLIDAR_CMD_GET_LIDAR_CONF`
+pub const RPLIDAR_GET_LIDAR_CONF_START_VERSION: u16 = ((1 << 8) | (24)) as u16;
+
 // LIDAR configurations
 
 /// LIDAR config entry for scan mode count
@@ -0,0 +1,178 @@
+use super::checksum::Checksum;
+use super::encoder::{RPLIDAR_CMDFLAG_HAS_PAYLOAD, RPLIDAR_CMD_SYNC_BYTE};
+
+/// Longest command payload, the size is sent in one byte
+const RPLIDAR_CMD_MAX_PAYLOAD_SIZE: usize = 255;
+
+/// A decoded command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Command<'a> {
+    /// The command, with the payload flag for commands with payload (e.g. `RPLIDAR_CMD_EXPRESS_SCAN`)
+    pub cmd: u8,
+
+    /// Payload data
+    pub payload: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecodeStatus {
+    WaitSyncByte,
+    WaitCmd,
+    WaitPayloadSize,
+    ReceivePayload,
+    WaitChecksum,
+    CommandReady,
+}
+
+/// Decoder of RPLIDAR commands, the state machine of the device side protocol
+///
+/// Like the LIDAR, it drops commands with a wrong checksum, they are counted by `rejected`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandDecoder {
+    status: DecodeStatus,
+    cmd: u8,
+    payload: [u8; RPLIDAR_CMD_MAX_PAYLOAD_SIZE],
+    payload_len: usize,
+    payload_size: usize,
+    rejected: usize,
+}
+
+impl CommandDecoder {
+    pub fn new() -> CommandDecoder {
+        CommandDecoder {
+            status: DecodeStatus::WaitSyncByte,
+            cmd: 0,
+            payload: [0u8; RPLIDAR_CMD_MAX_PAYLOAD_SIZE],
+            payload_len: 0,
+            payload_size: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Reset the decoder status
+    pub fn reset(&mut self) {
+        self.status = DecodeStatus::WaitSyncByte;
+        self.payload_len = 0;
+        self.payload_size = 0;
+    }
+
+    /// Number of commands dropped for a wrong checksum
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    fn decode_byte(&mut self, byte: u8) {
+        match self.status {
+            DecodeStatus::WaitSyncByte => {
+                if byte == RPLIDAR_CMD_SYNC_BYTE {
+                    self.status = DecodeStatus::WaitCmd;
+                }
+            }
+            DecodeStatus::WaitCmd => {
+                self.cmd = byte;
+                self.payload_len = 0;
+                self.payload_size = 0;
+                self.status = if byte & RPLIDAR_CMDFLAG_HAS_PAYLOAD == RPLIDAR_CMDFLAG_HAS_PAYLOAD {
+                    DecodeStatus::WaitPayloadSize
+                } else {
+                    DecodeStatus::CommandReady
+                };
+            }
+            DecodeStatus::WaitPayloadSize => {
+                self.payload_size = byte as usize;
+                self.status = if self.payload_size == 0 {
+                    DecodeStatus::WaitChecksum
+                } else {
+                    DecodeStatus::ReceivePayload
+                };
+            }
+            DecodeStatus::ReceivePayload => {
+                self.payload[self.payload_len] = byte;
+                self.payload_len += 1;
+                if self.payload_len == self.payload_size {
+                    self.status = DecodeStatus::WaitChecksum;
+                }
+            }
+            DecodeStatus::WaitChecksum => {
+                let mut checksum = Checksum::new();
+                checksum.push(RPLIDAR_CMD_SYNC_BYTE);
+                checksum.push(self.cmd);
+                checksum.push(self.payload_size as u8);
+                checksum.push_slice(&self.payload[0..self.payload_len]);
+
+                if checksum.checksum() == byte {
+                    self.status = DecodeStatus::CommandReady;
+                } else {
+                    self.rejected += 1;
+                    self.reset();
+                }
+            }
+            DecodeStatus::CommandReady => {}
+        }
+    }
+
+    /// Decode bytes and return consumed bytes and the command, if one is completed
+    ///
+    /// The command borrows the decoder, it stays valid until the next call.
+    pub fn decode(&mut self, buf: &[u8]) -> (usize, Option<Command<'_>>) {
+        if self.status == DecodeStatus::CommandReady {
+            self.reset();
+        }
+
+        let mut i = 0;
+
+        while i < buf.len() && self.status != DecodeStatus::CommandReady {
+            self.decode_byte(buf[i]);
+            i += 1;
+        }
+
+        (i, self.command())
+    }
+
+    /// The command completed by the last call of `decode`, if any
+    pub fn command(&self) -> Option<Command<'_>> {
+        if self.status == DecodeStatus::CommandReady {
+            Some(Command {
+                cmd: self.cmd,
+                payload: &self.payload[0..self.payload_len],
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for CommandDecoder {
+    fn default() -> CommandDecoder {
+        CommandDecoder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::encode_command;
+
+    #[test]
+    fn decode_commands() {
+        let mut decoder = CommandDecoder::new();
+        let mut bytes = [0u8; 16];
+
+        // garbage before a command without payload
+        let (read, cmd) = decoder.decode(&[0x00, 0xA5, 0x25, 0xA5]);
+        assert_eq!((read, cmd), (3, Some(Command { cmd: 0x25, payload: &[] })));
+
+        // a command with payload, in pieces
+        let size = encode_command(0x82, &[2, 0, 0, 0, 0], &mut bytes).unwrap();
+        assert_eq!(decoder.decode(&bytes[0..4]), (4, None));
+        assert_eq!(decoder.decode(&bytes[4..size]), (size - 4, Some(Command { cmd: 0x82, payload: &[2, 0, 0, 0, 0] })));
+
+        // wrong checksum
+        bytes[size - 1] ^= 0xFF;
+        assert_eq!(decoder.decode(&bytes[0..size]), (size, None));
+        assert_eq!(decoder.rejected(), 1);
+
+        let size = encode_command(0xF0, &[0x58, 0x02], &mut bytes).unwrap();
+        assert_eq!(decoder.decode(&bytes[0..size]).1, Some(Command { cmd: 0xF0, payload: &[0x58, 0x02] }));
+    }
+}
@@ -0,0 +1,557 @@
+use byteorder::{ByteOrder, LittleEndian};
+use core::cmp::min;
+use core::mem::size_of;
+use super::answers::*;
+use super::errors::*;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+pub(crate) const RPLIDAR_ANS_SYNC_BYTES: [u8; 2] = [0xA5, 0x5A];
+
+pub(crate) const RPLIDAR_ANS_PKTFLAG_LOOP: u8 = 0x1;
+
+pub(crate) const RPLIDAR_ANS_HEADER_SIZE_MASK: u32 = 0x3FFFFFFF;
+pub(crate) const RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT: usize = 30;
+
+/// The size of RPLIDAR protocol answer header (not including the two sync bytes)
+pub(crate) const RPLIDAR_ANS_HEADER_SIZE: usize = 5;
+
+/// Largest answer of types without a known size
+pub const RPLIDAR_DEFAULT_MAX_ANSWER_SIZE: usize = 1024;
+
+/// Largest LIDAR configuration answer, the configuration type and up to 256 bytes of data
+pub const RPLIDAR_MAX_CONF_ANSWER_SIZE: usize = 4 + 256;
+
+/// Number of answer types which can have their own limit in `AnswerSizeLimits`
+pub const RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES: usize = 8;
+
+/// Size of dense capsules, sync and checksum, start angle and 40 distances
+const DENSE_CAPSULE_SIZE: usize = 84;
+
+/// size of answers of `ans_type` known from the protocol
+fn known_answer_size(ans_type: u8) -> Option<usize> {
+    match ans_type {
+        RPLIDAR_ANS_TYPE_DEVINFO => Some(size_of::<RplidarResponseDeviceInfo>()),
+        RPLIDAR_ANS_TYPE_DEVHEALTH => Some(size_of::<RplidarResponseDeviceHealth>()),
+        RPLIDAR_ANS_TYPE_SAMPLE_RATE => Some(4),
+        RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG => Some(4),
+        RPLIDAR_ANS_TYPE_GET_LIDAR_CONF => Some(RPLIDAR_MAX_CONF_ANSWER_SIZE),
+        RPLIDAR_ANS_TYPE_MEASUREMENT => Some(size_of::<RplidarResponseMeasurementNode>()),
+        RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => Some(size_of::<RplidarResponseCapsuleMeasurementNodes>()),
+        RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => Some(size_of::<RplidarResponseHqCapsuledMeasurementNodes>()),
+        RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => Some(size_of::<RplidarResponseUltraCapsuleMeasurementNodes>()),
+        RPLIDAR_ANS_TYPE_MEASUREMENT_DENSE_CAPSULED => Some(DENSE_CAPSULE_SIZE),
+        _ => None,
+    }
+}
+
+/// largest plausible size of answers of `ans_type`, used by `AnswerDecoder` by default
+///
+/// Measurement and query answers have a known size, configuration answers are limited to
+/// `RPLIDAR_MAX_CONF_ANSWER_SIZE` and others to `RPLIDAR_DEFAULT_MAX_ANSWER_SIZE`.
+pub fn default_max_answer_size(ans_type: u8) -> usize {
+    known_answer_size(ans_type).unwrap_or(RPLIDAR_DEFAULT_MAX_ANSWER_SIZE)
+}
+
+/// Largest plausible size of each answer type, `default_max_answer_size` unless changed
+///
+/// # Example
+/// ```ignore
+/// let mut limits = AnswerSizeLimits::new();
+/// limits.set_limit(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, 4096);
+/// decoder.set_answer_size_limits(limits);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnswerSizeLimits {
+    unknown: usize,
+    overrides: [(u8, usize); RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES],
+    override_count: usize,
+}
+
+impl AnswerSizeLimits {
+    /// limits of `default_max_answer_size`
+    pub fn new() -> AnswerSizeLimits {
+        AnswerSizeLimits {
+            unknown: RPLIDAR_DEFAULT_MAX_ANSWER_SIZE,
+            overrides: [(0u8, 0usize); RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES],
+            override_count: 0,
+        }
+    }
+
+    /// set the limit of `ans_type`, returns false if `RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES` other types have one already
+    pub fn set_limit(&mut self, ans_type: u8, max_size: usize) -> bool {
+        let overrides = &mut self.overrides[..self.override_count];
+
+        if let Some(entry) = overrides.iter_mut().find(|(t, _)| *t == ans_type) {
+            entry.1 = max_size;
+            true
+        } else if self.override_count < RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES {
+            self.overrides[self.override_count] = (ans_type, max_size);
+            self.override_count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// set the limit of answer types without a known size and their own limit
+    pub fn set_unknown_limit(&mut self, max_size: usize) {
+        self.unknown = max_size;
+    }
+
+    /// largest plausible size of answers of `ans_type`
+    pub fn limit(&self, ans_type: u8) -> usize {
+        self.overrides[..self.override_count]
+            .iter()
+            .find(|(t, _)| *t == ans_type)
+            .map(|&(_, max_size)| max_size)
+            .or_else(|| known_answer_size(ans_type))
+            .unwrap_or(self.unknown)
+    }
+}
+
+impl Default for AnswerSizeLimits {
+    fn default() -> AnswerSizeLimits {
+        AnswerSizeLimits::new()
+    }
+}
+
+/// Storage of the answer being decoded
+pub trait AnswerBuffer {
+    /// start receiving an answer of `size` bytes, returns false if it can't be stored
+    fn start(&mut self, size: usize) -> bool;
+
+    /// append received bytes of the answer
+    fn append(&mut self, bytes: &[u8]);
+
+    /// bytes received since `start`
+    fn received(&self) -> &[u8];
+
+    /// drop the first `count` received bytes
+    fn drop_front(&mut self, count: usize);
+}
+
+/// Fixed capacity answer storage, for targets without heap
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayBuffer<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ArrayBuffer<N> {
+    pub fn new() -> ArrayBuffer<N> {
+        ArrayBuffer { data: [0u8; N], len: 0 }
+    }
+}
+
+impl<const N: usize> Default for ArrayBuffer<N> {
+    fn default() -> ArrayBuffer<N> {
+        ArrayBuffer::new()
+    }
+}
+
+impl<const N: usize> AnswerBuffer for ArrayBuffer<N> {
+    fn start(&mut self, size: usize) -> bool {
+        self.len = 0;
+        size <= N
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        self.data[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn received(&self) -> &[u8] {
+        &self.data[0..self.len]
+    }
+
+    fn drop_front(&mut self, count: usize) {
+        let count = min(count, self.len);
+        self.data.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl AnswerBuffer for Vec<u8> {
+    /// grows while receiving, so a bogus size doesn't allocate upfront
+    fn start(&mut self, _size: usize) -> bool {
+        self.clear();
+        true
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+
+    fn received(&self) -> &[u8] {
+        &self[..]
+    }
+
+    fn drop_front(&mut self, count: usize) {
+        let count = min(count, self.len());
+        self.drain(..count);
+    }
+}
+
+/// A decoded answer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Answer<'a> {
+    /// The answer type
+    pub ans_type: u8,
+
+    /// Payload data
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecodeStatus {
+    WaitSyncByte(usize),
+    WaitAnsHeader,
+    ReceiveResponse,
+    AnswerReady,
+}
+
+/// Decoder of RPLIDAR answers, the state machine of the host side protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnswerDecoder<B> {
+    status: DecodeStatus,
+    ans_header: [u8; RPLIDAR_ANS_HEADER_SIZE],
+    ans_header_len: usize,
+    ans_type: u8,
+    ans_flag: u8,
+    response_size: usize,
+    buffer: B,
+    answer_size_limits: AnswerSizeLimits,
+    rejected_headers: usize,
+}
+
+impl<B: AnswerBuffer> AnswerDecoder<B> {
+    /// create a decoder storing answers into `buffer`
+    pub fn new(buffer: B) -> AnswerDecoder<B> {
+        AnswerDecoder {
+            status: DecodeStatus::WaitSyncByte(0),
+            ans_header: [0u8; RPLIDAR_ANS_HEADER_SIZE],
+            ans_header_len: 0,
+            ans_type: 0,
+            ans_flag: 0,
+            response_size: 0,
+            buffer,
+            answer_size_limits: AnswerSizeLimits::new(),
+            rejected_headers: 0,
+        }
+    }
+
+    /// set the largest plausible size of each answer type, `default_max_answer_size` by default
+    pub fn set_answer_size_limits(&mut self, limits: AnswerSizeLimits) {
+        self.answer_size_limits = limits;
+    }
+
+    /// largest plausible size of each answer type
+    pub fn answer_size_limits(&self) -> &AnswerSizeLimits {
+        &self.answer_size_limits
+    }
+
+    /// number of answer headers rejected for announcing implausibly large answers
+    pub fn rejected_headers(&self) -> usize {
+        self.rejected_headers
+    }
+
+    /// shift the completed loop answer by one byte, its first byte is dropped and one more byte is awaited
+    ///
+    /// Loop answers carry no sync bytes, after bytes are lost on the line every following
+    /// answer is out of step. Realigning after each invalid answer finds the boundaries again.
+    /// Does nothing unless a loop answer is completed.
+    pub fn realign(&mut self) {
+        if self.status == DecodeStatus::AnswerReady && self.ans_flag & RPLIDAR_ANS_PKTFLAG_LOOP == RPLIDAR_ANS_PKTFLAG_LOOP {
+            self.buffer.drop_front(1);
+            self.status = DecodeStatus::ReceiveResponse;
+        }
+    }
+
+    /// Reset the decoder status
+    pub fn reset(&mut self) {
+        self.start_wait_sync_bytes(0);
+    }
+
+    fn start_wait_sync_bytes(&mut self, sync_byte_index: usize) {
+        self.status = DecodeStatus::WaitSyncByte(sync_byte_index);
+        self.ans_header_len = 0;
+        self.ans_flag = 0;
+        self.response_size = 0;
+    }
+
+    fn start_wait_ans_header(&mut self) {
+        self.status = DecodeStatus::WaitAnsHeader;
+        self.ans_header_len = 0;
+        self.ans_flag = 0;
+        self.response_size = 0;
+    }
+
+    fn start_receive_response(&mut self) -> Result<()> {
+        if !self.buffer.start(self.response_size) {
+            let size = self.response_size;
+            self.reset();
+            return Err(Error::AnswerTooLarge(size));
+        }
+
+        self.status = if self.response_size == 0 {
+            DecodeStatus::AnswerReady
+        } else {
+            DecodeStatus::ReceiveResponse
+        };
+        Ok(())
+    }
+
+    /// the answer has been handed out, continue with the next one
+    fn finish_answer(&mut self) -> Result<()> {
+        if self.ans_flag & RPLIDAR_ANS_PKTFLAG_LOOP == RPLIDAR_ANS_PKTFLAG_LOOP {
+            self.start_receive_response()
+        } else {
+            self.start_wait_sync_bytes(0);
+            Ok(())
+        }
+    }
+
+    fn decode_sync_byte(&mut self, byte: u8, i: usize) {
+        if byte != RPLIDAR_ANS_SYNC_BYTES[i] {
+            // the mismatching byte may be the start of the next sync sequence
+            let restart = if byte == RPLIDAR_ANS_SYNC_BYTES[0] { 1 } else { 0 };
+            self.start_wait_sync_bytes(restart);
+        } else if i == RPLIDAR_ANS_SYNC_BYTES.len() - 1 {
+            self.start_wait_ans_header();
+        } else {
+            self.start_wait_sync_bytes(i + 1);
+        }
+    }
+
+    fn decode_ans_header(&mut self, buf: &[u8]) -> Result<usize> {
+        let bytes_to_read = RPLIDAR_ANS_HEADER_SIZE - self.ans_header_len;
+        let bytes_actual_read = min(bytes_to_read, buf.len());
+        self.ans_header[self.ans_header_len..self.ans_header_len + bytes_actual_read]
+            .copy_from_slice(&buf[0..bytes_actual_read]);
+        self.ans_header_len += bytes_actual_read;
+
+        if self.ans_header_len == RPLIDAR_ANS_HEADER_SIZE {
+            self.decode_ans_header_metadata();
+
+            if self.response_size > self.answer_size_limits.limit(self.ans_type) {
+                self.reject_ans_header()?;
+                return Ok(bytes_actual_read);
+            }
+
+            if self.response_size == 0 && (self.ans_flag & RPLIDAR_ANS_PKTFLAG_LOOP) == RPLIDAR_ANS_PKTFLAG_LOOP {
+                self.reset();
+                return Err(Error::EmptyLoopAnswer);
+            }
+
+            self.start_receive_response()?;
+        }
+
+        Ok(bytes_actual_read)
+    }
+
+    /// drop an implausible header, e.g. sync bytes within data or a damaged size
+    ///
+    /// The real sync bytes may be among the header bytes, so they are decoded again.
+    fn reject_ans_header(&mut self) -> Result<()> {
+        self.rejected_headers = self.rejected_headers.wrapping_add(1);
+
+        let header = self.ans_header;
+        self.reset();
+        self.decode_bytes(&header)?;
+        Ok(())
+    }
+
+    fn decode_response(&mut self, buf: &[u8]) -> usize {
+        let bytes_to_read = self.response_size - self.buffer.received().len();
+        let bytes_actual_read = min(bytes_to_read, buf.len());
+        self.buffer.append(&buf[0..bytes_actual_read]);
+
+        if self.buffer.received().len() == self.response_size {
+            self.status = DecodeStatus::AnswerReady;
+        }
+
+        bytes_actual_read
+    }
+
+    /// when we finished reading the first five bytes of answer header, we will know the message type and payload size of answer header
+    fn decode_ans_header_metadata(&mut self) {
+        self.ans_type = self.ans_header[4];
+        let size_q30_subtype = LittleEndian::read_u32(&self.ans_header[0..4]);
+        self.ans_flag = (size_q30_subtype >> RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT as u32) as u8;
+        self.response_size = (size_q30_subtype & RPLIDAR_ANS_HEADER_SIZE_MASK) as usize;
+    }
+
+    /// Decode bytes and return consumed bytes and the answer, if one is completed
+    ///
+    /// The answer borrows the decoder, it stays valid until the next call.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Answer<'_>>)> {
+        if self.status == DecodeStatus::AnswerReady {
+            self.finish_answer()?;
+        }
+
+        let read = self.decode_bytes(buf)?;
+        Ok((read, self.answer()))
+    }
+
+    /// run the state machine over `buf` until an answer is ready, returns the consumed bytes
+    fn decode_bytes(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut i = 0;
+
+        while i < buf.len() && self.status != DecodeStatus::AnswerReady {
+            match self.status {
+                DecodeStatus::WaitSyncByte(sync_byte_index) => {
+                    self.decode_sync_byte(buf[i], sync_byte_index);
+                    i += 1;
+                }
+                DecodeStatus::WaitAnsHeader => {
+                    i += self.decode_ans_header(&buf[i..])?;
+                }
+                DecodeStatus::ReceiveResponse => {
+                    i += self.decode_response(&buf[i..]);
+                }
+                DecodeStatus::AnswerReady => {}
+            }
+        }
+
+        Ok(i)
+    }
+
+    /// The answer completed by the last call of `decode`, if any
+    pub fn answer(&self) -> Option<Answer<'_>> {
+        if self.status == DecodeStatus::AnswerReady {
+            Some(Answer {
+                ans_type: self.ans_type,
+                data: self.buffer.received(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::answer;
+
+    #[test]
+    fn answer_size_limits() {
+        let mut limits = AnswerSizeLimits::new();
+        assert_eq!(limits.limit(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED), 84);
+        assert_eq!(limits.limit(0x30), RPLIDAR_DEFAULT_MAX_ANSWER_SIZE);
+
+        assert!(limits.set_limit(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, 4096));
+        assert!(limits.set_limit(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, 2048));
+        limits.set_unknown_limit(16);
+        assert_eq!(limits.limit(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF), 2048);
+        assert_eq!(limits.limit(0x30), 16);
+        assert_eq!(limits.limit(RPLIDAR_ANS_TYPE_DEVINFO), 20);
+
+        for ans_type in 0x40..0x47 {
+            assert!(limits.set_limit(ans_type, 8));
+        }
+        assert!(!limits.set_limit(0x47, 8));
+        assert_eq!(limits.limit(0x47), 16);
+    }
+
+    #[test]
+    fn decode_single_answer_in_pieces() {
+        let mut decoder = AnswerDecoder::new(ArrayBuffer::<32>::new());
+        let bytes = answer(0x04, 0, &[1, 2, 3, 4]);
+
+        // garbage and a false sync byte before the answer
+        let (read, ans) = decoder.decode(&[0x00, 0xA5]).unwrap();
+        assert_eq!((read, ans), (2, None));
+
+        let (read, ans) = decoder.decode(&bytes[..4]).unwrap();
+        assert_eq!((read, ans), (4, None));
+
+        let (read, ans) = decoder.decode(&bytes[4..]).unwrap();
+        assert_eq!(read, bytes.len() - 4);
+        assert_eq!(ans, Some(Answer { ans_type: 0x04, data: &[1, 2, 3, 4] }));
+    }
+
+    #[test]
+    fn decode_loop_answers() {
+        let mut decoder = AnswerDecoder::new(ArrayBuffer::<8>::new());
+        let mut bytes = answer(0x81, 1, &[1, 2]);
+        bytes.extend_from_slice(&[3, 4, 5]);
+
+        let (read, ans) = decoder.decode(&bytes).unwrap();
+        assert_eq!(ans.unwrap().data, &[1, 2]);
+
+        let (_, ans) = decoder.decode(&bytes[read..]).unwrap();
+        assert_eq!(ans.unwrap().data, &[3, 4]);
+
+        assert_eq!(decoder.decode(&[6]).unwrap(), (1, None));
+    }
+
+    #[test]
+    fn realign_loop_answers() {
+        let mut decoder = AnswerDecoder::new(ArrayBuffer::<8>::new());
+
+        // a stray byte in front of the answers
+        let mut bytes = answer(0x81, 1, &[1, 2, 3]);
+        bytes.extend_from_slice(&[4, 5, 6, 7, 8, 9]);
+
+        let (read, ans) = decoder.decode(&bytes).unwrap();
+        assert_eq!(ans.unwrap().data, &[1, 2, 3]);
+
+        decoder.realign();
+        let (len, ans) = decoder.decode(&bytes[read..]).unwrap();
+        assert_eq!((len, ans.unwrap().data), (1, &[2, 3, 4][..]));
+
+        let (_, ans) = decoder.decode(&bytes[read + len..]).unwrap();
+        assert_eq!(ans.unwrap().data, &[5, 6, 7]);
+
+        // single answers stay as they are
+        decoder.reset();
+        let (_, ans) = decoder.decode(&answer(0x04, 0, &[1, 2])).unwrap();
+        assert!(ans.is_some());
+        decoder.realign();
+        assert_eq!(decoder.answer().unwrap().data, &[1, 2]);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut decoder = AnswerDecoder::new(ArrayBuffer::<2>::new());
+        assert_eq!(decoder.decode(&answer(0x04, 0, &[1, 2, 3])), Err(Error::AnswerTooLarge(3)));
+        assert_eq!(decoder.decode(&answer(0x81, 1, &[])), Err(Error::EmptyLoopAnswer));
+
+        let (_, ans) = decoder.decode(&answer(0x06, 0, &[])).unwrap();
+        assert_eq!(ans, Some(Answer { ans_type: 0x06, data: &[] }));
+    }
+
+    #[test]
+    fn reject_implausible_headers() {
+        let mut decoder = AnswerDecoder::new(ArrayBuffer::<32>::new());
+
+        // a damaged size announcing a 256 bytes device info, followed by a real answer
+        let mut bytes = answer(0x04, 0, &[]);
+        bytes[3] = 0x01;
+        bytes.extend_from_slice(&answer(0x06, 0, &[0, 0, 0]));
+
+        let (read, ans) = decoder.decode(&bytes).unwrap();
+        assert_eq!(read, bytes.len());
+        assert_eq!(ans, Some(Answer { ans_type: 0x06, data: &[0, 0, 0] }));
+        assert_eq!(decoder.rejected_headers(), 1);
+
+        // the real sync bytes within a rejected header are found again
+        let bytes = [0xA5, 0x5A, 0xA5, 0x5A, 0x03, 0x00, 0x00, 0x00, 0x06, 1, 2, 3];
+        let (read, ans) = decoder.decode(&bytes).unwrap();
+        assert_eq!(read, bytes.len());
+        assert_eq!(ans, Some(Answer { ans_type: 0x06, data: &[1, 2, 3] }));
+        assert_eq!(decoder.rejected_headers(), 2);
+
+        let mut limits = AnswerSizeLimits::new();
+        assert!(limits.set_limit(0x04, 64 * 1024));
+        decoder.set_answer_size_limits(limits);
+        let mut bytes = answer(0x04, 0, &[]);
+        bytes[3] = 0x01;
+        assert_eq!(decoder.decode(&bytes), Err(Error::AnswerTooLarge(256)));
+        assert_eq!(decoder.rejected_headers(), 2);
+    }
+}
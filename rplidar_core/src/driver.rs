@@ -0,0 +1,530 @@
+use byteorder::{ByteOrder, LittleEndian};
+use core::fmt;
+use super::answers::*;
+use super::clock::Clock;
+use super::cmds::*;
+use super::decoder::{Answer, AnswerBuffer, AnswerDecoder, AnswerSizeLimits};
+use super::encoder::encode_command;
+use super::errors::Error;
+use super::measurement::{parse_answer, AnswerData, MeasurementDecoder};
+use super::scan_mode::{ScanModeInfo, ScanOptions};
+use super::scan_point::ScanPoint;
+
+/// Size of the buffer for bytes read from the transport
+const RX_BUFFER_SIZE: usize = 64;
+
+/// Size of the largest command the drivers send
+pub(crate) const TX_BUFFER_SIZE: usize = 16;
+
+/// Most points decoded from one measurement answer, the 32 cabins of an ultra capsule
+const MAX_ANSWER_POINTS: usize = 96;
+
+/// Errors of the drivers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriverError<E> {
+    /// The transport failed
+    Io(E),
+
+    /// No answer before the timeout
+    Timeout,
+
+    /// The received data is invalid according to the protocol
+    Protocol(Error),
+
+    /// The answer doesn't match the command
+    AnswerMismatch,
+}
+
+/// Result of the drivers
+pub type DriverResult<T, E> = Result<T, DriverError<E>>;
+
+impl<E> From<Error> for DriverError<E> {
+    fn from(err: Error) -> DriverError<E> {
+        DriverError::Protocol(err)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for DriverError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::Io(err) => write!(f, "transport error: {:?}", err),
+            DriverError::Timeout => write!(f, "operation timeout"),
+            DriverError::Protocol(err) => write!(f, "protocol error: {}", err),
+            DriverError::AnswerMismatch => write!(f, "answer type mismatch"),
+        }
+    }
+}
+
+/// Point in time after which an operation times out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Deadline(u64);
+
+impl Deadline {
+    pub(crate) fn after<C: Clock>(clock: &C, timeout_ms: u64) -> Deadline {
+        Deadline(clock.now_us().saturating_add(timeout_ms.saturating_mul(1000)))
+    }
+
+    pub(crate) fn is_expired<C: Clock>(&self, clock: &C) -> bool {
+        clock.now_us() >= self.0
+    }
+}
+
+/// Transport independent part of the drivers: buffers, decoders, command encoding and answer parsing
+///
+/// The front ends only move bytes, the commands on top of them are generated by `driver_commands!`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DriverCore<B> {
+    decoder: AnswerDecoder<B>,
+    measurements: MeasurementDecoder,
+    rx: [u8; RX_BUFFER_SIZE],
+    rx_pos: usize,
+    rx_len: usize,
+    sample_ns: u64,
+}
+
+impl<B: AnswerBuffer> DriverCore<B> {
+    pub(crate) fn new(buffer: B) -> DriverCore<B> {
+        DriverCore {
+            decoder: AnswerDecoder::new(buffer),
+            measurements: MeasurementDecoder::new(),
+            rx: [0u8; RX_BUFFER_SIZE],
+            rx_pos: 0,
+            rx_len: 0,
+            sample_ns: 0,
+        }
+    }
+
+    /// encode a command into `bytes`, returns the encoded size
+    pub(crate) fn encode(cmd: u8, payload: &[u8], bytes: &mut [u8; TX_BUFFER_SIZE]) -> Result<usize, Error> {
+        encode_command(cmd, payload, bytes)
+    }
+
+    /// set the largest plausible size of each answer type
+    pub(crate) fn set_answer_size_limits(&mut self, limits: AnswerSizeLimits) {
+        self.decoder.set_answer_size_limits(limits);
+    }
+
+    /// number of answer headers dropped for announcing implausibly large answers
+    pub(crate) fn rejected_headers(&self) -> usize {
+        self.decoder.rejected_headers()
+    }
+
+    /// number of legacy measurement nodes dropped for wrong check bits
+    pub(crate) fn invalid_nodes(&self) -> usize {
+        self.measurements.invalid_nodes()
+    }
+
+    /// forget received bytes and the cached capsule, called before sending a command
+    pub(crate) fn restart(&mut self) {
+        self.decoder.reset();
+        self.measurements.reset();
+        self.rx_pos = 0;
+        self.rx_len = 0;
+    }
+
+    /// prepare decoding the measurements of `scan_mode`
+    pub(crate) fn start_scan(&mut self, scan_mode: &ScanModeInfo) {
+        self.restart();
+        self.sample_ns = (scan_mode.us_per_sample * 1000f32) as u64;
+    }
+
+    /// true if received bytes are waiting to be decoded
+    pub(crate) fn has_pending(&self) -> bool {
+        self.rx_pos < self.rx_len
+    }
+
+    /// buffer to read into, only valid when nothing is pending
+    pub(crate) fn rx_space(&mut self) -> &mut [u8] {
+        self.rx_pos = 0;
+        self.rx_len = 0;
+        &mut self.rx[..]
+    }
+
+    /// `size` bytes were read into `rx_space`
+    pub(crate) fn received(&mut self, size: usize) {
+        self.rx_len = size;
+    }
+
+    /// decode pending bytes until an answer is completed, returns the completed answer
+    pub(crate) fn decode_answer(&mut self) -> Result<Option<Answer<'_>>, Error> {
+        while self.has_pending() {
+            let (read, completed) = {
+                let (read, answer) = self.decoder.decode(&self.rx[self.rx_pos..self.rx_len])?;
+                (read, answer.is_some())
+            };
+            self.rx_pos += read;
+
+            if completed {
+                return Ok(self.decoder.answer());
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// the answer completed by the last decode, if any
+    pub(crate) fn answer(&self) -> Option<Answer<'_>> {
+        self.decoder.answer()
+    }
+
+    /// decode pending bytes until a measurement answer is decoded, returns the number of points
+    ///
+    /// Each point is passed to `on_point` with the estimated time it was measured, given that
+    /// the answer was received at `received_us`.
+    pub(crate) fn decode_points<F: FnMut(ScanPoint, u64)>(&mut self, received_us: u64, on_point: &mut F) -> Result<Option<usize>, Error> {
+        const NO_POINT: Option<ScanPoint> = None;
+
+        while self.has_pending() {
+            let (read, completed) = {
+                let (read, answer) = self.decoder.decode(&self.rx[self.rx_pos..self.rx_len])?;
+                (read, answer.is_some())
+            };
+            self.rx_pos += read;
+
+            let answer = match self.decoder.answer() {
+                Some(answer) if completed => answer,
+                _ => continue,
+            };
+
+            // timestamps count back from the last point, so the points are kept until all are decoded
+            let ans_type = answer.ans_type;
+            let mut points = [NO_POINT; MAX_ANSWER_POINTS];
+            let mut count = 0;
+            self.measurements.decode(ans_type, answer.data, |point| {
+                if count < MAX_ANSWER_POINTS {
+                    points[count] = Some(point);
+                    count += 1;
+                }
+            })?;
+
+            if self.measurements.is_misaligned() {
+                self.decoder.realign();
+            }
+
+            // points of capsuled answers belong to previous capsule, the current one was measured after them
+            let samples_after = if MeasurementDecoder::is_delayed(ans_type) { count } else { 0 };
+
+            for (i, point) in points[..count].iter_mut().enumerate() {
+                if let Some(point) = point.take() {
+                    on_point(point, self.sample_timestamp(received_us, count - 1 - i + samples_after));
+                }
+            }
+
+            return Ok(Some(count));
+        }
+
+        Ok(None)
+    }
+
+    /// estimate when a sample was measured, given that `samples_after` samples were measured after it
+    fn sample_timestamp(&self, received_us: u64, samples_after: usize) -> u64 {
+        received_us
+            .checked_sub(self.sample_ns.saturating_mul(samples_after as u64) / 1000)
+            .unwrap_or(received_us)
+    }
+}
+
+/// payload of `RPLIDAR_CMD_GET_LIDAR_CONF`, the configuration type and the scan mode for per mode configurations
+pub(crate) fn conf_payload(config_type: u32, scan_mode: Option<u16>) -> ([u8; 6], usize) {
+    let mut payload = [0u8; 6];
+    LittleEndian::write_u32(&mut payload[0..4], config_type);
+
+    match scan_mode {
+        Some(scan_mode) => {
+            LittleEndian::write_u16(&mut payload[4..6], scan_mode);
+            (payload, 6)
+        }
+        None => (payload, 4),
+    }
+}
+
+/// value of a `RPLIDAR_ANS_TYPE_GET_LIDAR_CONF` answer
+pub(crate) fn conf_value(config_type: u32, answer: Answer<'_>) -> Option<&[u8]> {
+    let data = answer.data;
+
+    if answer.ans_type != RPLIDAR_ANS_TYPE_GET_LIDAR_CONF || data.len() < 4 || LittleEndian::read_u32(&data[0..4]) != config_type {
+        None
+    } else {
+        Some(&data[4..])
+    }
+}
+
+/// little endian integer of exactly `size` bytes
+pub(crate) fn uint_value<E>(data: &[u8], size: usize) -> Result<u32, DriverError<E>> {
+    if data.len() != size {
+        return Err(DriverError::AnswerMismatch);
+    }
+    Ok(LittleEndian::read_uint(data, size) as u32)
+}
+
+/// typical scan mode of LIDARs without `RPLIDAR_CMD_GET_LIDAR_CONF`
+pub(crate) fn legacy_typical_scan_mode(info: &RplidarResponseDeviceInfo) -> Option<u16> {
+    legacy_scan_mode_count(info).map(|count| count - 1)
+}
+
+/// number of scan modes of LIDARs without `RPLIDAR_CMD_GET_LIDAR_CONF`
+pub(crate) fn legacy_scan_mode_count(info: &RplidarResponseDeviceInfo) -> Option<u16> {
+    if info.firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
+        Some(if info.model >= 0x20u8 { 2u16 } else { 1u16 })
+    } else {
+        None
+    }
+}
+
+/// command and payload starting a scan in `scan_mode`, returns the payload size
+pub(crate) fn scan_command(scan_mode: u16, options: &ScanOptions) -> (u8, [u8; 5], usize) {
+    let mut payload = [0u8; 5];
+
+    match (scan_mode, options.force_scan) {
+        (0, false) => (RPLIDAR_CMD_SCAN, payload, 0),
+        (0, true) => (RPLIDAR_CMD_FORCE_SCAN, payload, 0),
+        _ => {
+            payload[0] = scan_mode as u8;
+            LittleEndian::write_u16(&mut payload[1..3], options.options as u16);
+            (RPLIDAR_CMD_EXPRESS_SCAN, payload, 5)
+        }
+    }
+}
+
+/// parse an answer of the expected type
+pub(crate) fn expect_answer<T: AnswerData, E>(answer: Answer, ans_type: u8) -> Result<T, DriverError<E>> {
+    if answer.ans_type != ans_type {
+        return Err(DriverError::AnswerMismatch);
+    }
+    Ok(parse_answer(answer.data)?)
+}
+
+/// motor control support flag of a `RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG` answer
+pub(crate) fn motor_ctrl_support<E>(answer: Answer) -> Result<bool, DriverError<E>> {
+    if answer.ans_type != RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG {
+        return Err(DriverError::AnswerMismatch);
+    }
+    let flag = uint_value(answer.data, 4)?;
+    Ok((flag & RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK) == RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK)
+}
+
+/// Commands of the driver front ends, written once for blocking and async transports
+///
+/// The front end provides `send`, writing a command, and `fill`, reading until received bytes
+/// are pending or the deadline passes. `driver_commands!(async)` awaits them.
+macro_rules! driver_commands {
+    (blocking) => {
+        driver_commands!(@commands [] []);
+    };
+    (async) => {
+        driver_commands!(@commands [async] [.await]);
+    };
+    (@commands [$($async:tt)*] [$($await:tt)*]) => {
+        /// set the largest plausible size of each answer type
+        ///
+        /// Answer headers announcing more are dropped as damaged and the decoder resyncs at once.
+        pub fn set_answer_size_limits(&mut self, limits: AnswerSizeLimits) {
+            self.core.set_answer_size_limits(limits);
+        }
+
+        /// number of answer headers dropped for announcing implausibly large answers
+        pub fn rejected_headers(&self) -> usize {
+            self.core.rejected_headers()
+        }
+
+        /// number of legacy measurement nodes dropped for wrong check bits
+        pub fn invalid_nodes(&self) -> usize {
+            self.core.invalid_nodes()
+        }
+
+        /// send a command and parse its answer, the first answer received
+        $($async)* fn invoke<R, F>(&mut self, cmd: u8, payload: &[u8], timeout_ms: u64, parse: F) -> DriverResult<R, T::Error>
+        where
+            F: FnOnce(Answer) -> DriverResult<R, T::Error>,
+        {
+            self.core.restart();
+            self.send(cmd, payload)$($await)*?;
+
+            let deadline = Deadline::after(&self.clock, timeout_ms);
+
+            loop {
+                self.fill(&deadline)$($await)*?;
+
+                if self.core.decode_answer()?.is_some() {
+                    break;
+                }
+            }
+
+            // the completed answer is kept by the decoder until the next decode
+            match self.core.answer() {
+                Some(answer) => parse(answer),
+                None => Err(DriverError::AnswerMismatch),
+            }
+        }
+
+        /// get LIDAR configuration of `config_type`, for `scan_mode` if the configuration is per mode
+        $($async)* fn get_lidar_conf<R, F>(&mut self, config_type: u32, scan_mode: Option<u16>, timeout_ms: u64, parse: F) -> DriverResult<R, T::Error>
+        where
+            F: FnOnce(&[u8]) -> DriverResult<R, T::Error>,
+        {
+            let (payload, size) = conf_payload(config_type, scan_mode);
+
+            self.invoke(RPLIDAR_CMD_GET_LIDAR_CONF, &payload[..size], timeout_ms, |answer| {
+                match conf_value(config_type, answer) {
+                    Some(value) => parse(value),
+                    None => Err(DriverError::AnswerMismatch),
+                }
+            })$($await)*
+        }
+
+        /// get device info
+        pub $($async)* fn get_device_info(&mut self, timeout_ms: u64) -> DriverResult<RplidarResponseDeviceInfo, T::Error> {
+            self.invoke(RPLIDAR_CMD_GET_DEVICE_INFO, &[], timeout_ms, |answer| {
+                expect_answer(answer, RPLIDAR_ANS_TYPE_DEVINFO)
+            })$($await)*
+        }
+
+        /// get device health
+        pub $($async)* fn get_device_health(&mut self, timeout_ms: u64) -> DriverResult<Health, T::Error> {
+            self.invoke(RPLIDAR_CMD_GET_DEVICE_HEALTH, &[], timeout_ms, |answer| {
+                expect_answer::<RplidarResponseDeviceHealth, _>(answer, RPLIDAR_ANS_TYPE_DEVHEALTH).map(Health::from)
+            })$($await)*
+        }
+
+        /// check if the LIDAR supports motor control
+        pub $($async)* fn check_motor_ctrl_support(&mut self, timeout_ms: u64) -> DriverResult<bool, T::Error> {
+            self.invoke(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &[0u8; 4], timeout_ms, motor_ctrl_support)$($await)*
+        }
+
+        /// get typical scan mode of the LIDAR
+        pub $($async)* fn get_typical_scan_mode(&mut self, timeout_ms: u64) -> DriverResult<u16, T::Error> {
+            let info = self.get_device_info(timeout_ms)$($await)*?;
+
+            if let Some(scan_mode) = legacy_typical_scan_mode(&info) {
+                return Ok(scan_mode);
+            }
+
+            self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_TYPICAL, None, timeout_ms, |value| {
+                Ok(uint_value(value, 2)? as u16)
+            })$($await)*
+        }
+
+        /// get the description of `scan_mode`
+        pub $($async)* fn get_scan_mode(&mut self, scan_mode: u16, timeout_ms: u64) -> DriverResult<ScanModeInfo, T::Error> {
+            let us_per_sample = self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, Some(scan_mode), timeout_ms, |value| {
+                uint_value(value, 4)
+            })$($await)*?;
+            let max_distance = self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, Some(scan_mode), timeout_ms, |value| {
+                uint_value(value, 4)
+            })$($await)*?;
+            let ans_type = self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, Some(scan_mode), timeout_ms, |value| {
+                uint_value(value, 1)
+            })$($await)*?;
+
+            self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_NAME, Some(scan_mode), timeout_ms, |name| {
+                let us_per_sample = us_per_sample as f32 / 256f32;
+                let max_distance = max_distance as f32 / 256f32;
+                Ok(ScanModeInfo::new(scan_mode, us_per_sample, max_distance, ans_type as u8, name)?)
+            })$($await)*
+        }
+
+        /// pass every scan mode supported by the LIDAR to `on_mode`, returns the number of scan modes
+        pub $($async)* fn get_all_supported_scan_modes<F: FnMut(ScanModeInfo)>(&mut self, timeout_ms: u64, mut on_mode: F) -> DriverResult<u16, T::Error> {
+            let info = self.get_device_info(timeout_ms)$($await)*?;
+
+            if let Some(count) = legacy_scan_mode_count(&info) {
+                for scan_mode in 0..count {
+                    on_mode(ScanModeInfo::legacy(scan_mode));
+                }
+                return Ok(count);
+            }
+
+            let count = self.get_lidar_conf(RPLIDAR_CONF_SCAN_MODE_COUNT, None, timeout_ms, |value| {
+                Ok(uint_value(value, 2)? as u16)
+            })$($await)*?;
+
+            for scan_mode in 0..count {
+                on_mode(self.get_scan_mode(scan_mode, timeout_ms)$($await)*?);
+            }
+
+            Ok(count)
+        }
+
+        /// stop measurement
+        ///
+        /// Measurements are loop answers without sync bytes, the decoder is reset so the
+        /// answers of the next commands aren't taken for measurements.
+        pub $($async)* fn stop(&mut self) -> DriverResult<(), T::Error> {
+            self.send(RPLIDAR_CMD_STOP, &[])$($await)*?;
+            self.core.restart();
+            Ok(())
+        }
+
+        /// reset the LIDAR core
+        pub $($async)* fn core_reset(&mut self) -> DriverResult<(), T::Error> {
+            self.send(RPLIDAR_CMD_RESET, &[])$($await)*
+        }
+
+        /// set motor PWM (via accessory board)
+        pub $($async)* fn set_motor_pwm(&mut self, pwm: u16) -> DriverResult<(), T::Error> {
+            let mut payload = [0u8; 2];
+            LittleEndian::write_u16(&mut payload, pwm);
+            self.send(RPLIDAR_CMD_SET_MOTOR_PWM, &payload)$($await)*
+        }
+
+        /// set motor speed in RPM (LIDARs controlling the motor themselves, e.g. S series)
+        pub $($async)* fn set_motor_speed(&mut self, rpm: u16) -> DriverResult<(), T::Error> {
+            let mut payload = [0u8; 2];
+            LittleEndian::write_u16(&mut payload, rpm);
+            self.send(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &payload)$($await)*
+        }
+
+        /// start motor
+        pub $($async)* fn start_motor(&mut self) -> DriverResult<(), T::Error> {
+            self.set_motor_pwm(RPLIDAR_DEFAULT_MOTOR_PWM)$($await)*
+        }
+
+        /// stop motor
+        pub $($async)* fn stop_motor(&mut self) -> DriverResult<(), T::Error> {
+            self.set_motor_pwm(0)$($await)*
+        }
+
+        /// start scan in the typical scan mode
+        pub $($async)* fn start_scan(&mut self, timeout_ms: u64) -> DriverResult<ScanModeInfo, T::Error> {
+            self.start_scan_with_options(&ScanOptions::default(), timeout_ms)$($await)*
+        }
+
+        /// start scan with options, in the typical scan mode unless the options name one
+        pub $($async)* fn start_scan_with_options(&mut self, options: &ScanOptions, timeout_ms: u64) -> DriverResult<ScanModeInfo, T::Error> {
+            let scan_mode = match options.scan_mode {
+                Some(scan_mode) => scan_mode,
+                None => self.get_typical_scan_mode(timeout_ms)$($await)*?,
+            };
+
+            let info = self.get_scan_mode(scan_mode, timeout_ms)$($await)*?;
+            self.core.start_scan(&info);
+
+            let (cmd, payload, size) = scan_command(scan_mode, options);
+            self.send(cmd, &payload[..size])$($await)*?;
+
+            Ok(info)
+        }
+
+        /// wait for the next measurement answer and pass its points to `on_point`, returns the number of points
+        ///
+        /// Capsuled answers are decoded when the next capsule arrives, so the first call after
+        /// starting a scan may return no points.
+        pub $($async)* fn grab_scan_points<F: FnMut(ScanPoint)>(&mut self, timeout_ms: u64, mut on_point: F) -> DriverResult<usize, T::Error> {
+            self.grab_timed_scan_points(timeout_ms, |point, _| on_point(point))$($await)*
+        }
+
+        /// like `grab_scan_points`, passing the estimated measurement time of each point in microseconds of the clock
+        pub $($async)* fn grab_timed_scan_points<F: FnMut(ScanPoint, u64)>(&mut self, timeout_ms: u64, mut on_point: F) -> DriverResult<usize, T::Error> {
+            let deadline = Deadline::after(&self.clock, timeout_ms);
+
+            loop {
+                self.fill(&deadline)$($await)*?;
+
+                let received_us = self.clock.now_us();
+                if let Some(count) = self.core.decode_points(received_us, &mut on_point)? {
+                    return Ok(count);
+                }
+            }
+        }
+    };
+}
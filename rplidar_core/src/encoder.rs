@@ -0,0 +1,113 @@
+use byteorder::{ByteOrder, LittleEndian};
+use super::checksum::Checksum;
+use super::decoder::{
+    RPLIDAR_ANS_HEADER_SIZE, RPLIDAR_ANS_HEADER_SIZE_MASK, RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT, RPLIDAR_ANS_PKTFLAG_LOOP,
+    RPLIDAR_ANS_SYNC_BYTES,
+};
+use super::errors::*;
+
+pub(crate) const RPLIDAR_CMD_SYNC_BYTE: u8 = 0xA5;
+pub(crate) const RPLIDAR_CMDFLAG_HAS_PAYLOAD: u8 = 0x80;
+
+/// Size of an encoded answer header, including the sync bytes
+pub const RPLIDAR_ANS_HEADER_LEN: usize = RPLIDAR_ANS_SYNC_BYTES.len() + RPLIDAR_ANS_HEADER_SIZE;
+
+/// Size of the encoded command (must be greater than or equal to the actual encoded size)
+pub fn encoded_command_size(payload: &[u8]) -> Result<usize> {
+    if payload.len() > 255 {
+        return Err(Error::PayloadTooLarge);
+    }
+
+    if payload.is_empty() {
+        Ok(2)
+    } else {
+        Ok(4 + payload.len())
+    }
+}
+
+/// Encode command into byte array, returns the encoded size
+pub fn encode_command(cmd: u8, payload: &[u8], bytes: &mut [u8]) -> Result<usize> {
+    let encoded_size = encoded_command_size(payload)?;
+
+    if encoded_size > bytes.len() {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let cmd = if payload.is_empty() {
+        cmd
+    } else {
+        cmd | RPLIDAR_CMDFLAG_HAS_PAYLOAD
+    };
+
+    bytes[0] = RPLIDAR_CMD_SYNC_BYTE;
+    bytes[1] = cmd;
+
+    if !payload.is_empty() {
+        let mut checksum = Checksum::new();
+
+        checksum.push_slice(&bytes[0..2]);
+        checksum.push(payload.len() as u8);
+        checksum.push_slice(payload);
+
+        bytes[2] = payload.len() as u8;
+        bytes[3..3 + payload.len()].clone_from_slice(payload);
+        bytes[3 + payload.len()] = checksum.checksum();
+    }
+
+    Ok(encoded_size)
+}
+
+/// Encode the header of an answer with `size` bytes of data, returns the encoded size
+///
+/// This is the device side of the protocol, e.g. for simulators and proxies. Loop answers
+/// (measurements) repeat `size` bytes of data after a single header until stopped.
+pub fn encode_answer_header(ans_type: u8, size: usize, is_loop: bool, bytes: &mut [u8]) -> Result<usize> {
+    if size > RPLIDAR_ANS_HEADER_SIZE_MASK as usize {
+        return Err(Error::AnswerTooLarge(size));
+    }
+
+    if bytes.len() < RPLIDAR_ANS_HEADER_LEN {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let flag = if is_loop { RPLIDAR_ANS_PKTFLAG_LOOP as u32 } else { 0 };
+
+    bytes[0..2].copy_from_slice(&RPLIDAR_ANS_SYNC_BYTES);
+    LittleEndian::write_u32(&mut bytes[2..6], size as u32 | (flag << RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT));
+    bytes[6] = ans_type;
+
+    Ok(RPLIDAR_ANS_HEADER_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_encode() {
+        let mut buf = [0u8; 16];
+
+        assert_eq!(encode_command(0x25, &[], &mut buf), Ok(2));
+        assert_eq!(buf[0..2], [0xA5, 0x25]);
+
+        assert_eq!(encode_command(0x82, &[0; 5], &mut buf), Ok(9));
+        assert_eq!(buf[0..9], [0xA5, 0x82, 0x05, 0, 0, 0, 0, 0, 0x22]);
+
+        assert_eq!(encode_command(0x82, &[0; 15], &mut buf), Err(Error::BufferTooSmall));
+        assert_eq!(encode_command(0x84, &[0; 256], &mut [0u8; 300]), Err(Error::PayloadTooLarge));
+    }
+
+    #[test]
+    fn answer_header_encode() {
+        let mut buf = [0u8; 8];
+
+        assert_eq!(encode_answer_header(0x04, 20, false, &mut buf), Ok(7));
+        assert_eq!(buf[0..7], crate::test_util::answer(0x04, 0, &[0; 20])[0..7]);
+
+        assert_eq!(encode_answer_header(0x82, 84, true, &mut buf), Ok(7));
+        assert_eq!(buf[0..7], crate::test_util::answer(0x82, 1, &[0; 84])[0..7]);
+
+        assert_eq!(encode_answer_header(0x04, 1 << 30, false, &mut buf), Err(Error::AnswerTooLarge(1 << 30)));
+        assert_eq!(encode_answer_header(0x04, 20, false, &mut buf[0..6]), Err(Error::BufferTooSmall));
+    }
+}
@@ -0,0 +1,50 @@
+use core::fmt;
+
+/// Errors of protocol encoding and decoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The buffer is too small for message encoding
+    BufferTooSmall,
+
+    /// The command payload is longer than 255 bytes
+    PayloadTooLarge,
+
+    /// The answer doesn't fit into the decode buffer
+    AnswerTooLarge(usize),
+
+    /// Loop answer (e.g. measurements) announced with no response size
+    EmptyLoopAnswer,
+
+    /// The answer length doesn't match the answer type
+    LengthMismatch,
+
+    /// The sync bits of a measurement answer are missing
+    SyncMismatch,
+
+    /// The checksum of a measurement answer doesn't match
+    ChecksumMismatch,
+
+    /// The answer is not a measurement
+    UnexpectedAnswer(u8),
+
+    /// The scan mode name isn't valid UTF-8
+    InvalidScanModeName,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::BufferTooSmall => write!(f, "buffer is too small for message encoding"),
+            Error::PayloadTooLarge => write!(f, "payload too big"),
+            Error::AnswerTooLarge(size) => write!(f, "answer too big ({} bytes)", size),
+            Error::EmptyLoopAnswer => write!(f, "received loop answer with no response size"),
+            Error::LengthMismatch => write!(f, "data length mismatch"),
+            Error::SyncMismatch => write!(f, "sync mismatch"),
+            Error::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Error::UnexpectedAnswer(ans_type) => write!(f, "unexpected response 0x{:02x}", ans_type),
+            Error::InvalidScanModeName => write!(f, "invalid scan mode name"),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
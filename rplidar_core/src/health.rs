@@ -0,0 +1,23 @@
+use super::answers::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Health status of device
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Health {
+    Healthy,
+    Warning(u16),
+    Error(u16)
+}
+
+impl From<RplidarResponseDeviceHealth> for Health {
+    fn from(resp: RplidarResponseDeviceHealth) -> Health {
+        match resp.status {
+            RPLIDAR_HEALTH_STATUS_OK => Health::Healthy,
+            RPLIDAR_HEALTH_STATUS_WARNING => Health::Warning(resp.error_code),
+            RPLIDAR_HEALTH_STATUS_ERROR => Health::Error(resp.error_code),
+            _ => Health::Healthy
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! # Rplidar Core
+//!
+//! `rplidar_core` is the `no_std` part of the Slamtec Rplidar driver: answer
+//! structures, command encoding, answer decoding and measurement decoding, and the
+//! device side (command decoding, answer header encoding) for simulators and proxies.
+//!
+//! It doesn't depend on `std` or on any I/O, so it can be used to decode RPLIDAR
+//! data on microcontrollers. Enable the `alloc` feature to decode answers into
+//! `Vec` buffers.
+//!
+//! `RplidarDriver` drives a LIDAR over any blocking `Transport`, using a `Clock` for
+//! timeouts and measurement timestamps. The `embedded-io` feature makes every
+//! `embedded_io` serial port a `Transport`, and the `embedded-io-async` feature adds
+//! `AsyncRplidarDriver` for `embedded_io_async` serial ports. Both run the same commands.
+//!
+//! # Example
+//! ```ignore
+//! let mut decoder = AnswerDecoder::new(ArrayBuffer::<256>::new());
+//! let mut measurements = MeasurementDecoder::new();
+//!
+//! let (consumed, answer) = decoder.decode(&received)?;
+//! if let Some(answer) = answer {
+//!     measurements.decode(answer.ans_type, answer.data, |point| handle(point))?;
+//! }
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+extern crate byteorder;
+extern crate crc;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "embedded-io")]
+extern crate embedded_io;
+#[cfg(feature = "embedded-io-async")]
+extern crate embedded_io_async;
+
+pub mod answers;
+pub mod cmds;
+mod capsuled_parser;
+mod ultra_capsuled_parser;
+mod checksum;
+mod errors;
+mod scan_point;
+mod health;
+mod decoder;
+mod encoder;
+mod command_decoder;
+mod measurement;
+mod clock;
+mod scan_mode;
+mod transport;
+#[macro_use]
+mod driver;
+mod blocking;
+#[cfg(feature = "embedded-io-async")]
+mod asynch;
+#[cfg(test)]
+mod test_util;
+
+/// Parser internals for benchmarks, not a stable API
+#[cfg(feature = "internals")]
+#[doc(hidden)]
+pub mod internals {
+    pub use super::capsuled_parser::parse_capsuled;
+    pub use super::ultra_capsuled_parser::{calc_angle_offset_q16, parse_ultra_capsuled, varbit_scale_decode};
+}
+
+pub use self::checksum::Checksum;
+pub use self::errors::*;
+pub use self::scan_point::ScanPoint;
+pub use self::health::Health;
+pub use self::decoder::{
+    default_max_answer_size, Answer, AnswerBuffer, AnswerDecoder, AnswerSizeLimits, ArrayBuffer,
+    RPLIDAR_DEFAULT_MAX_ANSWER_SIZE, RPLIDAR_MAX_ANSWER_SIZE_OVERRIDES, RPLIDAR_MAX_CONF_ANSWER_SIZE,
+};
+pub use self::encoder::{encode_answer_header, encode_command, encoded_command_size, RPLIDAR_ANS_HEADER_LEN};
+pub use self::command_decoder::{Command, CommandDecoder};
+pub use self::measurement::{parse_answer, AnswerData, CachedPrevCapsule, MeasurementDecoder};
+pub use self::clock::Clock;
+pub use self::scan_mode::{ScanModeInfo, ScanOptions, RPLIDAR_MAX_SCAN_MODE_NAME_SIZE};
+pub use self::transport::Transport;
+pub use self::driver::{DriverError, DriverResult};
+pub use self::blocking::RplidarDriver;
+#[cfg(feature = "embedded-io-async")]
+pub use self::asynch::AsyncRplidarDriver;
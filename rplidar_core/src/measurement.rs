@@ -0,0 +1,307 @@
+use byteorder::{ByteOrder, LittleEndian};
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+use crc::crc32;
+use super::answers::*;
+use super::capsuled_parser::parse_capsuled;
+use super::checksum::Checksum;
+use super::errors::*;
+use super::scan_point::ScanPoint;
+use super::ultra_capsuled_parser::parse_ultra_capsuled;
+
+/// The previous capsule, measurement nodes of a capsule are decoded when the next one arrives
+#[derive(Debug, Clone, PartialEq)]
+pub enum CachedPrevCapsule {
+    None,
+    Capsuled(RplidarResponseCapsuleMeasurementNodes),
+    UltraCapsuled(RplidarResponseUltraCapsuleMeasurementNodes),
+}
+
+/// Answer structures which can be copied out of received bytes
+///
+/// Only implemented for plain `repr(C, packed)` structures of integers, so every
+/// bit pattern is a valid value.
+pub trait AnswerData: Copy {}
+
+impl AnswerData for RplidarResponseDeviceInfo {}
+impl AnswerData for RplidarResponseDeviceHealth {}
+impl AnswerData for RplidarResponseMeasurementNode {}
+impl AnswerData for RplidarResponseCapsuleMeasurementNodes {}
+impl AnswerData for RplidarResponseHqCapsuledMeasurementNodes {}
+impl AnswerData for RplidarResponseUltraCapsuleMeasurementNodes {}
+
+/// copy an answer structure out of the received bytes
+pub fn parse_answer<T: AnswerData>(data: &[u8]) -> Result<T> {
+    if data.len() != size_of::<T>() {
+        return Err(Error::LengthMismatch);
+    }
+
+    // the length is checked and `AnswerData` types have no invalid bit patterns
+    Ok(unsafe { read_unaligned(data.as_ptr() as *const T) })
+}
+
+/// misalignment score after which the loop answer is considered out of step
+///
+/// Invalid legacy nodes add 2 to the score and valid ones take 1 off. Out of step nodes
+/// pass the checks by chance about every other node, they still raise the score.
+const MISALIGNED_SCORE: usize = 6;
+
+/// Decoder of measurement answers into scan points
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeasurementDecoder {
+    cached_prev_capsule: CachedPrevCapsule,
+    invalid_nodes: usize,
+    misalignment: usize,
+    last_node_invalid: bool,
+}
+
+impl MeasurementDecoder {
+    pub fn new() -> MeasurementDecoder {
+        MeasurementDecoder {
+            cached_prev_capsule: CachedPrevCapsule::None,
+            invalid_nodes: 0,
+            misalignment: 0,
+            last_node_invalid: false,
+        }
+    }
+
+    /// forget the cached capsule, should be called when a new scan is started
+    pub fn reset(&mut self) {
+        self.cached_prev_capsule = CachedPrevCapsule::None;
+        self.misalignment = 0;
+        self.last_node_invalid = false;
+    }
+
+    /// number of legacy nodes dropped for wrong check bits
+    pub fn invalid_nodes(&self) -> usize {
+        self.invalid_nodes
+    }
+
+    /// true if the last legacy node was invalid after repeated ones, the loop answer has likely lost bytes
+    ///
+    /// The answer decoder should then be realigned, see `AnswerDecoder::realign`.
+    pub fn is_misaligned(&self) -> bool {
+        self.last_node_invalid && self.misalignment >= MISALIGNED_SCORE
+    }
+
+    /// check if the answer type is a measurement answer
+    pub fn is_measurement(ans_type: u8) -> bool {
+        matches!(
+            ans_type,
+            RPLIDAR_ANS_TYPE_MEASUREMENT
+                | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED
+                | RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA
+                | RPLIDAR_ANS_TYPE_MEASUREMENT_HQ
+        )
+    }
+
+    /// check if nodes decoded from this answer type belong to the previous answer
+    ///
+    /// Capsuled answers are decoded when the next capsule is received, so the decoded
+    /// nodes were measured one answer earlier than the answer which produced them.
+    pub fn is_delayed(ans_type: u8) -> bool {
+        ans_type == RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED || ans_type == RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA
+    }
+
+    /// decode a measurement answer, passing each scan point to `on_point`, returns the number of points
+    ///
+    /// Legacy nodes with wrong check bits are dropped and counted, decoding them returns no point.
+    pub fn decode<F: FnMut(ScanPoint)>(&mut self, ans_type: u8, data: &[u8], mut on_point: F) -> Result<usize> {
+        let mut count = 0;
+        let mut on_node = |node: RplidarResponseMeasurementNodeHq| {
+            on_point(ScanPoint::from(node));
+            count += 1;
+        };
+
+        match ans_type {
+            RPLIDAR_ANS_TYPE_MEASUREMENT => {
+                let node: RplidarResponseMeasurementNode = parse_answer(data)?;
+                self.last_node_invalid = !is_valid_legacy_node(&node);
+                if self.last_node_invalid {
+                    self.invalid_nodes = self.invalid_nodes.wrapping_add(1);
+                    self.misalignment = self.misalignment.saturating_add(2);
+                } else {
+                    self.misalignment = self.misalignment.saturating_sub(1);
+                    on_node(legacy_to_hq(node));
+                }
+            }
+            RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => {
+                check_sync_and_checksum(data)?;
+                self.cached_prev_capsule = parse_capsuled(&self.cached_prev_capsule, parse_answer(data)?, &mut on_node);
+            }
+            RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => {
+                check_sync_and_checksum(data)?;
+                self.cached_prev_capsule = parse_ultra_capsuled(&self.cached_prev_capsule, parse_answer(data)?, &mut on_node);
+            }
+            RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => {
+                check_sync_and_checksum_hq(data)?;
+                let nodes: RplidarResponseHqCapsuledMeasurementNodes = parse_answer(data)?;
+                let parsed_nodes = nodes.nodes;
+                for node in parsed_nodes.iter() {
+                    on_node(*node);
+                }
+            }
+            _ => {
+                return Err(Error::UnexpectedAnswer(ans_type));
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+impl Default for MeasurementDecoder {
+    fn default() -> MeasurementDecoder {
+        MeasurementDecoder::new()
+    }
+}
+
+/// check the inverted sync bit and the check bit of a legacy measurement node
+fn is_valid_legacy_node(node: &RplidarResponseMeasurementNode) -> bool {
+    let sync = node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT;
+    let inverse_sync = (node.sync_quality & RPLIDAR_RESP_MEASUREMENT_INVERSE_SYNCBIT) >> 1;
+    sync != inverse_sync && ({ node.angle_q6_checkbit } & RPLIDAR_RESP_MEASUREMENT_CHECKBIT) != 0
+}
+
+/// convert legacy measurement node
+fn legacy_to_hq(node: RplidarResponseMeasurementNode) -> RplidarResponseMeasurementNodeHq {
+    RplidarResponseMeasurementNodeHq {
+        angle_z_q14: ((((node.angle_q6_checkbit as u32)
+            >> RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT as u32)
+            << 8)
+            / 90) as u16,
+        dist_mm_q2: node.distance_q2 as u32,
+        flag: node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT,
+        quality: (node.sync_quality >> RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8)
+            << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8,
+    }
+}
+
+fn check_sync_and_checksum(data: &[u8]) -> Result<()> {
+    if data.len() < 2 {
+        return Err(Error::LengthMismatch);
+    }
+
+    if (data[0] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 {
+        return Err(Error::SyncMismatch);
+    }
+
+    if (data[1] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 {
+        return Err(Error::SyncMismatch);
+    }
+
+    let recv_checksum = (data[0] & 0xf) | (data[1] << 4);
+    let mut checksum = Checksum::new();
+    checksum.push_slice(&data[2..]);
+
+    if checksum.checksum() != recv_checksum {
+        Err(Error::ChecksumMismatch)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_sync_and_checksum_hq(data: &[u8]) -> Result<()> {
+    if data.len() != size_of::<RplidarResponseHqCapsuledMeasurementNodes>() {
+        return Err(Error::LengthMismatch);
+    }
+
+    if data[0] != RPLIDAR_RESP_MEASUREMENT_HQ_SYNC {
+        return Err(Error::SyncMismatch);
+    }
+
+    let checksum = crc32::checksum_ieee(&data[0..data.len()-4]);
+    let recv_checksum = LittleEndian::read_u32(&data[data.len()-4..data.len()]);
+
+    if checksum != recv_checksum {
+        Err(Error::ChecksumMismatch)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{capsule, ultra_capsule};
+
+    #[test]
+    fn decode_capsuled() {
+        let mut decoder = MeasurementDecoder::new();
+        let mut points = std::vec::Vec::new();
+
+        let count = decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &capsule(0, 4000), |p| points.push(p)).unwrap();
+        assert_eq!(count, 0);
+
+        // 32 nodes from 0 to 8 degrees (q6)
+        let count = decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &capsule(8 << 6, 4000), |p| points.push(p)).unwrap();
+        assert_eq!(count, 32);
+        assert!(points.iter().all(|p| p.dist_mm_q2 == 4000 && p.is_valid()));
+
+        let mut corrupted = capsule(16 << 6, 4000);
+        corrupted[10] ^= 1;
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &corrupted, |_| {}), Err(Error::ChecksumMismatch));
+        assert_eq!(decoder.decode(0x04, &[], |_| {}), Err(Error::UnexpectedAnswer(0x04)));
+    }
+
+    #[test]
+    fn decode_legacy() {
+        let mut decoder = MeasurementDecoder::new();
+        let mut points = std::vec::Vec::new();
+
+        // sync, quality 10, 90 degrees, 1m
+        let data = [(10 << 2) | 1, ((90 << 6) << 1 | 1) as u8, ((90 << 6) >> 7) as u8, 0xa0, 0x0f];
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &data, |p| points.push(p)), Ok(1));
+        assert_eq!(points[0].angle_z_q14, 16384);
+        assert_eq!(points[0].distance(), 1f32);
+        assert!(points[0].is_sync());
+    }
+
+    #[test]
+    fn drop_invalid_legacy_nodes() {
+        let mut decoder = MeasurementDecoder::new();
+        let mut points = std::vec::Vec::new();
+
+        let data = [(10 << 2) | 2, ((90 << 6) << 1 | 1) as u8, ((90 << 6) >> 7) as u8, 0xa0, 0x0f];
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &data, |p| points.push(p)), Ok(1));
+
+        // sync bit equal to its inverse, then check bit missing
+        let mut corrupted = data;
+        corrupted[0] |= 1;
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupted, |p| points.push(p)), Ok(0));
+        let mut corrupted = data;
+        corrupted[1] &= !1;
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupted, |p| points.push(p)), Ok(0));
+        assert_eq!(points.len(), 1);
+        assert_eq!(decoder.invalid_nodes(), 2);
+        assert!(!decoder.is_misaligned());
+
+        // invalid nodes outweigh valid ones
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &data, |_| {}).unwrap();
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupted, |_| {}).unwrap();
+        assert!(!decoder.is_misaligned());
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupted, |_| {}).unwrap();
+        assert!(decoder.is_misaligned());
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &data, |p| points.push(p)), Ok(1));
+        assert!(!decoder.is_misaligned());
+        assert_eq!(decoder.invalid_nodes(), 4);
+
+        decoder.reset();
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT, &corrupted, |_| {}).unwrap();
+        assert!(!decoder.is_misaligned());
+    }
+
+    #[test]
+    fn decode_damaged_capsules() {
+        let mut decoder = MeasurementDecoder::new();
+
+        // start angles above 360 degrees
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &capsule(0x7fff, 4000), |_| {}).unwrap();
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, &capsule(0, 4000), |_| {}), Ok(32));
+
+        // predicted distances below zero
+        let cabin = 0x010 | (0x3EF << 12) | (0x3EF << 22);
+        decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, &ultra_capsule(0x7fff, cabin), |_| {}).unwrap();
+        assert_eq!(decoder.decode(RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, &ultra_capsule(0, cabin), |_| {}), Ok(96));
+    }
+}
@@ -0,0 +1,139 @@
+use core::str;
+use super::answers::*;
+use super::errors::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Longest scan mode name kept by `ScanModeInfo`, longer names are truncated
+pub const RPLIDAR_MAX_SCAN_MODE_NAME_SIZE: usize = 64;
+
+/// Description of a scan mode, without heap allocation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanModeInfo {
+    /// The scan mode id
+    pub id: u16,
+
+    /// Microseconds per measurement sample
+    pub us_per_sample: f32,
+
+    /// Max distance of this measurement mode (meters)
+    pub max_distance: f32,
+
+    /// The answer command value of this scan mode
+    pub ans_type: u8,
+
+    name: [u8; RPLIDAR_MAX_SCAN_MODE_NAME_SIZE],
+    name_len: usize,
+}
+
+impl ScanModeInfo {
+    /// describe a scan mode, `name` is the name as received from the LIDAR, padded with NUL bytes
+    pub fn new(id: u16, us_per_sample: f32, max_distance: f32, ans_type: u8, name: &[u8]) -> Result<ScanModeInfo> {
+        let name = str::from_utf8(name).map_err(|_| Error::InvalidScanModeName)?.trim_matches('\0');
+
+        let mut name_len = name.len().min(RPLIDAR_MAX_SCAN_MODE_NAME_SIZE);
+        while !name.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+
+        let mut info = ScanModeInfo {
+            id,
+            us_per_sample,
+            max_distance,
+            ans_type,
+            name: [0u8; RPLIDAR_MAX_SCAN_MODE_NAME_SIZE],
+            name_len,
+        };
+        info.name[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+        Ok(info)
+    }
+
+    /// the name of the scan mode
+    pub fn name(&self) -> &str {
+        // only built from valid UTF-8 cut at a char boundary
+        str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// scan modes of LIDARs without `RPLIDAR_CMD_GET_LIDAR_CONF`, "Standard" and on A2 and later "Express"
+    pub(crate) fn legacy(id: u16) -> ScanModeInfo {
+        let (us_per_sample, max_distance, ans_type, name) = match id {
+            0 => (1000000f32 / 2000f32, 8f32, RPLIDAR_ANS_TYPE_MEASUREMENT, "Standard"),
+            _ => (1000000f32 / 4000f32, 16f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, "Express"),
+        };
+        ScanModeInfo::new(id, us_per_sample, max_distance, ans_type, name.as_bytes()).unwrap()
+    }
+}
+
+/// Scan options
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanOptions {
+    /// Specify this field to force use specific scan mode
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scan_mode: Option<u16>,
+
+    /// Make LIDAR scan regardless of it's spinning or not
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub force_scan: bool,
+
+    /// Parameters sent to LIDAR. Please use 0 for now
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub options: u32,
+}
+
+impl ScanOptions {
+    /// default options
+    pub fn default() -> ScanOptions {
+        ScanOptions {
+            scan_mode: None,
+            force_scan: false,
+            options: 0,
+        }
+    }
+
+    /// with specific mode
+    pub fn with_mode(scan_mode: u16) -> ScanOptions {
+        ScanOptions {
+            scan_mode: Some(scan_mode),
+            force_scan: false,
+            options: 0,
+        }
+    }
+
+    /// force scan
+    pub fn force_scan() -> ScanOptions {
+        ScanOptions {
+            scan_mode: None,
+            force_scan: true,
+            options: 0,
+        }
+    }
+
+    /// force scan with mode
+    pub fn force_scan_with_mode(scan_mode: u16) -> ScanOptions {
+        ScanOptions {
+            scan_mode: Some(scan_mode),
+            force_scan: true,
+            options: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_mode_names() {
+        let info = ScanModeInfo::new(3, 31.25, 40f32, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA, b"Boost\0\0\0").unwrap();
+        assert_eq!(info.name(), "Boost");
+
+        // cut before the last complete char
+        let long = [b'x'; RPLIDAR_MAX_SCAN_MODE_NAME_SIZE - 1].iter().chain("\u{e9}".as_bytes()).cloned().collect::<std::vec::Vec<u8>>();
+        let info = ScanModeInfo::new(3, 31.25, 40f32, 0, &long).unwrap();
+        assert_eq!(info.name().len(), RPLIDAR_MAX_SCAN_MODE_NAME_SIZE - 1);
+
+        assert_eq!(ScanModeInfo::new(3, 31.25, 40f32, 0, &[0xFF, 0xFE]), Err(Error::InvalidScanModeName));
+        assert_eq!(ScanModeInfo::legacy(1).name(), "Express");
+    }
+}
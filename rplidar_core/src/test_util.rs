@@ -0,0 +1,61 @@
+//! byte streams of a simulated LIDAR for tests
+
+use byteorder::{ByteOrder, LittleEndian};
+use super::answers::*;
+use super::checksum::Checksum;
+use std::vec::Vec;
+
+/// encode an answer, `flag` 1 for loop answers
+pub fn answer(ans_type: u8, flag: u32, data: &[u8]) -> Vec<u8> {
+    let mut bytes = std::vec![0xA5, 0x5A, 0, 0, 0, 0, ans_type];
+    LittleEndian::write_u32(&mut bytes[2..6], (data.len() as u32) | (flag << 30));
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+/// LIDAR configuration answer of `config_type`
+pub fn conf(config_type: u32, value: &[u8]) -> Vec<u8> {
+    let mut data = config_type.to_le_bytes().to_vec();
+    data.extend_from_slice(value);
+    answer(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, 0, &data)
+}
+
+/// capsule answer with all cabins measuring `dist_q2`
+pub fn capsule(start_angle_q6: u16, dist_q2: u16) -> [u8; 84] {
+    let mut data = [0u8; 84];
+    LittleEndian::write_u16(&mut data[2..4], start_angle_q6);
+    for cabin in data[4..].chunks_mut(5) {
+        LittleEndian::write_u16(&mut cabin[0..2], dist_q2);
+        LittleEndian::write_u16(&mut cabin[2..4], dist_q2);
+    }
+
+    let mut checksum = Checksum::new();
+    checksum.push_slice(&data[2..]);
+    data[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum.checksum() & 0xf);
+    data[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum.checksum() >> 4);
+    data
+}
+
+/// ultra capsule answer with all cabins set to `cabin`
+pub fn ultra_capsule(start_angle_q6: u16, cabin: u32) -> [u8; 132] {
+    let mut data = [0u8; 132];
+    LittleEndian::write_u16(&mut data[2..4], start_angle_q6);
+    for bytes in data[4..].chunks_mut(4) {
+        LittleEndian::write_u32(bytes, cabin);
+    }
+
+    let mut checksum = Checksum::new();
+    checksum.push_slice(&data[2..]);
+    data[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum.checksum() & 0xf);
+    data[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum.checksum() >> 4);
+    data
+}
+
+/// device info answer of an A2 with firmware 1.25
+pub fn device_info() -> Vec<u8> {
+    let mut data = [0u8; 20];
+    data[0] = 0x18;
+    LittleEndian::write_u16(&mut data[1..3], 0x0119);
+    data[3] = 5;
+    answer(RPLIDAR_ANS_TYPE_DEVINFO, 0, &data)
+}
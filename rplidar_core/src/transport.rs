@@ -0,0 +1,94 @@
+/// Serial port of the blocking `RplidarDriver`
+///
+/// `read` must return after a short wait when nothing was received, so the driver can
+/// check its timeouts. With the `embedded-io` feature, every `embedded_io::{Read, Write, ReadReady}`
+/// serial port is a transport.
+pub trait Transport {
+    /// error of the serial port
+    type Error;
+
+    /// write a whole command and flush it
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// read received bytes into `buf`, returns 0 if nothing was received
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// `ReadReady` keeps the driver from blocking in `read` while no data is available
+#[cfg(feature = "embedded-io")]
+impl<T> Transport for T
+where
+    T: embedded_io::Read + embedded_io::Write + embedded_io::ReadReady,
+{
+    type Error = T::Error;
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), T::Error> {
+        embedded_io::Write::write_all(self, bytes)?;
+        self.flush()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, T::Error> {
+        if self.read_ready()? {
+            embedded_io::Read::read(self, buf)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "embedded-io"))]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    /// serial port with `rx` received, failing reads which aren't ready
+    struct Uart {
+        rx: Vec<u8>,
+        tx: Vec<u8>,
+        flushed: usize,
+    }
+
+    impl embedded_io::ErrorType for Uart {
+        type Error = Infallible;
+    }
+
+    impl embedded_io::Read for Uart {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            assert!(!self.rx.is_empty(), "blocked in read");
+            let size = buf.len().min(self.rx.len());
+            buf[..size].copy_from_slice(&self.rx[..size]);
+            self.rx.drain(..size);
+            Ok(size)
+        }
+    }
+
+    impl embedded_io::ReadReady for Uart {
+        fn read_ready(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.rx.is_empty())
+        }
+    }
+
+    impl embedded_io::Write for Uart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            self.flushed = self.tx.len();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn embedded_io_transport() {
+        let mut uart = Uart { rx: std::vec![1, 2, 3], tx: Vec::new(), flushed: 0 };
+        let mut buf = [0u8; 8];
+
+        Transport::write_all(&mut uart, &[0xA5, 0x50]).unwrap();
+        assert_eq!(uart.flushed, 2);
+        assert_eq!(Transport::read(&mut uart, &mut buf), Ok(3));
+        assert_eq!(Transport::read(&mut uart, &mut buf), Ok(0));
+    }
+}
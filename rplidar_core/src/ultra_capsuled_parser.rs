@@ -0,0 +1,291 @@
+use super::measurement::CachedPrevCapsule;
+use super::answers::{RplidarResponseUltraCapsuleMeasurementNodes, RplidarResponseMeasurementNodeHq};
+use super::capsuled_parser::{ angle_diff_q8, check_sync, generate_quality, generate_flag };
+
+const ANGLE_360_Q6: i32 = 360 << 6;
+
+/// `(int)(7.5 * SDK_PI * (1 << 16) / 180.0)` of the reference SDK, offset of close samples
+const NEAR_ANGLE_OFFSET_Q16: i32 = 8578;
+
+/// `(int)(8 * SDK_PI * (1 << 16) / 180)` of the reference SDK
+const ANGLE_OFFSET_BASE_Q16: i32 = 9150;
+
+/// the reference SDK converts radians to degrees dividing by 3.14159265, scaled by 10^8
+const SDK_PI_E8: i64 = 314_159_265;
+
+struct ParsedNode {
+    pub dist_q2: u32,
+    pub angle_offset_q16: i32
+}
+
+fn get_start_angle_q8(nodes: &RplidarResponseUltraCapsuleMeasurementNodes) -> u32 {
+    return ((nodes.start_angle_sync_q6 & 0x7fffu16) as u32) << 2;
+}
+
+/// angle between the laser and the camera seeing a sample at `dist` (q2), in radians (q16)
+pub fn calc_angle_offset_q16(dist:u32) -> i32 {
+    if dist >= 50 * 4 {
+        // unsigned like the reference SDK, predicted distances may wrap to huge values
+        const K1:u32 = 98361;
+        let k2 = K1 / dist;
+
+        ANGLE_OFFSET_BASE_Q16 - ((k2 << 6) as i32) - (((k2 * k2 * k2) / 98304) as i32)
+    } else {
+        NEAR_ANGLE_OFFSET_Q16
+    }
+}
+
+/// `int(rad_q16 * 180 / SDK_PI)` of the reference SDK, without floating point
+fn rad_to_deg_q16(rad_q16: i32) -> i32 {
+    // i64 division truncates towards zero like the cast to int
+    ((rad_q16 as i64) * 180 * 100_000_000 / SDK_PI_E8) as i32
+}
+
+const RPLIDAR_VARBIT_SCALE_TARGET_BASES : [u32;5] = [
+    1 << 14, 1 << 12, 1 << 11, 1 << 9, 0
+];
+
+const RPLIDAR_VARBIT_SCALE_SCALE_BASES : [i32;5] = [
+    3328, 1792, 1280, 512, 0 
+];
+
+const RPLIDAR_VARBIT_SCALE_LEVELS : [u32;5] = [
+    4, 3, 2, 1, 0
+];
+
+/// decode varbit encoded distance to flat distance and scale level
+pub fn varbit_scale_decode(scaled:u32) -> (u32, u32) {
+    for i in 0..RPLIDAR_VARBIT_SCALE_SCALE_BASES.len() {
+        let scale_base = RPLIDAR_VARBIT_SCALE_SCALE_BASES[i];
+        let target_base = RPLIDAR_VARBIT_SCALE_TARGET_BASES[i];
+        let scale_level = RPLIDAR_VARBIT_SCALE_LEVELS[i];
+
+        let remain = (scaled as i32) - scale_base;
+        if remain >= 0 {
+            return (
+                target_base + ((remain << scale_level) as u32),
+                scale_level
+            );
+        }
+    }
+
+    return (0, 0);
+}
+
+/// parse ultra capsuled cabin to (major, predict1, predict2)
+fn parse_cabin(cabin:u32) -> (u32, i32, i32) {
+    return (
+        (cabin & 0xfffu32),
+        ((cabin << 10) as i32) >> 22,
+        (cabin as i32) >> 22
+    );
+}
+
+fn predict(base: u32, predict: i32, scale_lvl: u32) -> u32 {
+    if ((predict as u32) == 0xfffffe00u32) || (predict == 0x1ffi32) {
+        0u32
+    } else {
+        (((predict << scale_lvl) + (base as i32)) as u32) << 2
+    }
+}
+
+fn generate_nodes(dist_major: u32, next_major: u32, dist_predict1: i32, dist_predict2: i32) -> [ParsedNode;3] {
+    let (dist_major, scale_lvl_1) = varbit_scale_decode(dist_major);
+    let (next_major, scale_lvl_2) = varbit_scale_decode(next_major);
+
+    let (dist_base1, dist_base2, scale_lvl_1) = if (dist_major == 0) && (next_major != 0) {
+        (next_major, next_major, scale_lvl_2)
+    } else {
+        (dist_major, next_major, scale_lvl_1)
+    };
+
+    let dist0 = dist_major << 2;
+    let dist1 = predict(dist_base1, dist_predict1, scale_lvl_1);
+    let dist2 = predict(dist_base2, dist_predict2, scale_lvl_2);
+
+    return [
+        ParsedNode {
+            dist_q2: dist0,
+            angle_offset_q16: calc_angle_offset_q16(dist0)
+        },
+        ParsedNode {
+            dist_q2: dist1,
+            angle_offset_q16: calc_angle_offset_q16(dist1)
+        },
+        ParsedNode {
+            dist_q2: dist2,
+            angle_offset_q16: calc_angle_offset_q16(dist2)
+        },
+    ];
+}
+
+fn angle_q6_to_angle_z_q14(angle_q6: i32) -> u16 {
+    ((angle_q6 << 8) / 90) as u16
+}
+
+fn to_hq(node: &ParsedNode, cur_angle_raw_q16: u32, angle_inc_q16: u32) -> RplidarResponseMeasurementNodeHq {
+    let mut angle_q6 = ((cur_angle_raw_q16 as i32) - rad_to_deg_q16(node.angle_offset_q16)) >> 10;
+    let sync = check_sync(cur_angle_raw_q16, angle_inc_q16);
+
+    // samples just after the sync may be compensated below zero
+    if angle_q6 < 0 {
+        angle_q6 += ANGLE_360_Q6;
+    }
+    if angle_q6 >= ANGLE_360_Q6 {
+        angle_q6 -= ANGLE_360_Q6;
+    }
+
+    RplidarResponseMeasurementNodeHq {
+        angle_z_q14: angle_q6_to_angle_z_q14(angle_q6),
+        dist_mm_q2: node.dist_q2 as u32,
+        quality: generate_quality(node.dist_q2),
+        flag: generate_flag(sync)
+    }
+}
+
+/// parse the previous capsule, passing each node to `on_node`, and return the new cached capsule
+pub fn parse_ultra_capsuled<F: FnMut(RplidarResponseMeasurementNodeHq)>(cached_prev: &CachedPrevCapsule, nodes: RplidarResponseUltraCapsuleMeasurementNodes, on_node: &mut F) -> CachedPrevCapsule {
+    if let CachedPrevCapsule::UltraCapsuled(prev_capsule) = cached_prev {
+        let cur_start_angle_q8 = get_start_angle_q8(&nodes);
+        let prev_start_angle_q8 = get_start_angle_q8(&prev_capsule);
+
+        let diff_angle_q8 = angle_diff_q8(prev_start_angle_q8, cur_start_angle_q8);
+
+        let angle_inc_q16 = (diff_angle_q8 << 3) / 3;
+        let mut cur_angle_raw_q16 = prev_start_angle_q8 << 8;
+
+        let (mut cur_major, mut cur_predict1, mut cur_predict2) = parse_cabin(prev_capsule.ultra_cabins[0]);
+        let prev_cabins = prev_capsule.ultra_cabins;
+        let cabin_count = prev_cabins.len();
+
+        for i in 0..cabin_count {
+            let next_cabin = if i == cabin_count-1 {
+                nodes.ultra_cabins[0]
+            } else {
+                prev_capsule.ultra_cabins[i + 1]
+            };
+
+            let (next_major, next_predict1, next_predict2) = parse_cabin(next_cabin);
+
+            let parsed_nodes = generate_nodes(cur_major, next_major, cur_predict1, cur_predict2);
+
+            for node in parsed_nodes.iter() {
+                on_node(to_hq(&node, cur_angle_raw_q16, angle_inc_q16));
+                cur_angle_raw_q16 += angle_inc_q16;
+            }
+
+            cur_major = next_major;
+            cur_predict1 = next_predict1;
+            cur_predict2 = next_predict2;
+        }
+    }
+
+    return CachedPrevCapsule::UltraCapsuled(nodes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANGLE_360_Q16: u32 = 360 << 16;
+
+    /// PI truncated like the reference SDK does, see `SDK_PI_E8`
+    #[allow(clippy::approx_constant)]
+    const SDK_PI: f64 = 3.14159265;
+
+    /// offset and angle of the reference SDK, in floating point
+    fn sdk_angle_q6(cur_angle_raw_q16: u32, dist_q2: u32) -> i32 {
+        let mut offset_q16 = (7.5 * SDK_PI * (1 << 16) as f64 / 180.0) as i32;
+        if dist_q2 >= 50 * 4 {
+            let k2 = 98361 / dist_q2;
+            offset_q16 = (8.0 * SDK_PI * (1 << 16) as f64 / 180.0) as i32 - ((k2 << 6) as i32) - ((k2 * k2 * k2) / 98304) as i32;
+        }
+
+        let mut angle_q6 = (cur_angle_raw_q16 as i32 - ((offset_q16 * 180) as f64 / SDK_PI) as i32) >> 10;
+        if angle_q6 < 0 {
+            angle_q6 += 360 << 6;
+        }
+        if angle_q6 >= 360 << 6 {
+            angle_q6 -= 360 << 6;
+        }
+        angle_q6
+    }
+
+    /// angle (degrees) of the previous floating point implementation, which didn't wrap negative angles
+    fn float_angle(cur_angle_raw_q16: u32, dist_q2: u32) -> Option<f64> {
+        let deg_to_rad_q16 = |deg: f64| deg * SDK_PI * 65536f64 / 180f64;
+
+        let offset_q16 = if dist_q2 >= 50 * 4 {
+            let k2 = 98361 / dist_q2;
+            (deg_to_rad_q16(8f64) - ((k2 << 6) as f64) - (((k2 * k2 * k2) / 98304) as f64)) as i32
+        } else {
+            deg_to_rad_q16(7.5f64) as i32
+        };
+
+        let angle_q16 = (cur_angle_raw_q16 as i32) - (offset_q16 as f64 * 180f64 / SDK_PI) as i32;
+        if angle_q16 < 0 {
+            return None;
+        }
+        Some(((angle_q16 as u32 / 90) >> 2) as f64 * 90f64 / 16384f64)
+    }
+
+    fn hq(cur_angle_raw_q16: u32, dist_q2: u32) -> RplidarResponseMeasurementNodeHq {
+        let node = ParsedNode { dist_q2, angle_offset_q16: calc_angle_offset_q16(dist_q2) };
+        to_hq(&node, cur_angle_raw_q16, 1 << 10)
+    }
+
+    /// every distance, the offset only changes below 98361 (q2), and wrapped predictions
+    fn distances() -> impl Iterator<Item = u32> {
+        (0..=100_000u32).chain((0..32).map(|shift| u32::MAX >> shift))
+    }
+
+    #[test]
+    fn offsets_match_sdk() {
+        for dist_q2 in distances() {
+            let offset_q16 = calc_angle_offset_q16(dist_q2);
+
+            // at 0 degrees the compensated angle is the negated offset, wrapped
+            assert_eq!(i32::from(hq(0, dist_q2).angle_z_q14), (sdk_angle_q6(0, dist_q2) << 8) / 90, "dist_q2 {}", dist_q2);
+            assert_eq!(
+                rad_to_deg_q16(offset_q16),
+                ((offset_q16 * 180) as f64 / SDK_PI) as i32,
+                "dist_q2 {}",
+                dist_q2
+            );
+        }
+    }
+
+    #[test]
+    fn angles_match_sdk() {
+        let dists = [0, 4, 199, 200, 201, 756, 1000, 4000, 16000, 65532, 262140];
+
+        for cur_angle_raw_q16 in (0..ANGLE_360_Q16 + (16 << 16)).step_by(97) {
+            for &dist_q2 in dists.iter() {
+                let expected = (sdk_angle_q6(cur_angle_raw_q16, dist_q2) << 8) / 90;
+                assert_eq!(i32::from(hq(cur_angle_raw_q16, dist_q2).angle_z_q14), expected, "angle {} dist_q2 {}", cur_angle_raw_q16, dist_q2);
+            }
+        }
+    }
+
+    #[test]
+    fn angles_close_to_float_version() {
+        let mut compared = 0;
+
+        for cur_angle_raw_q16 in (0..ANGLE_360_Q16).step_by(16411) {
+            for dist_q2 in (0..65536u32).step_by(13) {
+                let previous = match float_angle(cur_angle_raw_q16, dist_q2) {
+                    Some(angle) => angle,
+                    None => continue,
+                };
+                let angle = f64::from(hq(cur_angle_raw_q16, dist_q2).angle_z_q14) * 90f64 / 16384f64;
+
+                // the SDK truncates to q6 before converting to q14
+                let diff = (angle - previous + 180f64).rem_euclid(360f64) - 180f64;
+                assert!(diff.abs() <= 1f64 / 64f64 + 90f64 / 16384f64, "angle {} dist_q2 {}: {} {}", cur_angle_raw_q16, dist_q2, angle, previous);
+                compared += 1;
+            }
+        }
+
+        assert!(compared > 1_000_000);
+    }
+}
@@ -83,6 +83,16 @@ where
         self.protocol.reset_decoder();
     }
 
+    /// the protocol of the channel
+    pub fn protocol(&self) -> &P {
+        &self.protocol
+    }
+
+    /// the protocol of the channel, e.g. to change decoder limits
+    pub fn protocol_mut(&mut self) -> &mut P {
+        &mut self.protocol
+    }
+
     /// Read message from channel
     /// 
     /// # Example
@@ -148,7 +158,167 @@ where
     /// ```
     pub fn invoke(&mut self, request:&Message, timeout: Duration) -> Result<Option<Message>> {
         self.write(request)?;
-        return self.read_until(timeout);
+        let resp = self.read_until(timeout);
+
+        // a damaged answer header may announce a long answer, don't let it swallow the next ones
+        if resp.is_err() {
+            self.protocol.reset_decoder();
+        }
+
+        return resp;
     }
     
 }
+
+impl<P, T: ?Sized> Channel<P, T>
+where
+    P: BufferedProtocolDecoder + ProtocolEncoder,
+    T: io::Read + io::Write
+{
+    /// Read bytes until a message is completed or no more bytes are available
+    fn read_buffered(&mut self) -> Result<bool> {
+        loop {
+            self.read_buffer.read_from(&mut self.stream)?;
+
+            let (decoded, completed) = self
+                .protocol
+                .decode_buffered(self.read_buffer.current_read_slice())?;
+            self.read_buffer.skip_bytes(decoded);
+
+            if completed {
+                return Ok(true);
+            }
+
+            if decoded == 0 {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Read message from channel without copying it out of the protocol decoder
+    ///
+    /// # Example
+    /// ```ignore
+    /// if let Some(msg) = channel.read_ref().unwrap() {
+    ///     println!("{:?}", msg.data);
+    /// }
+    /// ```
+    pub fn read_ref(&mut self) -> Result<Option<MessageRef<'_>>> {
+        if self.read_buffered()? {
+            return Ok(self.protocol.message());
+        }
+
+        Ok(None)
+    }
+
+    /// Read message until timeout, without copying it out of the protocol decoder
+    ///
+    /// # Example
+    /// ```ignore
+    /// let msg = channel.read_ref_until(Duration::from_secs(1))?;
+    /// ```
+    pub fn read_ref_until(&mut self, timeout: Duration) -> Result<Option<MessageRef<'_>>> {
+        let start = Instant::now();
+
+        while Instant::now() - start < timeout {
+            if self.read_buffered()? {
+                return Ok(self.protocol.message());
+            }
+        }
+
+        Err(RposError::OperationTimeout.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// messages of `[data length, cmd, data...]`
+    #[derive(Default)]
+    struct LengthPrefixed {
+        received: Vec<u8>,
+    }
+
+    impl ProtocolDecoder for LengthPrefixed {
+        fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
+            for (i, byte) in buf.iter().enumerate() {
+                self.received.push(*byte);
+                if self.received.len() == self.received[0] as usize + 2 {
+                    let msg = Message::with_data(self.received[1], &self.received[2..]);
+                    self.received.clear();
+                    return Ok((i + 1, Some(msg)));
+                }
+            }
+            Ok((buf.len(), None))
+        }
+
+        fn reset_decoder(&mut self) {
+            self.received.clear();
+        }
+    }
+
+    impl ProtocolEncoder for LengthPrefixed {
+        fn encode(&mut self, msg: &Message, bytes: &mut [u8]) -> Result<usize> {
+            bytes[0] = msg.data.len() as u8;
+            bytes[1] = msg.cmd;
+            bytes[2..msg.data.len() + 2].copy_from_slice(&msg.data);
+            Ok(msg.data.len() + 2)
+        }
+
+        fn estimate_encoded_size(&mut self, msg: &Message) -> Result<usize> {
+            Ok(msg.data.len() + 2)
+        }
+
+        fn write_to(&mut self, msg: &Message, dest: &mut impl io::Write) -> Result<usize> {
+            let mut bytes = vec![0u8; self.estimate_encoded_size(msg)?];
+            let len = self.encode(msg, &mut bytes)?;
+            dest.write_all(&bytes[..len])?;
+            Ok(len)
+        }
+
+        fn reset_encoder(&mut self) {}
+    }
+
+    /// answers each request with the next response, times out in between
+    struct Responder {
+        responses: VecDeque<Vec<u8>>,
+        pending: Vec<u8>,
+    }
+
+    impl io::Read for Responder {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pending.is_empty() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            let len = buf.len().min(self.pending.len());
+            buf[..len].copy_from_slice(&self.pending[..len]);
+            self.pending.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl io::Write for Responder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend(self.responses.pop_front().unwrap_or_default());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn invoke_resets_decoder_on_timeout() {
+        // a damaged length announces 9 bytes, the next answer must not complete it
+        let responses = vec![vec![9, 1, 0], vec![1, 2, 42]].into_iter().collect();
+        let stream = Responder { responses, pending: Vec::new() };
+        let mut channel = Channel::new(LengthPrefixed::default(), Box::new(stream));
+
+        assert!(channel.invoke(&Message::new(1), Duration::from_millis(10)).is_err());
+        let resp = channel.invoke(&Message::new(2), Duration::from_millis(10)).unwrap();
+        assert_eq!(resp, Some(Message::with_data(2, &[42])));
+    }
+}
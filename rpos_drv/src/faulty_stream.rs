@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Probabilities of the faults injected by `FaultyStream`
+///
+/// Byte faults (drop, duplicate, corrupt) apply to each received byte, the others to each
+/// `read` or `write` call. All faults are disabled by default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// received byte is lost
+    pub drop_rate: f64,
+
+    /// received byte arrives twice
+    pub duplicate_rate: f64,
+
+    /// received byte has flipped bits
+    pub corrupt_rate: f64,
+
+    /// received bytes are held back for up to `max_delay`
+    pub delay_rate: f64,
+
+    /// longest delay
+    pub max_delay: Duration,
+
+    /// read returns fewer bytes than available
+    pub short_read_rate: f64,
+
+    /// read fails with `TimedOut`
+    pub timeout_rate: f64,
+
+    /// read or write fails with `Interrupted`
+    pub interrupt_rate: f64,
+
+    /// write accepts only part of the bytes
+    pub split_write_rate: f64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> FaultConfig {
+        FaultConfig {
+            drop_rate: 0f64,
+            duplicate_rate: 0f64,
+            corrupt_rate: 0f64,
+            delay_rate: 0f64,
+            max_delay: Duration::from_millis(10),
+            short_read_rate: 0f64,
+            timeout_rate: 0f64,
+            interrupt_rate: 0f64,
+            split_write_rate: 0f64,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// every fault at `rate`, e.g. 0.01 for a noisy line
+    pub fn uniform(rate: f64) -> FaultConfig {
+        FaultConfig {
+            drop_rate: rate,
+            duplicate_rate: rate,
+            corrupt_rate: rate,
+            delay_rate: rate,
+            short_read_rate: rate,
+            timeout_rate: rate,
+            interrupt_rate: rate,
+            split_write_rate: rate,
+            ..FaultConfig::default()
+        }
+    }
+}
+
+/// Number of injected faults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultStats {
+    pub dropped: usize,
+    pub duplicated: usize,
+    pub corrupted: usize,
+    pub delays: usize,
+    pub short_reads: usize,
+    pub timeouts: usize,
+    pub interrupts: usize,
+    pub split_writes: usize,
+}
+
+/// Turns the faults of a `FaultyStream` on and off, while the stream is owned by a driver
+#[derive(Debug, Clone)]
+pub struct FaultSwitch(Arc<AtomicBool>);
+
+impl FaultSwitch {
+    pub fn enable(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn disable(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Stream wrapper injecting faults into the bytes received from and sent to `T`
+///
+/// Faults are chosen by a pseudo random generator, so a seed always reproduces the same
+/// faults for the same received bytes.
+///
+/// # Example
+/// ```ignore
+/// let stream = FaultyStream::new(device, 42, FaultConfig::uniform(0.01));
+/// let mut channel = Channel::new(RplidarHostProtocol::new(), Box::new(stream));
+/// ```
+#[derive(Debug)]
+pub struct FaultyStream<T> {
+    inner: T,
+    config: FaultConfig,
+    stats: FaultStats,
+    rng: u64,
+    switch: FaultSwitch,
+    received: VecDeque<u8>,
+    delayed_until: Option<Instant>,
+}
+
+impl<T: Read + Write> FaultyStream<T> {
+    pub fn new(inner: T, seed: u64, config: FaultConfig) -> FaultyStream<T> {
+        FaultyStream {
+            inner,
+            config,
+            stats: FaultStats::default(),
+            // xorshift must not start from zero
+            rng: seed ^ 0x9E37_79B9_7F4A_7C15,
+            switch: FaultSwitch(Arc::new(AtomicBool::new(true))),
+            received: VecDeque::new(),
+            delayed_until: None,
+        }
+    }
+
+    /// handle to turn the faults on and off, enabled initially
+    pub fn switch(&self) -> FaultSwitch {
+        self.switch.clone()
+    }
+
+    /// the injected faults so far
+    pub fn stats(&self) -> &FaultStats {
+        &self.stats
+    }
+
+    /// change the fault probabilities, e.g. to stop injecting faults
+    pub fn set_config(&mut self, config: FaultConfig) {
+        self.config = config;
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// true with probability `rate`
+    fn chance(&mut self, rate: f64) -> bool {
+        rate > 0f64 && self.switch.is_enabled() && ((self.next_random() >> 11) as f64 / (1u64 << 53) as f64) < rate
+    }
+
+    /// random number in `1..=max`
+    fn up_to(&mut self, max: usize) -> usize {
+        1 + (self.next_random() % max as u64) as usize
+    }
+
+    /// receive bytes from the inner stream, with byte faults applied
+    fn receive(&mut self, len: usize) -> io::Result<usize> {
+        let mut buf = vec![0u8; len];
+        let read = self.inner.read(&mut buf)?;
+
+        for &byte in buf[..read].iter() {
+            if self.chance(self.config.drop_rate) {
+                self.stats.dropped += 1;
+                continue;
+            }
+
+            let byte = if self.chance(self.config.corrupt_rate) {
+                self.stats.corrupted += 1;
+                byte ^ (self.up_to(255) as u8)
+            } else {
+                byte
+            };
+
+            self.received.push_back(byte);
+
+            if self.chance(self.config.duplicate_rate) {
+                self.stats.duplicated += 1;
+                self.received.push_back(byte);
+            }
+        }
+
+        Ok(read)
+    }
+}
+
+fn error(kind: io::ErrorKind) -> io::Error {
+    io::Error::new(kind, "injected fault")
+}
+
+impl<T: Read + Write> Read for FaultyStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // checked before any fault is drawn, so the schedule doesn't depend on timing
+        match self.delayed_until {
+            Some(until) if Instant::now() < until => return Err(error(io::ErrorKind::TimedOut)),
+            _ => self.delayed_until = None,
+        }
+
+        if self.chance(self.config.interrupt_rate) {
+            self.stats.interrupts += 1;
+            return Err(error(io::ErrorKind::Interrupted));
+        }
+
+        if self.chance(self.config.timeout_rate) {
+            self.stats.timeouts += 1;
+            return Err(error(io::ErrorKind::TimedOut));
+        }
+
+        if self.chance(self.config.delay_rate) {
+            self.stats.delays += 1;
+            let delay_us = self.up_to(self.config.max_delay.as_micros().max(1) as usize);
+            self.delayed_until = Some(Instant::now() + Duration::from_micros(delay_us as u64));
+            return Err(error(io::ErrorKind::TimedOut));
+        }
+
+        if self.received.is_empty() && self.receive(buf.len())? > 0 && self.received.is_empty() {
+            // every byte was dropped, like a serial port receiving nothing in time
+            return Err(error(io::ErrorKind::TimedOut));
+        }
+
+        let mut len = buf.len().min(self.received.len());
+        if len > 1 && self.chance(self.config.short_read_rate) {
+            self.stats.short_reads += 1;
+            len = self.up_to(len - 1);
+        }
+
+        for (dest, byte) in buf.iter_mut().zip(self.received.drain(..len)) {
+            *dest = byte;
+        }
+        Ok(len)
+    }
+}
+
+impl<T: Read + Write> Write for FaultyStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.chance(self.config.interrupt_rate) {
+            self.stats.interrupts += 1;
+            return Err(error(io::ErrorKind::Interrupted));
+        }
+
+        if buf.len() > 1 && self.chance(self.config.split_write_rate) {
+            self.stats.split_writes += 1;
+            let len = self.up_to(buf.len() - 1);
+            return self.inner.write(&buf[..len]);
+        }
+
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// reads everything, retrying injected errors
+    fn read_all<T: Read + Write>(stream: &mut FaultyStream<T>) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 16];
+
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return received,
+                Ok(len) => received.extend_from_slice(&buf[..len]),
+                Err(_) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_schedule() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        let config = FaultConfig { max_delay: Duration::from_micros(50), ..FaultConfig::uniform(0.05) };
+
+        let mut first = FaultyStream::new(Cursor::new(data.clone()), 7, config.clone());
+        let mut second = FaultyStream::new(Cursor::new(data.clone()), 7, config.clone());
+        let mut other = FaultyStream::new(Cursor::new(data.clone()), 8, config);
+
+        let received = read_all(&mut first);
+        assert_eq!(read_all(&mut second), received);
+        assert_ne!(read_all(&mut other), received);
+        assert_ne!(received, data);
+
+        let stats = first.stats();
+        assert_eq!(received.len(), data.len() - stats.dropped + stats.duplicated);
+        assert!(stats.corrupted > 0 && stats.short_reads > 0 && stats.timeouts > 0 && stats.interrupts > 0);
+
+        let mut stream = FaultyStream::new(Cursor::new(data.clone()), 7, FaultConfig::uniform(0.5));
+        stream.switch().disable();
+        assert_eq!(read_all(&mut stream), data);
+    }
+
+    #[test]
+    fn no_faults_by_default() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let mut stream = FaultyStream::new(Cursor::new(data.clone()), 1, FaultConfig::default());
+        assert_eq!(read_all(&mut stream), data);
+        assert_eq!(*stream.stats(), FaultStats::default());
+
+        let mut stream = FaultyStream::new(Cursor::new(Vec::new()), 1, FaultConfig { split_write_rate: 1f64, ..FaultConfig::default() });
+        stream.write_all(&data).unwrap();
+        assert_eq!(stream.get_ref().get_ref(), &data);
+        assert!(stream.stats().split_writes > 0);
+    }
+}
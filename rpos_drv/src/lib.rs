@@ -5,12 +5,22 @@
 //! `rpos_drv` is a collection of structs and traits to build drivers for RPOS.
 
 extern crate failure;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 mod channel;
 mod prelude;
 mod ring_byte_buffer;
 mod errors;
+#[cfg(feature = "testing")]
+mod faulty_stream;
+#[cfg(feature = "testing")]
+mod mock_stream;
 
 pub use self::prelude::*;
 pub use self::channel::*;
 pub use self::ring_byte_buffer::RingByteBuffer;
+#[cfg(feature = "testing")]
+pub use self::faulty_stream::{FaultConfig, FaultStats, FaultSwitch, FaultyStream};
+#[cfg(feature = "testing")]
+pub use self::mock_stream::MockStream;
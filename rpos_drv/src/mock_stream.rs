@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Read, Write};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq)]
+enum MockStep {
+    /// bytes the driver must write
+    Expect(Vec<u8>),
+
+    /// bytes returned by one read, once the expectations before are met
+    Respond(Vec<u8>),
+}
+
+/// Scripted stream for testing drivers without a device
+///
+/// Expectations are checked in order: writing other bytes than expected panics with a
+/// hex diff, and the responses after an expectation become readable once it is met.
+/// Reads without readable bytes fail with `TimedOut`, like serial ports. Dropping the
+/// stream with unmet expectations panics.
+///
+/// # Example
+/// ```ignore
+/// let stream = MockStream::new()
+///     .expect(&[0xA5, 0x50])
+///     .respond_in_fragments(&device_info_answer, 4);
+/// let mut rplidar = RplidarDevice::with_stream(Box::new(stream));
+/// let info = rplidar.get_device_info()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct MockStream {
+    steps: VecDeque<MockStep>,
+    written: Vec<u8>,
+    readable: VecDeque<Vec<u8>>,
+}
+
+impl MockStream {
+    pub fn new() -> MockStream {
+        MockStream::default()
+    }
+
+    /// the driver must write `bytes` next
+    pub fn expect(mut self, bytes: &[u8]) -> MockStream {
+        self.steps.push_back(MockStep::Expect(bytes.to_vec()));
+        self
+    }
+
+    /// answer with `bytes`, in one read
+    pub fn respond(mut self, bytes: &[u8]) -> MockStream {
+        self.steps.push_back(MockStep::Respond(bytes.to_vec()));
+        self
+    }
+
+    /// answer with `bytes`, in reads of at most `fragment_size` bytes
+    pub fn respond_in_fragments(mut self, bytes: &[u8], fragment_size: usize) -> MockStream {
+        for fragment in bytes.chunks(fragment_size.max(1)) {
+            self.steps.push_back(MockStep::Respond(fragment.to_vec()));
+        }
+        self
+    }
+
+    /// check if all expectations are met
+    pub fn is_done(&self) -> bool {
+        !self.steps.iter().any(|step| matches!(step, MockStep::Expect(_)))
+    }
+
+    /// make the responses before the next expectation readable
+    fn release_responses(&mut self) {
+        while let Some(MockStep::Respond(_)) = self.steps.front() {
+            if let Some(MockStep::Respond(bytes)) = self.steps.pop_front() {
+                self.readable.push_back(bytes);
+            }
+        }
+    }
+
+    /// match the written bytes against the expectations
+    fn check_written(&mut self) {
+        loop {
+            self.release_responses();
+
+            let expected = match self.steps.front() {
+                Some(MockStep::Expect(expected)) => expected,
+                _ if self.written.is_empty() => return,
+                _ => panic!("MockStream: unexpected bytes written: {}", hex(&self.written)),
+            };
+
+            let len = expected.len().min(self.written.len());
+            if expected[..len] != self.written[..len] {
+                panic!("MockStream: unexpected bytes written\n{}", diff(expected, &self.written));
+            }
+
+            if len < expected.len() {
+                return;
+            }
+
+            self.written.drain(..len);
+            self.steps.pop_front();
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 3);
+    for (i, byte) in bytes.iter().enumerate() {
+        let sep = if i == 0 { "" } else { " " };
+        let _ = write!(out, "{}{:02X}", sep, byte);
+    }
+    out
+}
+
+/// expected and actual bytes, with a marker under the first difference
+fn diff(expected: &[u8], actual: &[u8]) -> String {
+    let first = expected.iter().zip(actual.iter()).take_while(|(e, a)| e == a).count();
+    format!(
+        "expected: {}\n  actual: {}\n          {}^^ first difference at byte {}",
+        hex(expected),
+        hex(actual),
+        "   ".repeat(first),
+        first
+    )
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.release_responses();
+
+        let fragment = match self.readable.front_mut() {
+            Some(fragment) => fragment,
+            None => return Err(io::ErrorKind::TimedOut.into()),
+        };
+
+        let len = buf.len().min(fragment.len());
+        buf[..len].copy_from_slice(&fragment[..len]);
+        fragment.drain(..len);
+
+        if fragment.is_empty() {
+            self.readable.pop_front();
+        }
+        Ok(len)
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        self.check_written();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for MockStream {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+
+        for step in self.steps.iter() {
+            if let MockStep::Expect(expected) = step {
+                panic!("MockStream: expected bytes never written: {}", hex(expected));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Channel, Message, ProtocolDecoder, ProtocolEncoder, Result, RposError};
+    use std::time::Duration;
+
+    /// `cmd, len, data...` messages
+    struct TestProtocol;
+
+    impl ProtocolDecoder for TestProtocol {
+        fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
+            if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
+                return Ok((0, None));
+            }
+
+            let len = 2 + buf[1] as usize;
+            Ok((len, Some(Message::with_data(buf[0], &buf[2..len]))))
+        }
+
+        fn reset_decoder(&mut self) {}
+    }
+
+    impl ProtocolEncoder for TestProtocol {
+        fn encode(&mut self, msg: &Message, bytes: &mut [u8]) -> Result<usize> {
+            bytes[0] = msg.cmd;
+            bytes[1] = msg.data.len() as u8;
+            bytes[2..2 + msg.data.len()].copy_from_slice(&msg.data);
+            Ok(2 + msg.data.len())
+        }
+
+        fn estimate_encoded_size(&mut self, msg: &Message) -> Result<usize> {
+            Ok(2 + msg.data.len())
+        }
+
+        fn write_to(&mut self, msg: &Message, dest: &mut impl Write) -> Result<usize> {
+            let mut bytes = vec![0u8; self.estimate_encoded_size(msg)?];
+            let len = self.encode(msg, &mut bytes)?;
+            dest.write_all(&bytes[..len])?;
+            Ok(len)
+        }
+
+        fn reset_encoder(&mut self) {}
+    }
+
+    #[test]
+    fn invoke() {
+        let stream = MockStream::new()
+            .expect(&[1, 2, 0xAA, 0xBB])
+            .respond_in_fragments(&[0x81, 3, 7, 8, 9], 2)
+            .expect(&[2, 0]);
+        let mut channel = Channel::new(TestProtocol, Box::new(stream));
+
+        let resp = channel.invoke(&Message::with_data(1, &[0xAA, 0xBB]), Duration::from_secs(1)).unwrap();
+        assert_eq!(resp, Some(Message::with_data(0x81, &[7, 8, 9])));
+
+        // no response scripted
+        let err = channel.invoke(&Message::new(2), Duration::from_millis(10)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationTimeout)));
+    }
+
+    #[test]
+    fn mismatch_diff() {
+        let err = std::panic::catch_unwind(|| {
+            let mut stream = MockStream::new().expect(&[0xA5, 0x50]);
+            let _ = stream.write_all(&[0xA5, 0x52]);
+        })
+        .unwrap_err();
+
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.ends_with("expected: A5 50\n  actual: A5 52\n             ^^ first difference at byte 1"), "{}", message);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected bytes never written: A5 25")]
+    fn unmet_expectation() {
+        let _stream = MockStream::new().expect(&[0xA5, 0x25]);
+    }
+}
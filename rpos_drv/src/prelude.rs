@@ -1,9 +1,12 @@
 use std::io;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 pub use super::errors::*;
 
 /// A message send via channels
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Message {
     /// The command
     pub cmd: u8,
@@ -29,6 +32,23 @@ impl Message {
     }
 }
 
+/// A message borrowed from the buffer of a protocol decoder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageRef<'a> {
+    /// The command
+    pub cmd: u8,
+
+    /// Payload data
+    pub data: &'a [u8]
+}
+
+impl<'a> MessageRef<'a> {
+    /// copy the message out of the decoder buffer
+    pub fn to_message(&self) -> Message {
+        Message::with_data(self.cmd, self.data)
+    }
+}
+
 /// Protocol decoder
 pub trait ProtocolDecoder {
     /// Decode bytes and return consumed bytes and message
@@ -38,6 +58,15 @@ pub trait ProtocolDecoder {
     fn reset_decoder(&mut self);
 }
 
+/// Protocol decoder keeping the decoded message in its own buffer, so it can be read without allocations
+pub trait BufferedProtocolDecoder: ProtocolDecoder {
+    /// Decode bytes and return consumed bytes and whether a message is completed
+    fn decode_buffered(&mut self, buf: &[u8]) -> Result<(usize, bool)>;
+
+    /// The completed message, valid until the next decode
+    fn message(&self) -> Option<MessageRef<'_>>;
+}
+
 /// Protocol encoder
 pub trait ProtocolEncoder {
     /// Encode message into byte array
@@ -52,3 +81,14 @@ pub trait ProtocolEncoder {
     /// Reset encoder
     fn reset_encoder(&mut self);
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_message() {
+        let msg = Message::with_data(0x82, &[1, 2, 3]);
+        assert_eq!(bincode::deserialize::<Message>(&bincode::serialize(&msg).unwrap()).unwrap(), msg);
+    }
+}
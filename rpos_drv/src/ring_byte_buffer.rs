@@ -96,7 +96,8 @@ impl RingByteBuffer {
                 Ok(read)
             },
             Err(err) => {
-                if err.kind() == std::io::ErrorKind::TimedOut {
+                // nothing received in time, or interrupted by a signal: try again later
+                if err.kind() == std::io::ErrorKind::TimedOut || err.kind() == std::io::ErrorKind::Interrupted {
                     Ok(0)
                 } else {
                     Err(err)
@@ -216,4 +217,34 @@ mod tests {
         assert_eq!(ring_buf.len(), 0);
         assert_eq!(ring_buf.free_space(), 6);
     }
+
+    /// fails with `kind` once, then reads `data`
+    struct FailOnce {
+        kind: Option<std::io::ErrorKind>,
+        data: &'static [u8],
+    }
+
+    impl Read for FailOnce {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.kind.take() {
+                Some(kind) => Err(kind.into()),
+                None => self.data.read(buf),
+            }
+        }
+    }
+
+    #[test]
+    fn read_from_retryable_errors() {
+        let mut ring_buf = super::RingByteBuffer::with_capacity(6);
+
+        // timeouts and signals are no data yet, reading goes on
+        for kind in [std::io::ErrorKind::TimedOut, std::io::ErrorKind::Interrupted].iter() {
+            let mut upstream = FailOnce { kind: Some(*kind), data: &[1, 2] };
+            assert_eq!(ring_buf.read_from(&mut upstream).unwrap(), 2);
+        }
+        assert_eq!(ring_buf.current_read_slice(), [1, 2, 1, 2]);
+
+        let mut upstream = FailOnce { kind: Some(std::io::ErrorKind::BrokenPipe), data: &[] };
+        assert!(ring_buf.read_from(&mut upstream).is_err());
+    }
 }
@@ -0,0 +1,323 @@
+//! Motion compensation (deskew) of scans
+//!
+//! A scan takes 100ms to 200ms, so scans taken while the robot moves are warped.
+//! Given the measurement time of each point (see `RplidarDevice::grab_timed_scan`)
+//! and a source of robot poses over time, every point is re-projected into the
+//! robot frame at a single reference time.
+//!
+//! # Example
+//! ```ignore
+//! let mut odometry = PoseBuffer::with_capacity(200);
+//!
+//! // from the odometry thread
+//! odometry.push(Instant::now(), Pose2D::new(x, y, theta));
+//!
+//! // from the LIDAR thread
+//! let scan = rplidar.grab_timed_scan()?;
+//! let reference = scan.end_time().unwrap();
+//! let points = deskew_points(&scan, &mounting, &odometry, reference)?;
+//! ```
+
+use super::errors::*;
+use super::prelude::{ScanPoint, TimedScan};
+use super::transform::{Point2D, SensorMounting};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::time::Instant;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Pose of the robot in a fixed (odometry or world) frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pose2D {
+    /// x position (meters)
+    pub x: f64,
+
+    /// y position (meters)
+    pub y: f64,
+
+    /// heading (radians, counterclockwise)
+    pub theta: f64,
+}
+
+impl Pose2D {
+    /// create a pose
+    pub fn new(x: f64, y: f64, theta: f64) -> Pose2D {
+        Pose2D { x, y, theta }
+    }
+
+    /// the identity pose
+    pub fn identity() -> Pose2D {
+        Pose2D::new(0f64, 0f64, 0f64)
+    }
+
+    /// transform a point from the frame of this pose into the parent frame
+    pub fn transform(&self, x: f64, y: f64) -> (f64, f64) {
+        let (sin, cos) = self.theta.sin_cos();
+        (self.x + cos * x - sin * y, self.y + sin * x + cos * y)
+    }
+
+    /// the inverse transform
+    pub fn inverse(&self) -> Pose2D {
+        let (sin, cos) = self.theta.sin_cos();
+        Pose2D::new(
+            -cos * self.x - sin * self.y,
+            sin * self.x - cos * self.y,
+            -self.theta,
+        )
+    }
+
+    /// `self * other`, i.e. `other` expressed in the parent frame of `self`
+    pub fn compose(&self, other: &Pose2D) -> Pose2D {
+        let (x, y) = self.transform(other.x, other.y);
+        Pose2D::new(x, y, normalize_angle(self.theta + other.theta))
+    }
+
+    /// linear interpolation between two poses, taking the shortest way for the heading
+    pub fn interpolate(&self, other: &Pose2D, ratio: f64) -> Pose2D {
+        let dtheta = normalize_angle(other.theta - self.theta);
+        Pose2D::new(
+            self.x + (other.x - self.x) * ratio,
+            self.y + (other.y - self.y) * ratio,
+            normalize_angle(self.theta + dtheta * ratio),
+        )
+    }
+}
+
+/// normalize an angle into `(-π, π]`
+fn normalize_angle(angle: f64) -> f64 {
+    let mut angle = angle % (2f64 * PI);
+    if angle > PI {
+        angle -= 2f64 * PI;
+    } else if angle <= -PI {
+        angle += 2f64 * PI;
+    }
+    angle
+}
+
+/// Source of robot poses over time
+pub trait PoseProvider {
+    /// the pose of the robot at `time`, or `None` if it is unknown
+    fn pose_at(&self, time: Instant) -> Option<Pose2D>;
+}
+
+/// Keeps recent poses (e.g. from odometry or IMU) and interpolates between them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoseBuffer {
+    poses: VecDeque<(Instant, Pose2D)>,
+    capacity: usize,
+}
+
+impl PoseBuffer {
+    /// keep at most `capacity` poses
+    pub fn with_capacity(capacity: usize) -> PoseBuffer {
+        let capacity = std::cmp::max(capacity, 2);
+        PoseBuffer {
+            poses: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// add a pose, poses must be pushed in time order
+    pub fn push(&mut self, time: Instant, pose: Pose2D) {
+        if let Some(&(last, _)) = self.poses.back() {
+            if time < last {
+                return;
+            }
+        }
+
+        if self.poses.len() == self.capacity {
+            self.poses.pop_front();
+        }
+        self.poses.push_back((time, pose));
+    }
+
+    /// number of poses kept
+    pub fn len(&self) -> usize {
+        self.poses.len()
+    }
+
+    /// true if there is no pose
+    pub fn is_empty(&self) -> bool {
+        self.poses.is_empty()
+    }
+
+    /// remove all poses
+    pub fn clear(&mut self) {
+        self.poses.clear();
+    }
+}
+
+impl PoseProvider for PoseBuffer {
+    /// interpolate between the poses around `time`, no extrapolation is done
+    fn pose_at(&self, time: Instant) -> Option<Pose2D> {
+        let after = self.poses.iter().position(|&(t, _)| t >= time)?;
+        let (t1, p1) = self.poses[after];
+
+        if t1 == time {
+            return Some(p1);
+        }
+
+        if after == 0 {
+            return None;
+        }
+
+        let (t0, p0) = self.poses[after - 1];
+        let ratio = (time - t0).as_secs_f64() / (t1 - t0).as_secs_f64();
+
+        Some(p0.interpolate(&p1, ratio))
+    }
+}
+
+impl<F: Fn(Instant) -> Option<Pose2D>> PoseProvider for F {
+    fn pose_at(&self, time: Instant) -> Option<Pose2D> {
+        self(time)
+    }
+}
+
+/// motion of the robot from `reference` to `time`, expressed in the robot frame at `reference`
+fn relative_motion<P: PoseProvider + ?Sized>(
+    provider: &P,
+    reference_inverse: &Pose2D,
+    time: Instant,
+) -> Result<Pose2D> {
+    match provider.pose_at(time) {
+        Some(pose) => Ok(reference_inverse.compose(&pose)),
+        None => Err(RposError::OperationFail {
+            description: "no pose available for scan point".to_owned(),
+        }
+        .into()),
+    }
+}
+
+fn reference_inverse<P: PoseProvider + ?Sized>(provider: &P, reference: Instant) -> Result<Pose2D> {
+    match provider.pose_at(reference) {
+        Some(pose) => Ok(pose.inverse()),
+        None => Err(RposError::OperationFail {
+            description: "no pose available for reference time".to_owned(),
+        }
+        .into()),
+    }
+}
+
+/// re-project valid points of the scan into the robot frame at `reference`
+pub fn deskew_points<P: PoseProvider + ?Sized>(
+    scan: &TimedScan,
+    mounting: &SensorMounting,
+    provider: &P,
+    reference: Instant,
+) -> Result<Vec<Point2D<f64>>> {
+    let reference_inverse = reference_inverse(provider, reference)?;
+    let mut out = Vec::with_capacity(scan.points.len());
+
+    for (point, &time) in scan.points.iter().zip(scan.timestamps.iter()) {
+        if !point.is_valid() {
+            continue;
+        }
+
+        let motion = relative_motion(provider, &reference_inverse, time)?;
+        let (x, y) = mounting.transform_polar(point.angle() as f64, point.distance() as f64);
+        let (x, y) = motion.transform(x, y);
+
+        out.push(Point2D { x, y });
+    }
+
+    Ok(out)
+}
+
+/// re-project valid points of the scan in place, as if the whole scan was measured at `reference`
+///
+/// Angles and distances of the points are updated, so the result can be used
+/// anywhere a plain scan is expected. Timestamps are set to `reference`.
+pub fn deskew_scan<P: PoseProvider + ?Sized>(
+    scan: &mut TimedScan,
+    mounting: &SensorMounting,
+    provider: &P,
+    reference: Instant,
+) -> Result<()> {
+    let reference_inverse = reference_inverse(provider, reference)?;
+
+    for (point, time) in scan.points.iter_mut().zip(scan.timestamps.iter_mut()) {
+        if point.is_valid() {
+            deskew_point(point, mounting, &relative_motion(provider, &reference_inverse, *time)?);
+        }
+        *time = reference;
+    }
+
+    Ok(())
+}
+
+fn deskew_point(point: &mut ScanPoint, mounting: &SensorMounting, motion: &Pose2D) {
+    let (x, y) = mounting.transform_polar(point.angle() as f64, point.distance() as f64);
+    let (x, y) = motion.transform(x, y);
+    let (angle, distance) = mounting.to_sensor_polar(x, y);
+
+    point.set_angle(angle as f32);
+    point.set_distance(distance as f32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn point(degree: f32, range: f32) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: 10,
+            flag: 0,
+        };
+        p.set_angle(degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    #[test]
+    fn pose_buffer_interpolation() {
+        let t0 = Instant::now();
+        let mut buffer = PoseBuffer::with_capacity(4);
+        buffer.push(t0, Pose2D::new(0f64, 0f64, 3f64));
+        buffer.push(t0 + Duration::from_secs(1), Pose2D::new(1f64, 2f64, -3f64));
+
+        let pose = buffer.pose_at(t0 + Duration::from_millis(500)).unwrap();
+        assert!((pose.x - 0.5f64).abs() < 1e-9);
+        assert!((pose.y - 1f64).abs() < 1e-9);
+        // interpolated across ±π
+        assert!((pose.theta.abs() - PI).abs() < 1e-9);
+
+        assert!(buffer.pose_at(t0 + Duration::from_secs(2)).is_none());
+    }
+
+    #[test]
+    fn pose_inverse() {
+        let pose = Pose2D::new(1f64, -2f64, 0.7f64);
+        let identity = pose.compose(&pose.inverse());
+
+        assert!(identity.x.abs() < 1e-9 && identity.y.abs() < 1e-9 && identity.theta.abs() < 1e-9);
+    }
+
+    #[test]
+    fn deskew_moving_forward() {
+        // robot drives forward at 1m/s toward a wall 2m ahead at reference time
+        let t0 = Instant::now();
+        let odometry = move |t: Instant| {
+            let dt = if t >= t0 { (t - t0).as_secs_f64() } else { -(t0 - t).as_secs_f64() };
+            Some(Pose2D::new(dt, 0f64, 0f64))
+        };
+
+        let mut scan = TimedScan {
+            points: vec![point(0f32, 2.1f32), point(0f32, 2f32)],
+            timestamps: vec![t0 - Duration::from_millis(100), t0],
+        };
+
+        let points = deskew_points(&scan, &SensorMounting::identity(), &odometry, t0).unwrap();
+        assert!((points[0].x - 2f64).abs() < 1e-3);
+        assert!((points[1].x - 2f64).abs() < 1e-3);
+
+        deskew_scan(&mut scan, &SensorMounting::identity(), &odometry, t0).unwrap();
+        assert!((scan.points[0].distance() - 2f32).abs() < 1e-3);
+        assert_eq!(scan.timestamps[0], t0);
+    }
+}
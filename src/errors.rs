@@ -1,4 +1,23 @@
 pub use rpos_drv::RposError;
 pub use rpos_drv::Error;
 
-pub type Result<T> = std::result::Result<T, Error>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// convert errors of `rplidar_core` into driver errors
+pub(crate) fn from_core_error(err: rplidar_core::Error) -> Error {
+    match err {
+        rplidar_core::Error::BufferTooSmall => RposError::BufferTooSmall.into(),
+        rplidar_core::Error::PayloadTooLarge => RposError::OperationFail { description: err.to_string() }.into(),
+        _ => RposError::ProtocolError { description: err.to_string() }.into(),
+    }
+}
+
+/// convert errors of the `rplidar_core` driver over `std::io` streams into driver errors
+pub(crate) fn from_driver_error(err: rplidar_core::DriverError<std::io::Error>) -> Error {
+    match err {
+        rplidar_core::DriverError::Io(err) => err.into(),
+        rplidar_core::DriverError::Timeout => RposError::OperationTimeout.into(),
+        rplidar_core::DriverError::Protocol(err) => from_core_error(err),
+        rplidar_core::DriverError::AnswerMismatch => RposError::OperationFail { description: "answer type mismatch".to_owned() }.into(),
+    }
+}
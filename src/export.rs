@@ -0,0 +1,309 @@
+//! Export scans to point cloud files
+//!
+//! Supported formats are PCD (ascii and binary, as used by PCL), PLY (ascii and
+//! binary) and CSV. Every point is written with the following fields:
+//!
+//! | field     | description                                        |
+//! | --------- | -------------------------------------------------- |
+//! | x, y, z   | position in robot frame (meters)                   |
+//! | range     | measured distance (meters)                         |
+//! | bearing   | measured angle in sensor frame (radians, clockwise)|
+//! | quality   | quality of the measurement                         |
+//! | timestamp | timestamp of the scan (seconds)                    |
+//!
+//! # Example
+//! ```ignore
+//! let file = std::fs::File::create("scan.pcd")?;
+//! let mut writer = PointCloudWriter::with_mounting(file, PointCloudFormat::PcdBinary, mounting);
+//!
+//! writer.write_scan(&rplidar.grab_scan()?, 0f64)?;
+//! writer.finish()?;
+//! ```
+
+use super::errors::*;
+use super::prelude::ScanPoint;
+use super::transform::SensorMounting;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::Write;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Point cloud file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PointCloudFormat {
+    /// Comma separated values with a header line
+    Csv,
+
+    /// PLY with ascii body
+    PlyAscii,
+
+    /// PLY with little endian binary body
+    PlyBinary,
+
+    /// PCD v0.7 with ascii body
+    PcdAscii,
+
+    /// PCD v0.7 with binary body
+    PcdBinary,
+}
+
+/// A point to be exported
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExportPoint {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub range: f32,
+    pub bearing: f32,
+    pub quality: u8,
+    pub timestamp: f64,
+}
+
+impl ExportPoint {
+    /// create export point from scan point with mounting transform applied
+    pub fn from_scan_point(point: &ScanPoint, mounting: &SensorMounting, timestamp: f64) -> ExportPoint {
+        let [x, y, z] = mounting.transform(point);
+
+        ExportPoint {
+            x: x as f32,
+            y: y as f32,
+            z: z as f32,
+            range: point.distance(),
+            bearing: point.angle(),
+            quality: point.quality,
+            timestamp,
+        }
+    }
+}
+
+/// Writes scans into point cloud files
+///
+/// CSV output is streamed as scans are written. PCD and PLY store the number of
+/// points in the file header, so points are kept in memory until `finish` is called.
+#[derive(Debug)]
+pub struct PointCloudWriter<W: Write> {
+    dest: W,
+    format: PointCloudFormat,
+    mounting: SensorMounting,
+    points: Vec<ExportPoint>,
+    header_written: bool,
+}
+
+impl<W: Write> PointCloudWriter<W> {
+    /// create a writer with sensor placed at the origin
+    pub fn new(dest: W, format: PointCloudFormat) -> PointCloudWriter<W> {
+        PointCloudWriter::with_mounting(dest, format, SensorMounting::identity())
+    }
+
+    /// create a writer with sensor mounting transform
+    pub fn with_mounting(dest: W, format: PointCloudFormat, mounting: SensorMounting) -> PointCloudWriter<W> {
+        PointCloudWriter {
+            dest,
+            format,
+            mounting,
+            points: Vec::new(),
+            header_written: false,
+        }
+    }
+
+    /// the format of the output
+    pub fn format(&self) -> PointCloudFormat {
+        self.format
+    }
+
+    /// append valid points of a scan taken at `timestamp` (seconds)
+    pub fn write_scan(&mut self, scan: &[ScanPoint], timestamp: f64) -> Result<()> {
+        let mounting = self.mounting;
+        self.write_points(
+            scan.iter()
+                .filter(|p| p.is_valid())
+                .map(|p| ExportPoint::from_scan_point(p, &mounting, timestamp)),
+        )
+    }
+
+    /// append already transformed points
+    pub fn write_points<I: IntoIterator<Item = ExportPoint>>(&mut self, points: I) -> Result<()> {
+        match self.format {
+            PointCloudFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.dest, "x,y,z,range,bearing,quality,timestamp")?;
+                    self.header_written = true;
+                }
+
+                for p in points {
+                    writeln!(
+                        self.dest,
+                        "{},{},{},{},{},{},{}",
+                        p.x, p.y, p.z, p.range, p.bearing, p.quality, p.timestamp
+                    )?;
+                }
+            }
+            _ => self.points.extend(points),
+        }
+
+        Ok(())
+    }
+
+    /// write out buffered points and return the destination
+    pub fn finish(mut self) -> Result<W> {
+        match self.format {
+            PointCloudFormat::Csv => {
+                if !self.header_written {
+                    self.write_points(std::iter::empty())?;
+                }
+            }
+            PointCloudFormat::PlyAscii | PointCloudFormat::PlyBinary => self.write_ply()?,
+            PointCloudFormat::PcdAscii | PointCloudFormat::PcdBinary => self.write_pcd()?,
+        }
+
+        self.dest.flush()?;
+        Ok(self.dest)
+    }
+
+    fn write_ply(&mut self) -> Result<()> {
+        let binary = self.format == PointCloudFormat::PlyBinary;
+
+        writeln!(self.dest, "ply")?;
+        writeln!(
+            self.dest,
+            "format {} 1.0",
+            if binary { "binary_little_endian" } else { "ascii" }
+        )?;
+        writeln!(self.dest, "comment generated by rplidar_drv")?;
+        writeln!(self.dest, "element vertex {}", self.points.len())?;
+        writeln!(self.dest, "property float x")?;
+        writeln!(self.dest, "property float y")?;
+        writeln!(self.dest, "property float z")?;
+        writeln!(self.dest, "property float range")?;
+        writeln!(self.dest, "property float bearing")?;
+        writeln!(self.dest, "property uchar quality")?;
+        writeln!(self.dest, "property double timestamp")?;
+        writeln!(self.dest, "end_header")?;
+
+        self.write_body(binary)
+    }
+
+    fn write_pcd(&mut self) -> Result<()> {
+        let binary = self.format == PointCloudFormat::PcdBinary;
+
+        writeln!(self.dest, "# .PCD v0.7 - Point Cloud Data file format")?;
+        writeln!(self.dest, "VERSION 0.7")?;
+        writeln!(self.dest, "FIELDS x y z range bearing quality timestamp")?;
+        writeln!(self.dest, "SIZE 4 4 4 4 4 1 8")?;
+        writeln!(self.dest, "TYPE F F F F F U F")?;
+        writeln!(self.dest, "COUNT 1 1 1 1 1 1 1")?;
+        writeln!(self.dest, "WIDTH {}", self.points.len())?;
+        writeln!(self.dest, "HEIGHT 1")?;
+        writeln!(self.dest, "VIEWPOINT 0 0 0 1 0 0 0")?;
+        writeln!(self.dest, "POINTS {}", self.points.len())?;
+        writeln!(self.dest, "DATA {}", if binary { "binary" } else { "ascii" })?;
+
+        self.write_body(binary)
+    }
+
+    /// PCD and PLY share the same field order and body encoding
+    fn write_body(&mut self, binary: bool) -> Result<()> {
+        for p in self.points.iter() {
+            if binary {
+                self.dest.write_f32::<LittleEndian>(p.x)?;
+                self.dest.write_f32::<LittleEndian>(p.y)?;
+                self.dest.write_f32::<LittleEndian>(p.z)?;
+                self.dest.write_f32::<LittleEndian>(p.range)?;
+                self.dest.write_f32::<LittleEndian>(p.bearing)?;
+                self.dest.write_u8(p.quality)?;
+                self.dest.write_f64::<LittleEndian>(p.timestamp)?;
+            } else {
+                writeln!(
+                    self.dest,
+                    "{} {} {} {} {} {} {}",
+                    p.x, p.y, p.z, p.range, p.bearing, p.quality, p.timestamp
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// export a single scan into `dest`
+pub fn export_scan<W: Write>(
+    dest: W,
+    format: PointCloudFormat,
+    mounting: &SensorMounting,
+    scan: &[ScanPoint],
+    timestamp: f64,
+) -> Result<W> {
+    let mut writer = PointCloudWriter::with_mounting(dest, format, *mounting);
+    writer.write_scan(scan, timestamp)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(angle_z_q14: u16, dist_mm_q2: u32) -> ScanPoint {
+        ScanPoint {
+            angle_z_q14,
+            dist_mm_q2,
+            quality: 188,
+            flag: 0,
+        }
+    }
+
+    fn scan() -> Vec<ScanPoint> {
+        vec![point(0, 4000), point(16384, 8000), point(32768, 0)]
+    }
+
+    #[test]
+    fn export_csv() {
+        let out = export_scan(Vec::new(), PointCloudFormat::Csv, &SensorMounting::identity(), &scan(), 1.5f64).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "x,y,z,range,bearing,quality,timestamp");
+        assert_eq!(lines[1], "1,0,0,1,0,188,1.5");
+
+        // 90 degrees clockwise is on the right side of the robot
+        let fields: Vec<f32> = lines[2].split(',').map(|f| f.parse().unwrap()).collect();
+        assert!(fields[0].abs() < 1e-6);
+        assert!((fields[1] + 2f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn export_with_mounting() {
+        let mounting = SensorMounting::new(0.5f64, 0f64, 0.2f64, std::f64::consts::PI);
+        let out = export_scan(Vec::new(), PointCloudFormat::PcdAscii, &mounting, &scan()[0..1], 0f64).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let body: Vec<f32> = text.lines().last().unwrap().split(' ').map(|f| f.parse().unwrap()).collect();
+
+        assert!((body[0] + 0.5f32).abs() < 1e-6);
+        assert!(body[1].abs() < 1e-6);
+        assert!((body[2] - 0.2f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn export_pcd_binary() {
+        let out = export_scan(Vec::new(), PointCloudFormat::PcdBinary, &SensorMounting::identity(), &scan(), 0f64).unwrap();
+        let header = b"DATA binary\n";
+        let pos = out.windows(header.len()).position(|w| w == header).unwrap();
+
+        assert_eq!(out.len() - pos - header.len(), 2 * 29);
+        assert!(String::from_utf8_lossy(&out[0..pos]).contains("POINTS 2\n"));
+    }
+
+    #[test]
+    fn export_ply_ascii() {
+        let mut writer = PointCloudWriter::new(Vec::new(), PointCloudFormat::PlyAscii);
+        writer.write_scan(&scan(), 0f64).unwrap();
+        writer.write_scan(&scan(), 0.1f64).unwrap();
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert!(text.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(text.contains("element vertex 4\n"));
+        assert_eq!(text.split("end_header\n").nth(1).unwrap().lines().count(), 4);
+    }
+}
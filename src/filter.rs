@@ -0,0 +1,567 @@
+//! Composable scan filters
+//!
+//! Filters work in place on the output of `grab_scan`. Rejected points are not
+//! removed from the scan, they are marked invalid (distance and quality set to
+//! zero) so that the layout of the scan is kept and `ScanPoint::is_valid` keeps
+//! working. Filters keep their working buffers between scans, so no allocation
+//! happens once the buffers have grown to the size of a scan.
+//!
+//! # Example
+//! ```ignore
+//! let mode = rplidar.start_scan()?;
+//!
+//! let mut filters = ScanFilterChain::new()
+//!     .with(RangeFilter::from_scan_mode(&mode, 0.15f32))
+//!     .with(AngularMaskFilter::new(&[(2.8f32, 3.5f32)]))
+//!     .with(SpeckleFilter::new(0.05f32, 1))
+//!     .with(ShadowFilter::new(0.17f32, 2.97f32));
+//!
+//! loop {
+//!     let mut scan = rplidar.grab_scan()?;
+//!     filters.filter(&mut scan);
+//! }
+//! ```
+
+use super::prelude::{ScanMode, ScanPoint};
+use std::f32::consts::PI;
+
+const PI2: f32 = PI * 2f32;
+
+/// A filter of scans
+pub trait ScanFilter {
+    /// filter the scan in place, marking rejected points invalid
+    fn filter(&mut self, scan: &mut [ScanPoint]);
+
+    /// forget state collected from previous scans
+    fn reset(&mut self) {}
+}
+
+/// mark a point as invalid
+fn invalidate(point: &mut ScanPoint) {
+    point.dist_mm_q2 = 0;
+    point.quality = 0;
+}
+
+/// clockwise angle from `from` to `to` in `[0, 2π)`
+fn angle_cw(from: f32, to: f32) -> f32 {
+    let diff = (to - from) % PI2;
+    if diff < 0f32 {
+        diff + PI2
+    } else {
+        diff
+    }
+}
+
+/// distance between two valid points (meters)
+fn point_distance(a: &ScanPoint, b: &ScanPoint) -> f32 {
+    let (ra, rb) = (a.distance(), b.distance());
+    let delta = a.angle() - b.angle();
+    (ra * ra + rb * rb - 2f32 * ra * rb * delta.cos()).max(0f32).sqrt()
+}
+
+/// median of `values`, sorting them in place
+fn median(values: &mut [u32]) -> u32 {
+    values.sort_unstable();
+    values[values.len() / 2]
+}
+
+/// A chain of filters applied in order
+#[derive(Default)]
+pub struct ScanFilterChain {
+    filters: Vec<Box<dyn ScanFilter + Send>>,
+}
+
+impl ScanFilterChain {
+    /// an empty chain
+    pub fn new() -> ScanFilterChain {
+        ScanFilterChain { filters: Vec::new() }
+    }
+
+    /// append a filter
+    pub fn with<F: ScanFilter + Send + 'static>(mut self, filter: F) -> ScanFilterChain {
+        self.push(filter);
+        self
+    }
+
+    /// append a filter
+    pub fn push<F: ScanFilter + Send + 'static>(&mut self, filter: F) {
+        self.filters.push(Box::new(filter));
+    }
+
+    /// number of filters in the chain
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// true if there is no filter in the chain
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl std::fmt::Debug for ScanFilterChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ScanFilterChain {{ {} filters }}", self.filters.len())
+    }
+}
+
+impl ScanFilter for ScanFilterChain {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        for filter in self.filters.iter_mut() {
+            filter.filter(scan);
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+    }
+}
+
+/// Rejects points outside of a range window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeFilter {
+    /// Minimum range (meters)
+    pub range_min: f32,
+
+    /// Maximum range (meters)
+    pub range_max: f32,
+}
+
+impl RangeFilter {
+    /// keep points with range in `[range_min, range_max]`
+    pub fn new(range_min: f32, range_max: f32) -> RangeFilter {
+        RangeFilter { range_min, range_max }
+    }
+
+    /// keep points between `range_min` and the max distance of the scan mode
+    pub fn from_scan_mode(mode: &ScanMode, range_min: f32) -> RangeFilter {
+        RangeFilter::new(range_min, mode.max_distance)
+    }
+}
+
+impl ScanFilter for RangeFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        for point in scan.iter_mut().filter(|p| p.is_valid()) {
+            let range = point.distance();
+            if range < self.range_min || range > self.range_max {
+                invalidate(point);
+            }
+        }
+    }
+}
+
+/// Rejects points in angular sectors, e.g. parts of the robot body seen by the LIDAR
+#[derive(Debug, Clone, PartialEq)]
+pub struct AngularMaskFilter {
+    sectors: Vec<(f32, f32)>,
+}
+
+impl AngularMaskFilter {
+    /// mask sectors `(start, end)` in sensor angles (radians, clockwise)
+    ///
+    /// A sector with `start > end` wraps around zero.
+    pub fn new(sectors: &[(f32, f32)]) -> AngularMaskFilter {
+        AngularMaskFilter {
+            sectors: sectors.to_vec(),
+        }
+    }
+
+    /// mask one more sector
+    pub fn add_sector(&mut self, start: f32, end: f32) {
+        self.sectors.push((start, end));
+    }
+
+    /// true if the angle is masked
+    pub fn is_masked(&self, angle: f32) -> bool {
+        self.sectors
+            .iter()
+            .any(|&(start, end)| angle_cw(start, angle) <= angle_cw(start, end))
+    }
+}
+
+impl ScanFilter for AngularMaskFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        for point in scan.iter_mut().filter(|p| p.is_valid()) {
+            if self.is_masked(point.angle()) {
+                invalidate(point);
+            }
+        }
+    }
+}
+
+/// Rejects points with low quality
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityFilter {
+    /// Minimum quality
+    pub min_quality: u8,
+}
+
+impl QualityFilter {
+    /// keep points with quality of at least `min_quality`
+    pub fn new(min_quality: u8) -> QualityFilter {
+        QualityFilter { min_quality }
+    }
+}
+
+impl ScanFilter for QualityFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        for point in scan.iter_mut().filter(|p| p.is_valid()) {
+            if point.quality < self.min_quality {
+                invalidate(point);
+            }
+        }
+    }
+}
+
+/// Replaces the range of each point with the median of its neighbours in the same scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct MedianFilter {
+    half_window: usize,
+    ranges: Vec<u32>,
+    window: Vec<u32>,
+}
+
+impl MedianFilter {
+    /// median over `2 * half_window + 1` consecutive points
+    pub fn new(half_window: usize) -> MedianFilter {
+        MedianFilter {
+            half_window,
+            ranges: Vec::new(),
+            window: Vec::with_capacity(2 * half_window + 1),
+        }
+    }
+}
+
+impl ScanFilter for MedianFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        self.ranges.clear();
+        self.ranges.extend(scan.iter().map(|p| if p.is_valid() { p.dist_mm_q2 } else { 0 }));
+
+        for (i, point) in scan.iter_mut().enumerate() {
+            if !point.is_valid() {
+                continue;
+            }
+
+            let begin = i.saturating_sub(self.half_window);
+            let end = std::cmp::min(i + self.half_window + 1, self.ranges.len());
+
+            self.window.clear();
+            self.window
+                .extend(self.ranges[begin..end].iter().filter(|&&r| r != 0));
+
+            point.dist_mm_q2 = median(&mut self.window);
+        }
+    }
+}
+
+/// Replaces the range of each point with the median of the same direction over recent scans
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemporalMedianFilter {
+    bins: usize,
+    depth: usize,
+    history: Vec<u32>,
+    head: usize,
+    current: Vec<u32>,
+    window: Vec<u32>,
+}
+
+impl TemporalMedianFilter {
+    /// median over the current and `depth` previous scans, matching directions with `bins` angular bins
+    pub fn new(bins: usize, depth: usize) -> TemporalMedianFilter {
+        let bins = std::cmp::max(bins, 1);
+
+        TemporalMedianFilter {
+            bins,
+            depth,
+            history: vec![0; bins * depth],
+            head: 0,
+            current: vec![0; bins],
+            window: Vec::with_capacity(depth + 1),
+        }
+    }
+
+    fn bin_of(&self, point: &ScanPoint) -> usize {
+        ((point.angle() / PI2 * self.bins as f32) as usize) % self.bins
+    }
+}
+
+impl ScanFilter for TemporalMedianFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        for bin in self.current.iter_mut() {
+            *bin = 0;
+        }
+
+        for point in scan.iter_mut().filter(|p| p.is_valid()) {
+            let bin = self.bin_of(point);
+
+            if self.current[bin] == 0 || point.dist_mm_q2 < self.current[bin] {
+                self.current[bin] = point.dist_mm_q2;
+            }
+
+            self.window.clear();
+            self.window.push(point.dist_mm_q2);
+            for k in 0..self.depth {
+                let r = self.history[k * self.bins + bin];
+                if r != 0 {
+                    self.window.push(r);
+                }
+            }
+
+            point.dist_mm_q2 = median(&mut self.window);
+        }
+
+        if self.depth > 0 {
+            let offset = self.head * self.bins;
+            self.history[offset..offset + self.bins].copy_from_slice(&self.current);
+            self.head = (self.head + 1) % self.depth;
+        }
+    }
+
+    fn reset(&mut self) {
+        for r in self.history.iter_mut() {
+            *r = 0;
+        }
+        self.head = 0;
+    }
+}
+
+/// Removes isolated points (speckles)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeckleFilter {
+    /// Max distance to a neighbour (meters)
+    pub max_distance: f32,
+
+    /// Minimum number of neighbours a point must have to be kept
+    pub min_neighbors: usize,
+
+    /// Number of points checked on each side
+    pub half_window: usize,
+
+    valid: Vec<bool>,
+}
+
+impl SpeckleFilter {
+    /// keep points with at least `min_neighbors` neighbours closer than `max_distance`
+    pub fn new(max_distance: f32, min_neighbors: usize) -> SpeckleFilter {
+        SpeckleFilter {
+            max_distance,
+            min_neighbors,
+            half_window: std::cmp::max(min_neighbors, 1),
+            valid: Vec::new(),
+        }
+    }
+
+    /// check `half_window` points on each side
+    pub fn with_half_window(mut self, half_window: usize) -> SpeckleFilter {
+        self.half_window = half_window;
+        self
+    }
+}
+
+impl ScanFilter for SpeckleFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        let len = scan.len();
+
+        self.valid.clear();
+        self.valid.extend(scan.iter().map(|p| p.is_valid()));
+
+        for i in 0..len {
+            if !self.valid[i] {
+                continue;
+            }
+
+            let begin = i.saturating_sub(self.half_window);
+            let end = std::cmp::min(i + self.half_window + 1, len);
+
+            // neighbours are compared with the original flags, so removal order doesn't matter
+            let neighbors = (begin..end)
+                .filter(|&j| j != i && self.valid[j])
+                .filter(|&j| point_distance(&scan[i], &scan[j]) <= self.max_distance)
+                .count();
+
+            if neighbors < self.min_neighbors {
+                invalidate(&mut scan[i]);
+            }
+        }
+    }
+}
+
+/// Removes veiling points at object edges (shadows)
+///
+/// When the laser beam hits the edge of an object, the measured range may be
+/// somewhere between the object and the background. Such points form a line
+/// along the beam. A point is removed when the angle between the line of sight
+/// and the segment to one of its neighbours is outside of `[min_angle, max_angle]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowFilter {
+    /// Minimum angle of incidence (radians)
+    pub min_angle: f32,
+
+    /// Maximum angle of incidence (radians)
+    pub max_angle: f32,
+
+    /// Number of neighbours checked on each side
+    pub half_window: usize,
+
+    remove: Vec<bool>,
+}
+
+impl ShadowFilter {
+    /// remove points with incidence angle outside of `[min_angle, max_angle]`
+    pub fn new(min_angle: f32, max_angle: f32) -> ShadowFilter {
+        ShadowFilter {
+            min_angle,
+            max_angle,
+            half_window: 1,
+            remove: Vec::new(),
+        }
+    }
+
+    /// check `half_window` neighbours on each side
+    pub fn with_half_window(mut self, half_window: usize) -> ShadowFilter {
+        self.half_window = half_window;
+        self
+    }
+}
+
+impl ScanFilter for ShadowFilter {
+    fn filter(&mut self, scan: &mut [ScanPoint]) {
+        let len = scan.len();
+
+        self.remove.clear();
+        self.remove.resize(len, false);
+
+        for i in 0..len {
+            if !scan[i].is_valid() {
+                continue;
+            }
+
+            let end = std::cmp::min(i + self.half_window + 1, len);
+            for j in i + 1..end {
+                if !scan[j].is_valid() {
+                    continue;
+                }
+
+                let (r1, r2) = (scan[i].distance(), scan[j].distance());
+                let delta = (scan[j].angle() - scan[i].angle()).abs();
+                let delta = delta.min(PI2 - delta);
+                let angle = (r2 * delta.sin()).atan2(r1 - r2 * delta.cos()).abs();
+
+                if angle < self.min_angle || angle > self.max_angle {
+                    // the farther one is the veiling point
+                    if r1 > r2 {
+                        self.remove[i] = true;
+                    } else {
+                        self.remove[j] = true;
+                    }
+                }
+            }
+        }
+
+        for (point, &remove) in scan.iter_mut().zip(self.remove.iter()) {
+            if remove {
+                invalidate(point);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(degree: f32, range: f32, quality: u8) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality,
+            flag: 0,
+        };
+        p.set_angle(degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    /// a wall at 1m sampled every degree from 0 to 9 degrees
+    fn wall() -> Vec<ScanPoint> {
+        (0..10).map(|i| point(i as f32, 1f32, 40)).collect()
+    }
+
+    fn valid_count(scan: &[ScanPoint]) -> usize {
+        scan.iter().filter(|p| p.is_valid()).count()
+    }
+
+    #[test]
+    fn chain_of_simple_filters() {
+        let mut scan = wall();
+        scan[1].quality = 4;
+        scan[2].set_distance(20f32);
+
+        let mut chain = ScanFilterChain::new()
+            .with(QualityFilter::new(8))
+            .with(RangeFilter::new(0.15f32, 12f32))
+            .with(AngularMaskFilter::new(&[(8.5f32.to_radians(), 0.5f32.to_radians())]));
+        chain.filter(&mut scan);
+
+        assert_eq!(chain.len(), 3);
+        assert!(!scan[0].is_valid());
+        assert!(!scan[1].is_valid());
+        assert!(!scan[2].is_valid());
+        assert!(!scan[9].is_valid());
+        assert_eq!(valid_count(&scan), 6);
+    }
+
+    #[test]
+    fn median_removes_spike() {
+        let mut scan = wall();
+        scan[5].set_distance(3f32);
+
+        MedianFilter::new(1).filter(&mut scan);
+
+        assert!((scan[5].distance() - 1f32).abs() < 1e-3);
+        assert_eq!(valid_count(&scan), 10);
+    }
+
+    #[test]
+    fn temporal_median() {
+        let mut filter = TemporalMedianFilter::new(360, 2);
+
+        filter.filter(&mut wall());
+        filter.filter(&mut wall());
+
+        let mut scan = wall();
+        scan[3].set_distance(5f32);
+        filter.filter(&mut scan);
+
+        assert!((scan[3].distance() - 1f32).abs() < 1e-3);
+    }
+
+    #[test]
+    fn speckle_removed() {
+        let mut scan = wall();
+        scan[5].set_distance(3f32);
+
+        SpeckleFilter::new(0.05f32, 1).filter(&mut scan);
+
+        assert!(!scan[5].is_valid());
+        assert_eq!(valid_count(&scan), 9);
+    }
+
+    #[test]
+    fn shadow_removed() {
+        let mut scan = wall();
+        // veiling point between the wall and a background at 3m
+        scan[6].set_distance(2f32);
+        for p in scan[7..].iter_mut() {
+            p.set_distance(3f32);
+        }
+
+        ShadowFilter::new(0.17f32, 2.97f32).filter(&mut scan);
+
+        assert!(!scan[6].is_valid());
+        assert!(scan[5].is_valid());
+        assert!(scan[8].is_valid());
+    }
+}
@@ -0,0 +1,451 @@
+//! Resample scans into fixed angular bins
+//!
+//! Scans from capsuled and ultra capsuled modes have irregular angles which vary
+//! from scan to scan. `LaserScanBuilder` puts the points of a scan into bins of
+//! fixed angular resolution, which is what most range based algorithms expect.
+//!
+//! Angles follow the RPLIDAR convention: radians, increasing clockwise, in `[0, 2π)`.
+//!
+//! # Example
+//! ```ignore
+//! let builder = LaserScanBuilder::new()
+//!     .bin_count(720)
+//!     .range_window(0.15f32, 12f32)
+//!     .multi_hit(MultiHitPolicy::Nearest)
+//!     .empty_bin(EmptyBinPolicy::Infinity);
+//!
+//! let laser_scan = builder.build(&rplidar.grab_scan()?);
+//! ```
+
+use super::prelude::ScanPoint;
+use std::f32::consts::PI;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const PI2: f32 = PI * 2f32;
+
+/// Angle resolution of `ScanPoint` (q14 quarter turns), finer bins stay empty
+const MIN_ANGLE_INCREMENT: f32 = PI2 / 65536f32;
+
+/// What to do when more than one point falls into the same bin
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MultiHitPolicy {
+    /// Keep the nearest point
+    Nearest,
+
+    /// Average range and quality of all points
+    Mean,
+
+    /// Keep the point with highest quality (the nearest one on ties)
+    HighestQuality,
+}
+
+/// What to put in bins without any point
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EmptyBinPolicy {
+    /// `f32::NAN`
+    NaN,
+
+    /// `f32::INFINITY`
+    Infinity,
+
+    /// The maximum range of the range window
+    MaxRange,
+}
+
+/// A scan with fixed angular resolution
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LaserScan {
+    /// Angle of the first bin (radians, clockwise)
+    pub angle_min: f32,
+
+    /// Angular distance between bins (radians)
+    pub angle_increment: f32,
+
+    /// Minimum range accepted (meters)
+    pub range_min: f32,
+
+    /// Maximum range accepted (meters)
+    pub range_max: f32,
+
+    /// Range of each bin (meters), bin `i` is at `angle_min + i * angle_increment`
+    pub ranges: Vec<f32>,
+
+    /// Quality of each bin, zero for empty bins
+    pub intensities: Vec<f32>,
+
+    /// Points per bin while averaging, kept empty to reuse its buffer in `build_into`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hits: Vec<u32>,
+}
+
+impl LaserScan {
+    /// an empty laser scan
+    pub fn new() -> LaserScan {
+        LaserScan {
+            angle_min: 0f32,
+            angle_increment: 0f32,
+            range_min: 0f32,
+            range_max: 0f32,
+            ranges: Vec::new(),
+            intensities: Vec::new(),
+            hits: Vec::new(),
+        }
+    }
+
+    /// angle of the last bin
+    pub fn angle_max(&self) -> f32 {
+        self.angle_of(self.ranges.len().saturating_sub(1))
+    }
+
+    /// angle of bin `index`
+    pub fn angle_of(&self, index: usize) -> f32 {
+        self.angle_min + (index as f32) * self.angle_increment
+    }
+
+    /// number of bins
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// true if the scan has no bins
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl Default for LaserScan {
+    fn default() -> LaserScan {
+        LaserScan::new()
+    }
+}
+
+/// Builds `LaserScan` from scan points
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaserScanBuilder {
+    angle_min: f32,
+    angle_increment: f32,
+    bin_count: usize,
+    range_min: f32,
+    range_max: f32,
+    multi_hit: MultiHitPolicy,
+    empty_bin: EmptyBinPolicy,
+}
+
+impl LaserScanBuilder {
+    /// full circle with 1 degree resolution, keeping nearest points, empty bins as NaN
+    pub fn new() -> LaserScanBuilder {
+        LaserScanBuilder {
+            angle_min: 0f32,
+            angle_increment: PI2 / 360f32,
+            bin_count: 360,
+            range_min: 0f32,
+            range_max: f32::INFINITY,
+            multi_hit: MultiHitPolicy::Nearest,
+            empty_bin: EmptyBinPolicy::NaN,
+        }
+    }
+
+    /// cover the full circle starting at `angle_min` with `bin_count` bins
+    pub fn bin_count(mut self, bin_count: usize) -> LaserScanBuilder {
+        let bin_count = std::cmp::max(bin_count, 1);
+        self.bin_count = bin_count;
+        self.angle_increment = PI2 / (bin_count as f32);
+        self
+    }
+
+    /// cover `[angle_min, angle_max]` (clockwise) with bins of `angle_increment`
+    ///
+    /// `angle_increment` is raised to the angle resolution of `ScanPoint` if it is smaller, zero, negative or NaN.
+    pub fn angle_window(mut self, angle_min: f32, angle_max: f32, angle_increment: f32) -> LaserScanBuilder {
+        let mut span = angle_max - angle_min;
+        if span < 0f32 {
+            span += PI2;
+        }
+
+        // written to also catch NaN
+        let angle_increment = if angle_increment >= MIN_ANGLE_INCREMENT {
+            angle_increment
+        } else {
+            MIN_ANGLE_INCREMENT
+        };
+
+        self.angle_min = angle_min;
+        self.angle_increment = angle_increment;
+        self.bin_count = std::cmp::max((span / angle_increment).round() as usize + 1, 1);
+        self
+    }
+
+    /// set the angle of the first bin
+    pub fn angle_min(mut self, angle_min: f32) -> LaserScanBuilder {
+        self.angle_min = angle_min;
+        self
+    }
+
+    /// only keep points with range in `[range_min, range_max]`
+    pub fn range_window(mut self, range_min: f32, range_max: f32) -> LaserScanBuilder {
+        self.range_min = range_min;
+        self.range_max = range_max;
+        self
+    }
+
+    /// set policy for bins with multiple points
+    pub fn multi_hit(mut self, policy: MultiHitPolicy) -> LaserScanBuilder {
+        self.multi_hit = policy;
+        self
+    }
+
+    /// set policy for bins without any point
+    pub fn empty_bin(mut self, policy: EmptyBinPolicy) -> LaserScanBuilder {
+        self.empty_bin = policy;
+        self
+    }
+
+    /// number of bins in the output
+    pub fn len(&self) -> usize {
+        self.bin_count
+    }
+
+    /// true if the output has no bins (never, there is at least one bin)
+    pub fn is_empty(&self) -> bool {
+        self.bin_count == 0
+    }
+
+    /// index of the bin for `angle`, or `None` if the angle is outside of the window
+    pub fn bin_of(&self, angle: f32) -> Option<usize> {
+        let mut offset = (angle - self.angle_min) % PI2;
+        if offset < 0f32 {
+            offset += PI2;
+        }
+
+        let full_circle = (self.angle_increment * self.bin_count as f32) >= PI2 - self.angle_increment / 2f32;
+        let index = (offset / self.angle_increment).round() as usize;
+
+        if index < self.bin_count {
+            Some(index)
+        } else if full_circle {
+            Some(index % self.bin_count)
+        } else if PI2 - offset <= self.angle_increment / 2f32 {
+            // slightly before angle_min
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// bin the scan
+    pub fn build(&self, scan: &[ScanPoint]) -> LaserScan {
+        let mut out = LaserScan::new();
+        self.build_into(scan, &mut out);
+        out
+    }
+
+    /// bin the scan into `out`, reusing its buffers
+    pub fn build_into(&self, scan: &[ScanPoint], out: &mut LaserScan) {
+        out.angle_min = self.angle_min;
+        out.angle_increment = self.angle_increment;
+        out.range_min = self.range_min;
+        out.range_max = self.range_max;
+
+        out.ranges.clear();
+        out.ranges.resize(self.bin_count, f32::NAN);
+        out.intensities.clear();
+        out.intensities.resize(self.bin_count, 0f32);
+
+        let mut hits = std::mem::take(&mut out.hits);
+        if self.multi_hit == MultiHitPolicy::Mean {
+            hits.resize(self.bin_count, 0u32);
+        }
+
+        for point in scan.iter().filter(|p| p.is_valid()) {
+            let range = point.distance();
+            if range < self.range_min || range > self.range_max {
+                continue;
+            }
+
+            let index = match self.bin_of(point.angle()) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let quality = point.quality as f32;
+            let current = out.ranges[index];
+
+            if current.is_nan() {
+                out.ranges[index] = range;
+                out.intensities[index] = quality;
+                if !hits.is_empty() {
+                    hits[index] = 1;
+                }
+                continue;
+            }
+
+            match self.multi_hit {
+                MultiHitPolicy::Nearest => {
+                    if range < current {
+                        out.ranges[index] = range;
+                        out.intensities[index] = quality;
+                    }
+                }
+                MultiHitPolicy::Mean => {
+                    out.ranges[index] += range;
+                    out.intensities[index] += quality;
+                    hits[index] += 1;
+                }
+                MultiHitPolicy::HighestQuality => {
+                    let current_quality = out.intensities[index];
+                    if quality > current_quality || (quality == current_quality && range < current) {
+                        out.ranges[index] = range;
+                        out.intensities[index] = quality;
+                    }
+                }
+            }
+        }
+
+        for (i, &count) in hits.iter().enumerate() {
+            if count > 1 {
+                out.ranges[i] /= count as f32;
+                out.intensities[i] /= count as f32;
+            }
+        }
+
+        hits.clear();
+        out.hits = hits;
+
+        let empty = match self.empty_bin {
+            EmptyBinPolicy::NaN => f32::NAN,
+            EmptyBinPolicy::Infinity => f32::INFINITY,
+            EmptyBinPolicy::MaxRange => self.range_max,
+        };
+
+        if !empty.is_nan() {
+            for range in out.ranges.iter_mut().filter(|r| r.is_nan()) {
+                *range = empty;
+            }
+        }
+    }
+}
+
+impl Default for LaserScanBuilder {
+    fn default() -> LaserScanBuilder {
+        LaserScanBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(degree: f32, range: f32, quality: u8) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality,
+            flag: 0,
+        };
+        p.set_angle(degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    fn scan() -> Vec<ScanPoint> {
+        vec![
+            point(0.2f32, 2f32, 10),
+            point(0.4f32, 1f32, 5),
+            point(90.1f32, 3f32, 10),
+            point(180f32, 20f32, 10),
+            point(270f32, 0f32, 0),
+            point(359f32, 4f32, 10),
+        ]
+    }
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn bin_nearest() {
+        let scan = LaserScanBuilder::new()
+            .bin_count(4)
+            .range_window(0.1f32, 10f32)
+            .build(&scan());
+
+        assert_eq!(scan.len(), 4);
+        assert!(approx(scan.ranges[0], 1f32));
+        assert!(approx(scan.ranges[1], 3f32));
+        assert!(scan.ranges[2].is_nan());
+        assert!(scan.ranges[3].is_nan());
+        assert_eq!(scan.intensities[2], 0f32);
+        assert!(approx(scan.angle_max(), 3f32 * PI / 2f32));
+    }
+
+    #[test]
+    fn bin_mean_and_quality() {
+        let builder = LaserScanBuilder::new().bin_count(4).range_window(0f32, 10f32);
+
+        let mean = builder.clone().multi_hit(MultiHitPolicy::Mean).build(&scan());
+        assert!(approx(mean.ranges[0], 7f32 / 3f32));
+        assert!(approx(mean.intensities[0], 25f32 / 3f32));
+
+        let quality = builder.multi_hit(MultiHitPolicy::HighestQuality).build(&scan());
+        assert!(approx(quality.ranges[0], 2f32));
+    }
+
+    #[test]
+    fn empty_bins() {
+        let builder = LaserScanBuilder::new().bin_count(4).range_window(0f32, 10f32);
+
+        let inf = builder.clone().empty_bin(EmptyBinPolicy::Infinity).build(&scan());
+        assert_eq!(inf.ranges[2], f32::INFINITY);
+
+        let max = builder.empty_bin(EmptyBinPolicy::MaxRange).build(&scan());
+        assert_eq!(max.ranges[2], 10f32);
+    }
+
+    #[test]
+    fn reuse_buffers() {
+        let builder = LaserScanBuilder::new()
+            .bin_count(4)
+            .range_window(0f32, 10f32)
+            .multi_hit(MultiHitPolicy::Mean);
+
+        let mut out = LaserScan::new();
+        builder.build_into(&scan(), &mut out);
+        let hits = out.hits.capacity();
+        assert!(hits >= 4);
+
+        builder.build_into(&scan(), &mut out);
+        assert_eq!(out.hits.capacity(), hits);
+        assert!(approx(out.ranges[0], 7f32 / 3f32));
+    }
+
+    #[test]
+    fn invalid_angle_increment() {
+        for &increment in &[0f32, -1f32, f32::NAN, 1e-30f32] {
+            let builder = LaserScanBuilder::new().angle_window(0f32, 1f32.to_radians(), increment);
+            assert_eq!(builder.len(), 183);
+
+            let scan = builder.build(&scan());
+            assert!(approx(scan.angle_increment, MIN_ANGLE_INCREMENT));
+            assert_eq!(scan.ranges.iter().filter(|r| !r.is_nan()).count(), 2);
+        }
+    }
+
+    #[test]
+    fn partial_window() {
+        let builder = LaserScanBuilder::new().angle_window(
+            (-10f32).to_radians(),
+            10f32.to_radians(),
+            1f32.to_radians(),
+        );
+        assert_eq!(builder.len(), 21);
+
+        let scan = builder.build(&scan());
+        assert!(approx(scan.ranges[10], 1f32));
+        assert!(approx(scan.ranges[9], 4f32));
+        assert_eq!(scan.ranges.iter().filter(|r| !r.is_nan()).count(), 2);
+    }
+}
@@ -3,111 +3,93 @@
 //! `rplidar_drv` is driver for Slamtec Rplidar series
 
 extern crate byteorder;
-extern crate crc;
 extern crate rpos_drv;
+pub extern crate rplidar_core;
+#[cfg(any(feature = "mcap", feature = "web"))]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "web")]
+extern crate tungstenite;
 
 mod internals;
-mod answers;
-mod capsuled_parser;
-mod ultra_capsuled_parser;
-mod checksum;
-mod cmds;
 mod errors;
 mod prelude;
 mod protocol;
+mod stream;
 pub mod utils;
+pub mod scan_order;
+pub mod transform;
+pub mod export;
+pub mod laser_scan;
+pub mod filter;
+pub mod deskew;
+pub mod multi_lidar;
+pub mod tcp;
+pub mod udp;
+pub mod network;
+pub mod simulator;
+#[cfg(feature = "mcap")]
+pub mod mcap;
+#[cfg(feature = "web")]
+pub mod web;
 
 pub use self::prelude::*;
 pub use self::errors::*;
 
-pub use self::answers::RplidarResponseDeviceInfo;
+pub use rplidar_core::answers::RplidarResponseDeviceInfo;
+pub use rplidar_core::AnswerSizeLimits;
 
-use self::answers::*;
+use rplidar_core::RplidarDriver;
 use self::internals::*;
-use self::capsuled_parser::parse_capsuled;
-use self::ultra_capsuled_parser::parse_ultra_capsuled;
-use self::checksum::Checksum;
-use self::cmds::*;
+use self::stream::{InstantClock, StreamTransport};
 pub use self::protocol::RplidarHostProtocol;
-use byteorder::{ByteOrder, LittleEndian};
-use rpos_drv::{Channel, Message, Result};
+pub use self::tcp::TcpTransport;
+pub use self::udp::UdpTransport;
+pub use self::network::{NetworkConfig, NetworkProtocol, NetworkTransport};
 use std::collections::VecDeque;
 use std::io::{Read, Write};
-use std::mem::transmute_copy;
 use std::time::{ Instant, Duration };
-use crc::{ crc32 };
-
-const RPLIDAR_GET_LIDAR_CONF_START_VERSION:u16 = ((1 << 8) | (24)) as u16;
 
 /// Rplidar device driver
+///
+/// Commands and answers go through the blocking `rplidar_core::RplidarDriver`, over the stream.
 #[derive(Debug)]
 pub struct RplidarDevice<T: ?Sized> {
-    channel: Channel<RplidarHostProtocol, T>,
+    driver: RplidarDriver<StreamTransport<T>, InstantClock, Vec<u8>>,
+    clock: InstantClock,
     cached_measurement_nodes: VecDeque<ScanPoint>,
-    cached_prev_capsule: CachedPrevCapsule,
-}
-
-macro_rules! parse_resp_data {
-    ($x:expr, $t:ty) => {{
-        const SIZE: usize = std::mem::size_of::<$t>();
-        if $x.len() != SIZE {
-            Err(Error::from(RposError::OperationFail { description: "answer type mismatch".to_owned() }))
-        } else {
-            let mut slice = [0u8; SIZE];
-            slice.clone_from_slice(&$x[..]);
-            Ok(unsafe { transmute_copy::<[u8; SIZE], $t>(&slice) })
-        }
-    }};
-}
-
-macro_rules! parse_resp {
-    ($x:expr, $t:ty) => {
-        parse_resp_data!($x.data, $t)
-    };
-}
-
-macro_rules! handle_resp {
-    ($ans:expr, $x:expr, $t:ty) => {
-        if $x.cmd != $ans {
-            Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into())
-        } else {
-            parse_resp!($x, $t)
-        }
-    };
+    cached_measurement_timestamps: VecDeque<Instant>,
 }
 
-impl From<RplidarResponseMeasurementNodeHq> for ScanPoint {
-    fn from(p: RplidarResponseMeasurementNodeHq) -> ScanPoint {
-        ScanPoint {
-            angle_z_q14: p.angle_z_q14,
-            dist_mm_q2: p.dist_mm_q2,
-            quality: p.quality,
-            flag: p.flag,
-        }
-    }
+/// timeout of the core driver, in milliseconds rounded up
+fn timeout_ms(timeout: Duration) -> u64 {
+    ((timeout.as_micros() + 999) / 1000) as u64
 }
 
 impl<T: ?Sized> RplidarDevice<T>
 where
     T: Read + Write,
 {
-    /// Construct a new RplidarDevice with channel
+    /// Construct a new RplidarDevice with stream
     ///
     /// # Example
     /// ```ignore
     /// let mut serial_port = serialport::open(serial_port_name)?;
-    /// let channel = Channel::new(RplidarHostProtocol::new(), serial_port);
-    /// let rplidar_device = RplidarDevice::new(channel);
+    /// let rplidar_device = RplidarDevice::new(serial_port);
     /// ```
-    pub fn new(channel: Channel<RplidarHostProtocol, T>) -> RplidarDevice<T> {
+    pub fn new(stream: Box<T>) -> RplidarDevice<T> {
+        let clock = InstantClock::new();
+
         RplidarDevice {
-            channel: channel,
+            driver: RplidarDriver::with_buffer(StreamTransport::new(stream), clock, Vec::new()),
+            clock,
             cached_measurement_nodes: VecDeque::with_capacity(RPLIDAR_DEFAULT_CACHE_DEPTH),
-            cached_prev_capsule: CachedPrevCapsule::None,
+            cached_measurement_timestamps: VecDeque::with_capacity(RPLIDAR_DEFAULT_CACHE_DEPTH),
         }
     }
 
-    /// Construct a new RplidarDevice with stream
+    /// Construct a new RplidarDevice with stream, same as `new`
     ///
     /// # Example
     /// ```ignore
@@ -115,7 +97,27 @@ where
     /// let rplidar_device = RplidarDevice::with_stream(serial_port);
     /// ```
     pub fn with_stream(stream: Box<T>) -> RplidarDevice<T> {
-        RplidarDevice::<T>::new(rpos_drv::Channel::new(RplidarHostProtocol::new(), stream))
+        RplidarDevice::new(stream)
+    }
+
+    /// set the largest plausible size of each answer type
+    ///
+    /// Answer headers announcing more are dropped as damaged and the decoder resyncs at once,
+    /// `AnswerSizeLimits::new` knows the sizes of the standard answers.
+    pub fn set_answer_size_limits(&mut self, limits: AnswerSizeLimits) {
+        self.driver.set_answer_size_limits(limits);
+    }
+
+    /// number of answer headers dropped for announcing implausibly large answers
+    pub fn rejected_headers(&self) -> usize {
+        self.driver.rejected_headers()
+    }
+
+    /// number of legacy measurement nodes dropped for wrong check bits
+    ///
+    /// After repeated invalid nodes the answer stream is realigned byte by byte, as bytes were likely lost.
+    pub fn invalid_nodes(&self) -> usize {
+        self.driver.invalid_nodes()
     }
 
     /// get device info of the RPLIDAR
@@ -128,97 +130,40 @@ where
         &mut self,
         timeout: Duration,
     ) -> Result<RplidarResponseDeviceInfo> {
-        if let Some(msg) = self
-            .channel
-            .invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_INFO), timeout)?
-        {
-            return handle_resp!(RPLIDAR_ANS_TYPE_DEVINFO, msg, RplidarResponseDeviceInfo);
-        }
-
-        return Err(RposError::OperationTimeout.into());
+        self.driver.get_device_info(timeout_ms(timeout)).map_err(from_driver_error)
     }
 
     /// Stop lidar
+    ///
+    /// Measurements are loop answers without sync bytes, the decoder is reset so the
+    /// answers of the next commands aren't taken for measurements.
     pub fn stop(&mut self) -> Result<()> {
-        self.channel.write(&Message::new(RPLIDAR_CMD_STOP))?;
-        return Ok(());
+        self.driver.stop().map_err(from_driver_error)
     }
 
     /// Reset RPLIDAR core
     pub fn core_reset(&mut self) -> Result<()> {
-        self.channel.write(&Message::new(RPLIDAR_CMD_RESET))?;
-        return Ok(());
+        self.driver.core_reset().map_err(from_driver_error)
     }
 
     /// Set motor PWM (via accessory board)
     pub fn set_motor_pwm(&mut self, pwm: u16) -> Result<()> {
-        let mut payload = [0; 2];
-        LittleEndian::write_u16(&mut payload, pwm);
-
-        self.channel
-            .write(&Message::with_data(RPLIDAR_CMD_SET_MOTOR_PWM, &payload))?;
+        self.driver.set_motor_pwm(pwm).map_err(from_driver_error)
+    }
 
-        return Ok(());
+    /// Set motor speed in RPM (LIDARs controlling the motor themselves, e.g. S series)
+    pub fn set_motor_speed(&mut self, rpm: u16) -> Result<()> {
+        self.driver.set_motor_speed(rpm).map_err(from_driver_error)
     }
 
     /// Stop motor
     pub fn stop_motor(&mut self) -> Result<()> {
-        self.set_motor_pwm(0)
+        self.driver.stop_motor().map_err(from_driver_error)
     }
 
     /// Start motor
     pub fn start_motor(&mut self) -> Result<()> {
-        self.set_motor_pwm(RPLIDAR_DEFAULT_MOTOR_PWM)
-    }
-
-    /*
-    /// Get LIDAR config
-    fn get_lidar_conf(&mut self, config_type: u32) -> Result<Vec<u8>> {
-        self.get_lidar_conf_with_timeout(config_type, RPLIDAR_DEFAULT_TIMEOUT)
-    }
-
-    /// get lidar config with parameter
-    fn get_lidar_conf_with_param(&mut self, config_type: u32, param: &[u8]) -> Result<Vec<u8>> {
-        self.get_lidar_conf_with_param_and_timeout(config_type, param, RPLIDAR_DEFAULT_TIMEOUT)
-    }
-    */
-
-    /// get lidar config with timeout
-    fn get_lidar_conf_with_timeout(
-        &mut self,
-        config_type: u32,
-        timeout: Duration,
-    ) -> Result<Vec<u8>> {
-        self.get_lidar_conf_with_param_and_timeout(config_type, &[], timeout)
-    }
-
-    /// get lidar config with parameter and timeout
-    fn get_lidar_conf_with_param_and_timeout(
-        &mut self,
-        config_type: u32,
-        param: &[u8],
-        timeout: Duration,
-    ) -> Result<Vec<u8>> {
-        let mut msg = Message::with_data(RPLIDAR_CMD_GET_LIDAR_CONF, &[0; 4]);
-
-        LittleEndian::write_u32(&mut msg.data, config_type);
-        msg.data.extend_from_slice(param);
-
-        let response = self.channel.invoke(&msg, timeout)?;
-
-        if let Some(mut response_msg) = response {
-            if response_msg.cmd != RPLIDAR_ANS_TYPE_GET_LIDAR_CONF {
-                return Err(RposError::OperationFail { description: "answer type mismatch".to_owned() }.into());
-            } else if response_msg.data.len() < 4
-                || LittleEndian::read_u32(&response_msg.data[0..4]) != config_type
-            {
-                return Err(RposError::OperationFail { description: "answer config type mismatch".to_owned() }.into());
-            } else {
-                return Ok(response_msg.data.split_off(4));
-            }
-        } else {
-            return Err(RposError::OperationTimeout.into());
-        }
+        self.driver.start_motor().map_err(from_driver_error)
     }
 
     /// get typical scan mode of target LIDAR
@@ -228,114 +173,7 @@ where
 
     /// get typical scan mode of target LIDAR with timeout
     pub fn get_typical_scan_mode_with_timeout(&mut self, timeout: Duration) -> Result<u16> {
-        let device_info = self.get_device_info_with_timeout(timeout)?;
-
-        if device_info.firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
-            return Ok(if device_info.model >= 0x20u8 {
-                1u16
-            } else {
-                0u16
-            });
-        }
-
-        let scan_mode_data =
-            self.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_TYPICAL, timeout)?;
-
-        return parse_resp_data!(scan_mode_data, u16);
-    }
-
-    /// get lidar sample duration
-    fn get_scan_mode_us_per_sample_with_timeout(
-        &mut self,
-        scan_mode: u16,
-        timeout: Duration,
-    ) -> Result<f32> {
-        let mut param = [0; 2];
-        LittleEndian::write_u16(&mut param, scan_mode);
-        let us_per_sample_data = self.get_lidar_conf_with_param_and_timeout(
-            RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE,
-            &param,
-            timeout,
-        )?;
-        let us_per_sample = (parse_resp_data!(us_per_sample_data, u32)? as f32) / 256f32;
-        return Ok(us_per_sample);
-    }
-
-    /// get lidar scan mode max distance
-    fn get_scan_mode_max_distance_with_timeout(
-        &mut self,
-        scan_mode: u16,
-        timeout: Duration,
-    ) -> Result<f32> {
-        let mut param = [0; 2];
-        LittleEndian::write_u16(&mut param, scan_mode);
-        let max_distance_data = self.get_lidar_conf_with_param_and_timeout(
-            RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE,
-            &param,
-            timeout,
-        )?;
-        let max_distance = (parse_resp_data!(max_distance_data, u32)? as f32) / 256f32;
-        return Ok(max_distance);
-    }
-
-    /// get scan mode answer type
-    fn get_scan_mode_ans_type_with_timeout(
-        &mut self,
-        scan_mode: u16,
-        timeout: Duration,
-    ) -> Result<u8> {
-        let mut param = [0; 2];
-        LittleEndian::write_u16(&mut param, scan_mode);
-        let ans_type_data = self.get_lidar_conf_with_param_and_timeout(
-            RPLIDAR_CONF_SCAN_MODE_ANS_TYPE,
-            &param,
-            timeout,
-        )?;
-        return parse_resp_data!(ans_type_data, u8);
-    }
-
-    /// get scan mode name
-    fn get_scan_mode_name_with_timeout(
-        &mut self,
-        scan_mode: u16,
-        timeout: Duration,
-    ) -> Result<String> {
-        let mut param = [0; 2];
-        LittleEndian::write_u16(&mut param, scan_mode);
-        let ans_type_data = self.get_lidar_conf_with_param_and_timeout(
-            RPLIDAR_CONF_SCAN_MODE_NAME,
-            &param,
-            timeout,
-        )?;
-
-        if let Ok(name) = std::str::from_utf8(&ans_type_data) {
-            return Ok(name.to_owned().trim_matches('\0').to_owned());
-        } else {
-            return Err(RposError::ProtocolError { description: "invalid scan mode name".to_owned() }.into());
-        }
-    }
-
-    /// get scan mode count
-    fn get_scan_mode_count_with_timeout(&mut self, timeout: Duration) -> Result<u16> {
-        let scan_mode_count_data =
-            self.get_lidar_conf_with_timeout(RPLIDAR_CONF_SCAN_MODE_COUNT, timeout)?;
-        return parse_resp_data!(scan_mode_count_data, u16);
-    }
-
-    /// get scan mode of specific scan mode id
-    fn get_scan_mode_with_timeout(
-        &mut self,
-        scan_mode: u16,
-        timeout: Duration,
-    ) -> Result<ScanMode> {
-        Ok(ScanMode {
-            id: scan_mode,
-            us_per_sample: self.get_scan_mode_us_per_sample_with_timeout(scan_mode, timeout)?
-                as f32,
-            max_distance: self.get_scan_mode_max_distance_with_timeout(scan_mode, timeout)?,
-            ans_type: self.get_scan_mode_ans_type_with_timeout(scan_mode, timeout)?,
-            name: self.get_scan_mode_name_with_timeout(scan_mode, timeout)?,
-        })
+        self.driver.get_typical_scan_mode(timeout_ms(timeout)).map_err(from_driver_error)
     }
 
     /// get all supported scan modes supported by the LIDAR
@@ -348,40 +186,11 @@ where
         &mut self,
         timeout: Duration,
     ) -> Result<Vec<ScanMode>> {
-        let device_info = self.get_device_info_with_timeout(timeout)?;
-
-        if device_info.firmware_version < RPLIDAR_GET_LIDAR_CONF_START_VERSION {
-            let mut output: Vec<ScanMode> = Vec::with_capacity(2);
-
-            output.push(ScanMode {
-                id: 0u16,
-                us_per_sample: 1000000f32 / 2000f32,
-                max_distance: 8000f32,
-                ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT,
-                name: "Standard".to_owned()
-            });
-
-            if device_info.model >= 0x20u8 {
-                output.push(ScanMode {
-                    id: 1u16,
-                    us_per_sample: 1000000f32 / 4000f32,
-                    max_distance: 16000f32,
-                    ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED,
-                    name: "Express".to_owned()
-                });
-            }
-
-            return Ok(output);
-        } else {
-            let scan_mode_count = self.get_scan_mode_count_with_timeout(timeout)?;
-            let mut output: Vec<ScanMode> = Vec::with_capacity(scan_mode_count as usize);
-
-            for i in 0..scan_mode_count {
-                output.push(self.get_scan_mode_with_timeout(i as u16, timeout)?);
-            }
-
-            return Ok(output);
-        }
+        let mut output = Vec::new();
+        self.driver
+            .get_all_supported_scan_modes(timeout_ms(timeout), |mode| output.push(ScanMode::from(mode)))
+            .map_err(from_driver_error)?;
+        Ok(output)
     }
 
     /// start scan
@@ -405,153 +214,28 @@ where
         options: &ScanOptions,
         timeout: Duration,
     ) -> Result<ScanMode> {
-        self.cached_prev_capsule = CachedPrevCapsule::None;
-
-        let scan_mode = match options.scan_mode {
-            Some(mode) => mode,
-            None => self.get_typical_scan_mode_with_timeout(timeout)?,
-        };
-
-        let scan_mode_info = self.get_scan_mode_with_timeout(scan_mode, timeout)?;
-
-        match scan_mode {
-            0 => self.legacy_start_scan(options.force_scan)?,
-            _ => {
-                let payload = RplidarPayloadExpressScan {
-                    work_mode: scan_mode as u8,
-                    work_flags: options.options as u16,
-                    param: 0,
-                };
-                self.start_express_scan(&payload)?;
-            }
-        }
-
-        return Ok(scan_mode_info);
-    }
-
-    /// use legacy command to start scan
-    fn legacy_start_scan(&mut self, force_scan: bool) -> Result<()> {
-        self.channel.write(&Message::new(if force_scan {
-            RPLIDAR_CMD_FORCE_SCAN
-        } else {
-            RPLIDAR_CMD_SCAN
-        }))?;
-        return Ok(());
-    }
-
-    /// start express scan with options
-    fn start_express_scan(&mut self, options: &RplidarPayloadExpressScan) -> Result<()> {
-        let data = unsafe {
-            transmute_copy::<
-                RplidarPayloadExpressScan,
-                [u8; std::mem::size_of::<RplidarPayloadExpressScan>()],
-            >(options)
-        };
-        self.channel
-            .write(&Message::with_data(RPLIDAR_CMD_EXPRESS_SCAN, &data))?;
-        return Ok(());
-    }
-
-    /// when hq measurement node received
-    fn on_measurement_node_hq(&mut self, node: RplidarResponseMeasurementNodeHq) {
-        self.cached_measurement_nodes
-            .push_back(ScanPoint::from(node));
-    }
-
-    /// when measurement node received
-    fn on_measurement_node(&mut self, node: RplidarResponseMeasurementNode) {
-        self.on_measurement_node_hq(RplidarResponseMeasurementNodeHq {
-            angle_z_q14: ((((node.angle_q6_checkbit as u32)
-                >> RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT as u32)
-                << 8)
-                / 90) as u16,
-            dist_mm_q2: node.distance_q2 as u32,
-            flag: node.sync_quality & RPLIDAR_RESP_MEASUREMENT_SYNCBIT,
-            quality: (node.sync_quality >> RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8)
-                << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT as u8,
-        });
-    }
-
-    /// when capsuled measurement msg received
-    fn on_measurement_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum(msg)?;
-        self.on_measurement_capsuled(parse_resp!(msg, RplidarResponseCapsuleMeasurementNodes)?);
-        return Ok(());
-    }
-
-    /// when capsuled measurement response received
-    fn on_measurement_capsuled(&mut self, nodes: RplidarResponseCapsuleMeasurementNodes) {
-        let (parsed_nodes, new_cached_capsuled) = parse_capsuled(&self.cached_prev_capsule, nodes);
-        self.cached_prev_capsule = new_cached_capsuled;
-
-        for node in parsed_nodes {
-            self.on_measurement_node_hq(node);
-        }
-    }
-
-    /// when ultra capsuled measurement msg received
-    fn on_measurement_ultra_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum(msg)?;
-        self.on_measurement_ultra_capsuled(parse_resp!(
-            msg,
-            RplidarResponseUltraCapsuleMeasurementNodes
-        )?);
-        return Ok(());
-    }
-
-    /// when ultra capsuled measurement response received
-    fn on_measurement_ultra_capsuled(
-        &mut self,
-        nodes: RplidarResponseUltraCapsuleMeasurementNodes,
-    ) {
-        let (parsed_nodes, new_cached_capsuled) = parse_ultra_capsuled(&self.cached_prev_capsule, nodes);
-        self.cached_prev_capsule = new_cached_capsuled;
-
-        for node in parsed_nodes {
-            self.on_measurement_node_hq(node);
-        }
-    }
-
-    /// when hq capsuled measurement msg received
-    fn on_measurement_hq_capsuled_msg(&mut self, msg: &Message) -> Result<()> {
-        check_sync_and_checksum_hq(msg)?;
-        self.on_measurement_hq_capsuled(parse_resp!(
-            msg,
-            RplidarResponseHqCapsuledMeasurementNodes
-        )?);
-        return Ok(());
-    }
-
-    /// when hq capsuled measurement response received
-    fn on_measurement_hq_capsuled(
-        &mut self,
-        nodes: RplidarResponseHqCapsuledMeasurementNodes,
-    ) {
-        for node in nodes.nodes.iter() {
-            self.on_measurement_node_hq(node.clone());
-        }
+        let scan_mode = self.driver
+            .start_scan_with_options(options, timeout_ms(timeout))
+            .map_err(from_driver_error)?;
+        Ok(ScanMode::from(scan_mode))
     }
 
     /// wait for next section of scan data
+    ///
+    /// The answer is decoded in the buffer of the core driver, straight into the point cache.
     fn wait_scan_data_with_timeout(&mut self, timeout: Duration) -> Result<()> {
-        let opt_msg = self.channel.read_until(timeout)?;
+        let clock = self.clock;
+        let nodes = &mut self.cached_measurement_nodes;
+        let timestamps = &mut self.cached_measurement_timestamps;
 
-        if let Some(msg) = opt_msg {
-            match msg.cmd {
-                RPLIDAR_ANS_TYPE_MEASUREMENT => {
-                    self.on_measurement_node(parse_resp!(msg, RplidarResponseMeasurementNode)?)
-                }
-                RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED => self.on_measurement_capsuled_msg(&msg)?,
-                RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED_ULTRA => self.on_measurement_ultra_capsuled_msg(&msg)?,
-                RPLIDAR_ANS_TYPE_MEASUREMENT_HQ => self.on_measurement_hq_capsuled_msg(&msg)?,
-                _ => {
-                    return Err(RposError::ProtocolError { description: "unexpected response".to_owned() }.into());
-                }
-            }
-            return Ok(());
-        } else {
-            return Ok(());
-        }
+        self.driver
+            .grab_timed_scan_points(timeout_ms(timeout), |point, measured_us| {
+                nodes.push_back(point);
+                timestamps.push_back(clock.instant(measured_us));
+            })
+            .map_err(from_driver_error)?;
+
+        Ok(())
     }
 
     /// read scan point
@@ -569,6 +253,7 @@ where
             }
         }
 
+        self.cached_measurement_timestamps.pop_front();
         return Ok(self.cached_measurement_nodes.pop_front().unwrap());
     }
 
@@ -579,10 +264,62 @@ where
 
     /// read scan frame
     pub fn grab_scan_with_timeout(&mut self, timeout: Duration) -> Result<Vec<ScanPoint>> {
+        let mut points = Vec::new();
+        self.grab_scan_into_with_timeout(&mut points, timeout)?;
+        Ok(points)
+    }
+
+    /// read scan frame into `points`, reusing its capacity
+    pub fn grab_scan_into(&mut self, points: &mut Vec<ScanPoint>) -> Result<()> {
+        self.grab_scan_into_with_timeout(points, RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read scan frame into `points`, reusing its capacity
+    pub fn grab_scan_into_with_timeout(&mut self, points: &mut Vec<ScanPoint>, timeout: Duration) -> Result<()> {
+        let end = self.wait_scan_end(timeout)?;
+
+        points.clear();
+        points.extend(self.cached_measurement_nodes.drain(0..end));
+        self.cached_measurement_timestamps.drain(0..end);
+
+        Ok(())
+    }
+
+    /// read scan frame with timestamp of each point
+    pub fn grab_timed_scan(&mut self) -> Result<TimedScan> {
+        self.grab_timed_scan_with_timeout(RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read scan frame with timestamp of each point
+    pub fn grab_timed_scan_with_timeout(&mut self, timeout: Duration) -> Result<TimedScan> {
+        let mut out = TimedScan { points: Vec::new(), timestamps: Vec::new() };
+        self.grab_timed_scan_into_with_timeout(&mut out, timeout)?;
+        Ok(out)
+    }
+
+    /// read scan frame with timestamp of each point into `scan`, reusing its capacity
+    pub fn grab_timed_scan_into(&mut self, scan: &mut TimedScan) -> Result<()> {
+        self.grab_timed_scan_into_with_timeout(scan, RPLIDAR_DEFAULT_TIMEOUT * 5)
+    }
+
+    /// read scan frame with timestamp of each point into `scan`, reusing its capacity
+    pub fn grab_timed_scan_into_with_timeout(&mut self, scan: &mut TimedScan, timeout: Duration) -> Result<()> {
+        let end = self.wait_scan_end(timeout)?;
+
+        scan.points.clear();
+        scan.points.extend(self.cached_measurement_nodes.drain(0..end));
+        scan.timestamps.clear();
+        scan.timestamps.extend(self.cached_measurement_timestamps.drain(0..end));
+
+        Ok(())
+    }
+
+    /// wait until the next scan starts, returns the number of cached points of the current one
+    fn wait_scan_end(&mut self, timeout: Duration) -> Result<usize> {
         let deadline = Instant::now() + timeout;
         let mut end = 1;
 
-        'outer_loop: loop {
+        loop {
             if Instant::now() > deadline {
                 return Err(RposError::OperationTimeout.into());
             }
@@ -593,22 +330,12 @@ where
 
             for i in end..self.cached_measurement_nodes.len() {
                 if self.cached_measurement_nodes[i].is_sync() {
-                    end = i;
-                    break 'outer_loop;
+                    return Ok(i);
                 }
             }
 
             end = self.cached_measurement_nodes.len();
         }
-
-        let mut out = Vec::<ScanPoint>::with_capacity(end);
-        for _ in 0..end {
-            if let Some(point) = self.cached_measurement_nodes.pop_front() {
-                out.push(point);
-            }
-        }
-
-        return Ok(out);
     }
 
     /// Get LIDAR health information
@@ -621,21 +348,7 @@ where
         &mut self,
         timeout: Duration,
     ) -> Result<Health> {
-        if let Some(msg) = self
-            .channel
-            .invoke(&Message::new(RPLIDAR_CMD_GET_DEVICE_HEALTH), timeout)?
-        {
-            let resp = handle_resp!(RPLIDAR_ANS_TYPE_DEVHEALTH, msg, RplidarResponseDeviceHealth)?;
-
-            return Ok(match resp.status {
-                RPLIDAR_HEALTH_STATUS_OK => Health::Healthy,
-                RPLIDAR_HEALTH_STATUS_WARNING => Health::Warning(resp.error_code),
-                RPLIDAR_HEALTH_STATUS_ERROR => Health::Error(resp.error_code),
-                _ => Health::Healthy
-            });
-        }
-
-        return Err(RposError::OperationTimeout.into());
+        self.driver.get_device_health(timeout_ms(timeout)).map_err(from_driver_error)
     }
 
     /// Check if the connected LIDAR supports motor control
@@ -645,60 +358,339 @@ where
 
     /// Check if the connected LIDAR supports motor control with timeout
     pub fn check_motor_ctrl_support_with_timeout(&mut self, timeout: Duration) -> Result<bool> {
-        let mut data = [0u8; 4];
-        LittleEndian::write_u32(&mut data, 0u32);
-
-        let resp_msg = self.channel.invoke(&Message::with_data(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &data), timeout)?;
-
-        if let Some(msg) = resp_msg {
-            let support_flag = handle_resp!(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, msg, u32)?;
-            
-            return Ok((support_flag & RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK) == RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK);
-        } else {
-            return Err(RposError::OperationTimeout.into());
-        }
+        self.driver.check_motor_ctrl_support(timeout_ms(timeout)).map_err(from_driver_error)
     }
 }
 
-fn check_sync_and_checksum(msg: &Message) -> Result<()> {
-    if msg.data.len() < 2 {
-        return Err(RposError::ProtocolError { description: "data too short".to_owned() }.into());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rplidar_core::answers::*;
+    use rplidar_core::cmds::*;
+    use rpos_drv::MockStream;
+
+    fn command(cmd: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0xA5, cmd];
+        if !payload.is_empty() {
+            bytes.push(payload.len() as u8);
+            bytes.extend_from_slice(payload);
+            bytes.push(bytes.iter().fold(0, |checksum, byte| checksum ^ byte));
+        }
+        bytes
     }
 
-    if (msg.data[0] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 {
-        return Err(RposError::ProtocolError { description: "miss sync 1".to_owned() }.into());
+    fn answer(ans_type: u8, is_loop: bool, data: &[u8]) -> Vec<u8> {
+        let flag = if is_loop { 0x40 } else { 0 };
+        let mut bytes = vec![0xA5, 0x5A, data.len() as u8, 0, 0, flag, ans_type];
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn device_info(model: u8, firmware_version: u16) -> Vec<u8> {
+        let mut data = vec![model, firmware_version as u8, (firmware_version >> 8) as u8, 7];
+        data.extend_from_slice(&[0x42; 16]);
+        answer(RPLIDAR_ANS_TYPE_DEVINFO, false, &data)
     }
-
-    if (msg.data[1] >> 4) != RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 {
-        return Err(RposError::ProtocolError { description: "miss sync 2".to_owned() }.into());
+
+    /// LIDAR configuration query and its answer
+    fn lidar_conf(stream: MockStream, conf_type: u32, param: &[u8], value: &[u8]) -> MockStream {
+        let mut payload = conf_type.to_le_bytes().to_vec();
+        payload.extend_from_slice(param);
+        let mut data = conf_type.to_le_bytes().to_vec();
+        data.extend_from_slice(value);
+
+        stream
+            .expect(&command(RPLIDAR_CMD_GET_LIDAR_CONF, &payload))
+            .respond(&answer(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, false, &data))
+    }
+
+    fn scan_mode_conf(stream: MockStream, id: u16, us_per_sample: u32, max_distance: u32, ans_type: u8, name: &str) -> MockStream {
+        let param = id.to_le_bytes();
+        let stream = lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, &param, &(us_per_sample << 8).to_le_bytes());
+        let stream = lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, &param, &(max_distance << 8).to_le_bytes());
+        let stream = lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, &param, &[ans_type]);
+        lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_NAME, &param, format!("{}\0", name).as_bytes())
+    }
+
+    /// legacy measurement node at `angle` degrees
+    fn node(angle: u16, dist_mm: u16, sync: bool) -> Vec<u8> {
+        let sync_bits = if sync { 0b01 } else { 0b10 };
+        let angle_q6 = ((angle * 64) << 1) | 1;
+        let dist_q2 = dist_mm * 4;
+        vec![(47 << 2) | sync_bits, angle_q6 as u8, (angle_q6 >> 8) as u8, dist_q2 as u8, (dist_q2 >> 8) as u8]
+    }
+
+    fn device(stream: MockStream) -> RplidarDevice<MockStream> {
+        RplidarDevice::with_stream(Box::new(stream))
+    }
+
+    #[test]
+    fn get_device_info() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .respond_in_fragments(&device_info(0x18, 0x011D), 3);
+        let mut rplidar = device(stream);
+
+        let info = rplidar.get_device_info().unwrap();
+        assert_eq!({ info.model }, 0x18);
+        assert_eq!({ info.firmware_version }, 0x011D);
+        assert_eq!({ info.hardware_version }, 7);
+        assert_eq!({ info.serialnum }, [0x42; 16]);
+    }
+
+    #[test]
+    fn reject_damaged_answer_headers() {
+        // a damaged size byte announces a 64KB device info
+        let mut damaged = device_info(0x18, 0x011D);
+        damaged[4] = 0x01;
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .respond(&damaged[..9])
+            .respond(&device_info(0x18, 0x011D));
+        let mut rplidar = device(stream);
+
+        assert_eq!({ rplidar.get_device_info().unwrap().model }, 0x18);
+        assert_eq!(rplidar.rejected_headers(), 1);
+    }
+
+    #[test]
+    fn answer_errors() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .expect(&[0xA5, 0x52])
+            .respond(&answer(RPLIDAR_ANS_TYPE_DEVINFO, false, &[0; 20]))
+            .expect(&[0xA5, 0x52])
+            .respond(&answer(RPLIDAR_ANS_TYPE_DEVHEALTH, false, &[0; 2]));
+        let mut rplidar = device(stream);
+
+        let err = rplidar.get_device_info_with_timeout(Duration::from_millis(10)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RposError>(), Some(RposError::OperationTimeout)));
+
+        // wrong answer type, then wrong answer size
+        assert!(rplidar.get_device_health().is_err());
+        assert!(rplidar.get_device_health().is_err());
+    }
+
+    #[test]
+    fn motor_and_reset_commands() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x25])
+            .expect(&[0xA5, 0x40])
+            .expect(&command(RPLIDAR_CMD_SET_MOTOR_PWM, &[0x34, 0x12]))
+            .expect(&command(RPLIDAR_CMD_HQ_MOTOR_SPEED_CTRL, &600u16.to_le_bytes()))
+            .expect(&command(RPLIDAR_CMD_SET_MOTOR_PWM, &[0, 0]))
+            .expect(&command(RPLIDAR_CMD_SET_MOTOR_PWM, &RPLIDAR_DEFAULT_MOTOR_PWM.to_le_bytes()));
+        let mut rplidar = device(stream);
+
+        rplidar.stop().unwrap();
+        rplidar.core_reset().unwrap();
+        rplidar.set_motor_pwm(0x1234).unwrap();
+        rplidar.set_motor_speed(600).unwrap();
+        rplidar.stop_motor().unwrap();
+        rplidar.start_motor().unwrap();
+    }
+
+    #[test]
+    fn health_and_accessory_board() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x52])
+            .respond(&answer(RPLIDAR_ANS_TYPE_DEVHEALTH, false, &[0, 0, 0]))
+            .expect(&[0xA5, 0x52])
+            .respond(&answer(RPLIDAR_ANS_TYPE_DEVHEALTH, false, &[1, 0x34, 0x12]))
+            .expect(&command(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &[0; 4]))
+            .respond(&answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, false, &[1, 0, 0, 0]))
+            .expect(&command(RPLIDAR_CMD_GET_ACC_BOARD_FLAG, &[0; 4]))
+            .respond(&answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, false, &[0, 0, 0, 0]));
+        let mut rplidar = device(stream);
+
+        assert_eq!(rplidar.get_device_health().unwrap(), Health::Healthy);
+        assert_eq!(rplidar.get_device_health_with_timeout(Duration::from_millis(100)).unwrap(), Health::Warning(0x1234));
+        assert!(rplidar.check_motor_ctrl_support().unwrap());
+        assert!(!rplidar.check_motor_ctrl_support_with_timeout(Duration::from_millis(100)).unwrap());
+    }
+
+    #[test]
+    fn scan_modes_from_lidar_conf() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .respond(&device_info(0x18, 0x011D));
+        let stream = lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[], &[1, 0]);
+        let stream = stream.expect(&[0xA5, 0x50]).respond(&device_info(0x18, 0x011D));
+        let stream = lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_COUNT, &[], &[2, 0]);
+        let stream = scan_mode_conf(stream, 0, 500, 12, RPLIDAR_ANS_TYPE_MEASUREMENT, "Standard");
+        let stream = scan_mode_conf(stream, 1, 250, 12, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, "Express");
+        let mut rplidar = device(stream);
+
+        assert_eq!(rplidar.get_typical_scan_mode().unwrap(), 1);
+
+        let modes = rplidar.get_all_supported_scan_modes().unwrap();
+        assert_eq!(modes.len(), 2);
+        assert_eq!((modes[0].id, modes[0].us_per_sample, modes[0].max_distance), (0, 500f32, 12f32));
+        assert_eq!((modes[1].ans_type, modes[1].name.as_str()), (RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, "Express"));
+    }
+
+    #[test]
+    fn scan_modes_of_old_firmware() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .respond(&device_info(0x18, 0x0111))
+            .expect(&[0xA5, 0x50])
+            .respond(&device_info(0x18, 0x0111));
+        let mut rplidar = device(stream);
+
+        assert_eq!(rplidar.get_typical_scan_mode_with_timeout(Duration::from_millis(100)).unwrap(), 0);
+
+        let modes = rplidar.get_all_supported_scan_modes_with_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(modes.iter().map(|mode| mode.name.as_str()).collect::<Vec<_>>(), ["Standard"]);
+    }
+
+    #[test]
+    fn max_distance_of_legacy_scan_modes_in_meters() {
+        let stream = MockStream::new()
+            .expect(&[0xA5, 0x50])
+            .respond(&device_info(0x20, 0x0117));
+        let mut rplidar = device(stream);
+
+        // same unit as RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, see scan_modes_from_lidar_conf
+        let modes = rplidar.get_all_supported_scan_modes_with_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(modes.iter().map(|mode| (mode.name.as_str(), mode.max_distance)).collect::<Vec<_>>(), [("Standard", 8f32), ("Express", 16f32)]);
+    }
+
+    /// queries and command of starting the typical express scan
+    fn express_scan_start(stream: MockStream, typical: bool) -> MockStream {
+        let stream = if typical {
+            let stream = stream.expect(&[0xA5, 0x50]).respond(&device_info(0x18, 0x011D));
+            lidar_conf(stream, RPLIDAR_CONF_SCAN_MODE_TYPICAL, &[], &[1, 0])
+        } else {
+            stream
+        };
+        let stream = scan_mode_conf(stream, 1, 250, 12, RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED, "Express");
+        stream.expect(&command(RPLIDAR_CMD_EXPRESS_SCAN, &[1, 0, 0, 0, 0]))
     }
 
-    let recv_checksum = (msg.data[0] & 0xf) | (msg.data[1] << 4);
-    let mut checksum = Checksum::new();
-    checksum.push_slice(&msg.data[2..]);
+    #[test]
+    fn start_express_scan() {
+        let stream = express_scan_start(MockStream::new(), true);
+        let stream = express_scan_start(stream, true);
+        let stream = express_scan_start(stream, false);
+        let mut rplidar = device(stream);
 
-    if checksum.checksum() != recv_checksum {
-        return Err(RposError::ProtocolError { description: "checksum mismatch".to_owned() }.into());
-    } else {
-        return Ok(());
-    }
-}
+        assert_eq!(rplidar.start_scan().unwrap().name, "Express");
+        assert_eq!(rplidar.start_scan_with_timeout(Duration::from_millis(100)).unwrap().us_per_sample, 250f32);
 
-fn check_sync_and_checksum_hq(msg: &Message) -> Result<()> {
-    if msg.data.len() != std::mem::size_of::<RplidarResponseHqCapsuledMeasurementNodes>() {
-        return Err(RposError::ProtocolError { description: "data length mismatch".to_owned() }.into());
+        let options = ScanOptions::with_mode(1);
+        assert_eq!(rplidar.start_scan_with_options_and_timeout(&options, Duration::from_millis(100)).unwrap().id, 1);
     }
 
-    if msg.data[0] != RPLIDAR_RESP_MEASUREMENT_HQ_SYNC {
-        return Err(RposError::ProtocolError { description: "sync mismatch".to_owned() }.into());
-    }
+    #[test]
+    fn grab_legacy_scan() {
+        let stream = scan_mode_conf(MockStream::new(), 0, 500, 12, RPLIDAR_ANS_TYPE_MEASUREMENT, "Standard");
+        let mut measurements = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, true, &[0; 5]);
+        measurements.truncate(7);
+        for rotation in 0..3 {
+            for (i, angle) in [0, 90, 180, 270].iter().enumerate() {
+                measurements.extend(node(*angle, 1000 + rotation * 100 + *angle, i == 0));
+            }
+        }
+        let stream = stream
+            .expect(&[0xA5, RPLIDAR_CMD_FORCE_SCAN])
+            .respond_in_fragments(&measurements, 7);
+        let mut rplidar = device(stream);
+
+        let options = ScanOptions { scan_mode: Some(0), ..ScanOptions::force_scan() };
+        assert_eq!(rplidar.start_scan_with_options(&options).unwrap().name, "Standard");
+
+        let point = rplidar.grab_scan_point().unwrap();
+        assert!(point.is_sync());
+        assert_eq!(point.distance(), 1f32);
+        assert_eq!(rplidar.grab_scan_point_with_timeout(Duration::from_millis(100)).unwrap().distance(), 1.09f32);
+
+        // the rest of the first rotation
+        assert_eq!(rplidar.grab_scan().unwrap().len(), 2);
+
+        let scan = rplidar.grab_timed_scan().unwrap();
+        assert_eq!(scan.points.len(), 4);
+        assert_eq!(scan.timestamps.len(), 4);
+        assert_eq!(scan.points.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1.1f32, 1.19, 1.28, 1.37]);
+    }
+
+    #[test]
+    fn query_after_stop() {
+        let mut measurements = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, true, &[0; 5]);
+        measurements.truncate(7);
+        measurements.extend(node(0, 1000, true));
+        measurements.extend(&node(90, 1000, false)[..3]);
+        let stream = MockStream::new()
+            .respond(&measurements)
+            .expect(&[0xA5, RPLIDAR_CMD_STOP])
+            .expect(&[0xA5, 0x50])
+            .respond(&device_info(0x18, 0x011D));
+        let mut rplidar = device(stream);
+
+        assert!(rplidar.grab_scan_point().unwrap().is_sync());
+        rplidar.stop().unwrap();
+
+        // the device info isn't decoded as the rest of the interrupted node
+        assert_eq!({ rplidar.get_device_info().unwrap().model }, 0x18);
+    }
+
+    #[test]
+    fn drop_invalid_legacy_nodes() {
+        let mut measurements = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, true, &[0; 5]);
+        measurements.truncate(7);
+        for rotation in 0..8 {
+            for (i, angle) in [0, 90, 180, 270].iter().enumerate() {
+                let mut node = node(*angle, 1000 + rotation * 100 + *angle, i == 0);
+                match (rotation, i) {
+                    // a flipped sync bit, then a lost byte
+                    (0, 2) => node[0] ^= 0b01,
+                    (1, 1) => node.truncate(4),
+                    _ => {}
+                }
+                measurements.extend(node);
+            }
+        }
+        let mut rplidar = device(MockStream::new().respond_in_fragments(&measurements, 16));
 
-    let checksum = crc32::checksum_ieee(&msg.data[0..msg.data.len()-4]);
-    let recv_checksum = LittleEndian::read_u32(&msg.data[msg.data.len()-4..msg.data.len()]);
+        let scan = rplidar.grab_scan().unwrap();
+        assert_eq!(scan.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1f32, 1.09, 1.27]);
+        assert_eq!(rplidar.invalid_nodes(), 1);
 
-    if checksum != recv_checksum {
-        return Err(RposError::ProtocolError { description: "checksum mismatch".to_owned() }.into());
-    } else {
-        return Ok(());
+        // out of step nodes until the stream is realigned, some pass the checks by chance
+        let mut scan: Vec<ScanPoint> = Vec::new();
+        while scan.len() != 4 {
+            rplidar.grab_scan_into(&mut scan).unwrap();
+        }
+        assert_eq!(scan.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1.4f32, 1.49, 1.58, 1.67]);
+        rplidar.grab_scan_into(&mut scan).unwrap();
+        assert_eq!(scan.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1.5f32, 1.59, 1.68, 1.77]);
+        assert!(rplidar.invalid_nodes() > 3);
+    }
+
+    #[test]
+    fn grab_scan_into_buffers() {
+        let mut measurements = answer(RPLIDAR_ANS_TYPE_MEASUREMENT, true, &[0; 5]);
+        measurements.truncate(7);
+        for rotation in 0..4 {
+            for (i, angle) in [0, 90, 180, 270].iter().enumerate() {
+                measurements.extend(node(*angle, 1000 + rotation * 100 + *angle, i == 0));
+            }
+        }
+        let mut rplidar = device(MockStream::new().respond_in_fragments(&measurements, 16));
+
+        let mut points = Vec::new();
+        rplidar.grab_scan_into(&mut points).unwrap();
+        assert_eq!(points.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1f32, 1.09, 1.18, 1.27]);
+
+        // the buffer is refilled, not reallocated
+        let storage = points.as_ptr();
+        rplidar.grab_scan_into(&mut points).unwrap();
+        assert_eq!(points.as_ptr(), storage);
+        assert_eq!(points.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1.1f32, 1.19, 1.28, 1.37]);
+
+        let mut scan = TimedScan { points: Vec::with_capacity(8), timestamps: Vec::with_capacity(8) };
+        rplidar.grab_timed_scan_into(&mut scan).unwrap();
+        assert_eq!(scan.points.iter().map(|p| p.distance()).collect::<Vec<_>>(), [1.2f32, 1.29, 1.38, 1.47]);
+        assert_eq!(scan.timestamps.len(), 4);
     }
 }
@@ -0,0 +1,366 @@
+//! Record scans into MCAP files
+//!
+//! Scans are written as JSON messages following the `foxglove.LaserScan` schema,
+//! so recordings can be opened in Foxglove Studio directly. Device info, health
+//! and scan mode are stored as MCAP metadata records.
+//!
+//! The writer produces an unchunked MCAP file without summary section, which is
+//! a valid MCAP file that can be read sequentially.
+//!
+//! # Example
+//! ```ignore
+//! let file = std::io::BufWriter::new(std::fs::File::create("scans.mcap")?);
+//! let mut recorder = McapWriter::new(file, "/scan", "laser")?;
+//!
+//! recorder.write_device_info(&rplidar.get_device_info()?)?;
+//! recorder.write_health(&rplidar.get_device_health()?)?;
+//!
+//! loop {
+//!     let scan = rplidar.grab_scan()?;
+//!     recorder.write_scan(&scan, now_ns())?;
+//! }
+//! ```
+
+use rplidar_core::answers::RplidarResponseDeviceInfo;
+use super::errors::*;
+use super::laser_scan::LaserScan;
+use super::prelude::{Health, ScanMode, ScanPoint};
+use super::transform::SensorMounting;
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::Write;
+
+const MCAP_MAGIC: [u8; 8] = [0x89, b'M', b'C', b'A', b'P', b'0', b'\r', b'\n'];
+
+const MCAP_OP_HEADER: u8 = 0x01;
+const MCAP_OP_FOOTER: u8 = 0x02;
+const MCAP_OP_SCHEMA: u8 = 0x03;
+const MCAP_OP_CHANNEL: u8 = 0x04;
+const MCAP_OP_MESSAGE: u8 = 0x05;
+const MCAP_OP_METADATA: u8 = 0x0C;
+const MCAP_OP_DATA_END: u8 = 0x0F;
+
+const LASER_SCAN_SCHEMA_ID: u16 = 1;
+const LASER_SCAN_CHANNEL_ID: u16 = 1;
+
+/// JSON schema of `foxglove.LaserScan`
+const LASER_SCAN_SCHEMA: &str = r#"{
+  "title": "foxglove.LaserScan",
+  "description": "A single scan from a planar laser range-finder",
+  "type": "object",
+  "properties": {
+    "timestamp": {
+      "type": "object",
+      "properties": { "sec": { "type": "integer", "minimum": 0 }, "nsec": { "type": "integer", "minimum": 0, "maximum": 999999999 } }
+    },
+    "frame_id": { "type": "string" },
+    "pose": {
+      "type": "object",
+      "properties": {
+        "position": { "type": "object", "properties": { "x": { "type": "number" }, "y": { "type": "number" }, "z": { "type": "number" } } },
+        "orientation": { "type": "object", "properties": { "x": { "type": "number" }, "y": { "type": "number" }, "z": { "type": "number" }, "w": { "type": "number" } } }
+      }
+    },
+    "start_angle": { "type": "number" },
+    "end_angle": { "type": "number" },
+    "ranges": { "type": "array", "items": { "type": "number" } },
+    "intensities": { "type": "array", "items": { "type": "number" } }
+  }
+}"#;
+
+/// Writes scans and device metadata into an MCAP file
+#[derive(Debug)]
+pub struct McapWriter<W: Write> {
+    dest: W,
+    frame_id: String,
+    mounting: SensorMounting,
+    sequence: u32,
+}
+
+impl<W: Write> McapWriter<W> {
+    /// create a writer publishing scans on `topic` with frame id `frame_id`
+    pub fn new(dest: W, topic: &str, frame_id: &str) -> Result<McapWriter<W>> {
+        let mut writer = McapWriter {
+            dest,
+            frame_id: frame_id.to_owned(),
+            mounting: SensorMounting::identity(),
+            sequence: 0,
+        };
+
+        writer.write_bytes(&MCAP_MAGIC)?;
+
+        let mut header = Vec::new();
+        put_string(&mut header, "")?;
+        put_string(&mut header, concat!("rplidar_drv ", env!("CARGO_PKG_VERSION")))?;
+        writer.write_record(MCAP_OP_HEADER, &header)?;
+
+        let mut schema = Vec::new();
+        schema.write_u16::<LittleEndian>(LASER_SCAN_SCHEMA_ID)?;
+        put_string(&mut schema, "foxglove.LaserScan")?;
+        put_string(&mut schema, "jsonschema")?;
+        put_bytes(&mut schema, LASER_SCAN_SCHEMA.as_bytes())?;
+        writer.write_record(MCAP_OP_SCHEMA, &schema)?;
+
+        let mut channel = Vec::new();
+        channel.write_u16::<LittleEndian>(LASER_SCAN_CHANNEL_ID)?;
+        channel.write_u16::<LittleEndian>(LASER_SCAN_SCHEMA_ID)?;
+        put_string(&mut channel, topic)?;
+        put_string(&mut channel, "json")?;
+        put_map(&mut channel, &[])?;
+        writer.write_record(MCAP_OP_CHANNEL, &channel)?;
+
+        Ok(writer)
+    }
+
+    /// set the mounting of the sensor, written as pose of each scan
+    pub fn set_mounting(&mut self, mounting: SensorMounting) {
+        self.mounting = mounting;
+    }
+
+    /// write device info as metadata record named `device_info`
+    pub fn write_device_info(&mut self, info: &RplidarResponseDeviceInfo) -> Result<()> {
+        let firmware_version = info.firmware_version;
+        let serial_number: String = info.serialnum.iter().map(|b| format!("{:02X}", b)).collect();
+
+        self.write_metadata(
+            "device_info",
+            &[
+                ("model", format!("{}", info.model)),
+                ("firmware_version", format!("{}.{}", firmware_version >> 8, firmware_version & 0xff)),
+                ("hardware_version", format!("{}", info.hardware_version)),
+                ("serial_number", serial_number),
+            ],
+        )
+    }
+
+    /// write device health as metadata record named `health`
+    pub fn write_health(&mut self, health: &Health) -> Result<()> {
+        let (status, error_code) = match health {
+            Health::Healthy => ("healthy", 0u16),
+            Health::Warning(code) => ("warning", *code),
+            Health::Error(code) => ("error", *code),
+        };
+
+        self.write_metadata(
+            "health",
+            &[
+                ("status", status.to_owned()),
+                ("error_code", format!("{:04X}", error_code)),
+            ],
+        )
+    }
+
+    /// write scan mode as metadata record named `scan_mode`
+    pub fn write_scan_mode(&mut self, mode: &ScanMode) -> Result<()> {
+        self.write_metadata(
+            "scan_mode",
+            &[
+                ("id", format!("{}", mode.id)),
+                ("name", mode.name.clone()),
+                ("us_per_sample", format!("{}", mode.us_per_sample)),
+                ("max_distance", format!("{}", mode.max_distance)),
+                ("ans_type", format!("{:02X}", mode.ans_type)),
+            ],
+        )
+    }
+
+    /// write a metadata record
+    pub fn write_metadata(&mut self, name: &str, entries: &[(&str, String)]) -> Result<()> {
+        let mut record = Vec::new();
+        put_string(&mut record, name)?;
+        put_map(&mut record, entries)?;
+        self.write_record(MCAP_OP_METADATA, &record)
+    }
+
+    /// write a scan (as returned by `grab_scan`) taken at `timestamp_ns` (nanoseconds since epoch)
+    ///
+    /// RPLIDAR angles increase clockwise while `foxglove.LaserScan` angles increase
+    /// counterclockwise, so the points are reversed and their angles negated.
+    /// Points are assumed to be evenly spaced between the first and the last angle.
+    pub fn write_scan(&mut self, scan: &[ScanPoint], timestamp_ns: u64) -> Result<()> {
+        let mut sorted: Vec<&ScanPoint> = scan.iter().collect();
+        sorted.sort();
+
+        let (start_angle, end_angle) = match (sorted.first(), sorted.last()) {
+            (Some(first), Some(last)) => (-last.angle(), -first.angle()),
+            _ => (0f32, 0f32),
+        };
+
+        let ranges: Vec<f32> = sorted
+            .iter()
+            .rev()
+            .map(|p| if p.is_valid() { p.distance() } else { 0f32 })
+            .collect();
+        let intensities: Vec<f32> = sorted.iter().rev().map(|p| p.quality as f32).collect();
+
+        self.write_laser_scan(timestamp_ns, start_angle, end_angle, &ranges, &intensities)
+    }
+
+    /// write a binned laser scan taken at `timestamp_ns` (nanoseconds since epoch)
+    ///
+    /// Empty bins which are not finite are written as zero range.
+    pub fn write_binned_scan(&mut self, scan: &LaserScan, timestamp_ns: u64) -> Result<()> {
+        let ranges: Vec<f32> = scan
+            .ranges
+            .iter()
+            .rev()
+            .map(|&r| if r.is_finite() { r } else { 0f32 })
+            .collect();
+        let intensities: Vec<f32> = scan.intensities.iter().rev().cloned().collect();
+
+        self.write_laser_scan(timestamp_ns, -scan.angle_max(), -scan.angle_min, &ranges, &intensities)
+    }
+
+    /// write a `foxglove.LaserScan` message with counterclockwise angles (radians) and ranges (meters)
+    pub fn write_laser_scan(
+        &mut self,
+        timestamp_ns: u64,
+        start_angle: f32,
+        end_angle: f32,
+        ranges: &[f32],
+        intensities: &[f32],
+    ) -> Result<()> {
+        let (half_sin, half_cos) = (self.mounting.yaw / 2f64).sin_cos();
+
+        let message = serde_json::json!({
+            "timestamp": {
+                "sec": timestamp_ns / 1_000_000_000,
+                "nsec": timestamp_ns % 1_000_000_000,
+            },
+            "frame_id": self.frame_id,
+            "pose": {
+                "position": { "x": self.mounting.x, "y": self.mounting.y, "z": self.mounting.z },
+                "orientation": { "x": 0f64, "y": 0f64, "z": half_sin, "w": half_cos },
+            },
+            "start_angle": start_angle,
+            "end_angle": end_angle,
+            "ranges": ranges,
+            "intensities": intensities,
+        });
+
+        let data = serde_json::to_vec(&message)?;
+
+        let mut record = Vec::with_capacity(22 + data.len());
+        record.write_u16::<LittleEndian>(LASER_SCAN_CHANNEL_ID)?;
+        record.write_u32::<LittleEndian>(self.sequence)?;
+        record.write_u64::<LittleEndian>(timestamp_ns)?;
+        record.write_u64::<LittleEndian>(timestamp_ns)?;
+        record.extend_from_slice(&data);
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.write_record(MCAP_OP_MESSAGE, &record)
+    }
+
+    /// finish the file and return the destination
+    pub fn finish(mut self) -> Result<W> {
+        // zero data section crc means the crc is not available
+        let mut data_end = Vec::new();
+        data_end.write_u32::<LittleEndian>(0)?;
+        self.write_record(MCAP_OP_DATA_END, &data_end)?;
+
+        let mut footer = Vec::new();
+        footer.write_u64::<LittleEndian>(0)?;
+        footer.write_u64::<LittleEndian>(0)?;
+        footer.write_u32::<LittleEndian>(0)?;
+        self.write_record(MCAP_OP_FOOTER, &footer)?;
+
+        self.write_bytes(&MCAP_MAGIC)?;
+        self.dest.flush()?;
+
+        Ok(self.dest)
+    }
+
+    fn write_record(&mut self, opcode: u8, content: &[u8]) -> Result<()> {
+        let mut prefix = [0u8; 9];
+        prefix[0] = opcode;
+        (&mut prefix[1..]).write_u64::<LittleEndian>(content.len() as u64)?;
+
+        self.write_bytes(&prefix)?;
+        self.write_bytes(content)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.dest.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+fn put_string(dest: &mut Vec<u8>, s: &str) -> Result<()> {
+    put_bytes(dest, s.as_bytes())
+}
+
+fn put_bytes(dest: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+    dest.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    dest.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn put_map(dest: &mut Vec<u8>, entries: &[(&str, String)]) -> Result<()> {
+    let mut content = Vec::new();
+    for (key, value) in entries {
+        put_string(&mut content, key)?;
+        put_string(&mut content, value)?;
+    }
+    put_bytes(dest, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    /// split a MCAP file into (opcode, content) records
+    fn records(data: &[u8]) -> Vec<(u8, &[u8])> {
+        assert_eq!(&data[0..8], &MCAP_MAGIC);
+        assert_eq!(&data[data.len() - 8..], &MCAP_MAGIC);
+
+        let mut out = Vec::new();
+        let mut i = 8;
+        while i < data.len() - 8 {
+            let len = LittleEndian::read_u64(&data[i + 1..i + 9]) as usize;
+            out.push((data[i], &data[i + 9..i + 9 + len]));
+            i += 9 + len;
+        }
+        assert_eq!(i, data.len() - 8);
+
+        out
+    }
+
+    #[test]
+    fn write_scans() {
+        let mut writer = McapWriter::new(Vec::new(), "/scan", "laser").unwrap();
+        writer.write_health(&Health::Warning(3)).unwrap();
+
+        let scan = vec![
+            ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 10, flag: 0 },
+            ScanPoint { angle_z_q14: 0, dist_mm_q2: 8000, quality: 20, flag: 1 },
+        ];
+        writer.write_scan(&scan, 1_500_000_000).unwrap();
+
+        let data = writer.finish().unwrap();
+        let ops: Vec<u8> = records(&data).iter().map(|r| r.0).collect();
+
+        assert_eq!(
+            ops,
+            vec![
+                MCAP_OP_HEADER,
+                MCAP_OP_SCHEMA,
+                MCAP_OP_CHANNEL,
+                MCAP_OP_METADATA,
+                MCAP_OP_MESSAGE,
+                MCAP_OP_DATA_END,
+                MCAP_OP_FOOTER
+            ]
+        );
+
+        let message = records(&data)[4].1;
+        assert_eq!(LittleEndian::read_u64(&message[6..14]), 1_500_000_000);
+
+        let json: serde_json::Value = serde_json::from_slice(&message[22..]).unwrap();
+        assert_eq!(json["timestamp"]["sec"], 1);
+        assert_eq!(json["timestamp"]["nsec"], 500_000_000);
+        assert_eq!(json["ranges"], serde_json::json!([1f32, 2f32]));
+        assert_eq!(json["intensities"], serde_json::json!([10f32, 20f32]));
+        assert!((json["start_angle"].as_f64().unwrap() + std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(json["end_angle"], 0f64);
+    }
+}
@@ -0,0 +1,567 @@
+//! Acquire several LIDARs concurrently and merge their scans in the robot frame
+//!
+//! Each LIDAR is driven by its own thread. The manager collects the latest scan
+//! of every LIDAR, waits until they are close enough in time and merges them
+//! into a single 360 degree point set in the robot frame. Scans not collected
+//! before the next one of the same LIDAR are dropped, so a slow consumer gets
+//! the newest scans and memory doesn't grow.
+//!
+//! # Example
+//! ```ignore
+//! let mut lidars = MultiLidar::start(vec![
+//!     LidarConfig::new("front", Box::new(front_port), SensorMounting::new(0.3f64, 0f64, 0.2f64, 0f64)),
+//!     LidarConfig::new("rear", Box::new(rear_port), SensorMounting::new(-0.3f64, 0f64, 0.2f64, PI)),
+//! ])?;
+//!
+//! loop {
+//!     let merged = lidars.grab_merged_scan()?;
+//!     let laser_scan = merged.to_laser_scan(&LaserScanBuilder::new().bin_count(720));
+//! }
+//! ```
+
+use super::errors::*;
+use super::laser_scan::{LaserScan, LaserScanBuilder};
+use super::prelude::{ScanOptions, ScanPoint, TimedScan};
+use super::transform::{Point2D, SensorMounting};
+use super::RplidarDevice;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Default max time difference between scans merged together
+pub const DEFAULT_MAX_TIME_OFFSET: Duration = Duration::from_millis(100);
+
+/// Byte streams used to talk to the LIDARs
+pub trait LidarStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> LidarStream for T {}
+
+/// Configuration of one LIDAR
+pub struct LidarConfig {
+    /// Name of the LIDAR, used in error messages
+    pub name: String,
+
+    /// Stream connected to the LIDAR
+    pub stream: Box<dyn LidarStream>,
+
+    /// Scan options
+    pub options: ScanOptions,
+
+    /// Mounting on the robot
+    pub mounting: SensorMounting,
+
+    /// Start the motor (via accessory board) before scanning
+    pub start_motor: bool,
+}
+
+impl LidarConfig {
+    /// LIDAR in typical scan mode with motor started by the driver
+    pub fn new(name: &str, stream: Box<dyn LidarStream>, mounting: SensorMounting) -> LidarConfig {
+        LidarConfig {
+            name: name.to_owned(),
+            stream,
+            options: ScanOptions::default(),
+            mounting,
+            start_motor: true,
+        }
+    }
+
+    /// use specific scan options
+    pub fn with_options(mut self, options: ScanOptions) -> LidarConfig {
+        self.options = options;
+        self
+    }
+
+    /// set if the motor is started by the driver
+    pub fn with_start_motor(mut self, start_motor: bool) -> LidarConfig {
+        self.start_motor = start_motor;
+        self
+    }
+}
+
+/// A point of a merged scan
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergedPoint {
+    /// Position in robot frame (meters)
+    pub position: Point2D<f64>,
+
+    /// Index of the LIDAR which measured the point
+    pub source: usize,
+
+    /// Quality of the measurement
+    pub quality: u8,
+
+    /// Time of the measurement
+    pub timestamp: Instant,
+}
+
+/// Points from all LIDARs in the robot frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedScan {
+    /// Time of the newest point
+    pub timestamp: Instant,
+
+    /// Merged points
+    pub points: Vec<MergedPoint>,
+}
+
+impl MergedScan {
+    /// merge scans of several LIDARs, skipping invalid points
+    pub fn merge(scans: &[(&SensorMounting, &TimedScan)]) -> MergedScan {
+        let capacity = scans.iter().map(|(_, scan)| scan.points.len()).sum();
+        let mut points = Vec::with_capacity(capacity);
+        let mut timestamp = None;
+
+        for (source, (mounting, scan)) in scans.iter().enumerate() {
+            for (point, &time) in scan.points.iter().zip(scan.timestamps.iter()) {
+                if !point.is_valid() {
+                    continue;
+                }
+
+                points.push(MergedPoint {
+                    position: mounting.to_robot_frame(point),
+                    source,
+                    quality: point.quality,
+                    timestamp: time,
+                });
+            }
+
+            if let Some(end) = scan.end_time() {
+                timestamp = Some(timestamp.map_or(end, |t: Instant| std::cmp::max(t, end)));
+            }
+        }
+
+        MergedScan {
+            timestamp: timestamp.unwrap_or_else(Instant::now),
+            points,
+        }
+    }
+
+    /// convert into scan points as seen from the robot origin (clockwise angles, robot x axis as zero)
+    pub fn to_scan_points(&self) -> Vec<ScanPoint> {
+        let origin = SensorMounting::identity();
+
+        self.points
+            .iter()
+            .map(|p| {
+                let (angle, distance) = origin.to_sensor_polar(p.position.x, p.position.y);
+                let mut point = ScanPoint {
+                    angle_z_q14: 0,
+                    dist_mm_q2: 0,
+                    quality: p.quality,
+                    flag: 0,
+                };
+                point.set_angle(angle as f32);
+                point.set_distance(distance as f32);
+                point
+            })
+            .collect()
+    }
+
+    /// bin the merged points around the robot origin
+    pub fn to_laser_scan(&self, builder: &LaserScanBuilder) -> LaserScan {
+        builder.build(&self.to_scan_points())
+    }
+}
+
+/// What a LIDAR thread leaves for the manager
+enum Published {
+    Scan(TimedScan),
+
+    /// the thread goes on scanning
+    Error(Error),
+
+    /// the thread exited, e.g. the LIDAR didn't start, reported by every grab
+    Exited(Error),
+}
+
+/// Newest scan or error of a LIDAR thread, not collected yet
+type Slot = Arc<Mutex<Option<Published>>>;
+
+struct LidarHandle {
+    name: String,
+    mounting: SensorMounting,
+    slot: Slot,
+    latest: Option<TimedScan>,
+    fresh: bool,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Drives several LIDARs, each in its own thread
+pub struct MultiLidar {
+    lidars: Vec<LidarHandle>,
+    /// indexes of LIDARs which filled their slot
+    events: Receiver<usize>,
+    stop: Arc<AtomicBool>,
+    max_time_offset: Duration,
+}
+
+impl MultiLidar {
+    /// start scanning on all LIDARs, fails if a thread can't be spawned
+    pub fn start(configs: Vec<LidarConfig>) -> Result<MultiLidar> {
+        // notifications only, the scans wait in the slots
+        let (sender, events) = sync_channel(configs.len().max(1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut multi_lidar = MultiLidar {
+            lidars: Vec::with_capacity(configs.len()),
+            events,
+            stop,
+            max_time_offset: DEFAULT_MAX_TIME_OFFSET,
+        };
+
+        for (index, config) in configs.into_iter().enumerate() {
+            let sender = sender.clone();
+            let stop = multi_lidar.stop.clone();
+            let slot = Slot::default();
+            let thread_slot = slot.clone();
+            let LidarConfig {
+                name,
+                stream,
+                options,
+                mounting,
+                start_motor,
+            } = config;
+
+            let thread = std::thread::Builder::new()
+                .name(format!("rplidar-{}", name))
+                .spawn(move || {
+                    let mut device = RplidarDevice::with_stream(stream);
+
+                    if let Err(err) = start_device(&mut device, &options, start_motor) {
+                        publish(&thread_slot, &sender, index, Published::Exited(err));
+                        return;
+                    }
+
+                    while !stop.load(Ordering::Relaxed) {
+                        let published = match device.grab_timed_scan() {
+                            Ok(scan) => Published::Scan(scan),
+                            Err(err) => match err.downcast_ref::<RposError>() {
+                                Some(RposError::OperationTimeout) => continue,
+                                _ => Published::Error(err),
+                            },
+                        };
+
+                        if !publish(&thread_slot, &sender, index, published) {
+                            break;
+                        }
+                    }
+
+                    let _ = device.stop();
+                    if start_motor {
+                        let _ = device.stop_motor();
+                    }
+                });
+
+            // dropping `multi_lidar` stops the threads spawned so far
+            let thread = thread.map_err(|err| RposError::OperationFail {
+                description: format!("{}: can't spawn thread: {}", name, err),
+            })?;
+
+            multi_lidar.lidars.push(LidarHandle {
+                name,
+                mounting,
+                slot,
+                latest: None,
+                fresh: false,
+                thread: Some(thread),
+            });
+        }
+
+        Ok(multi_lidar)
+    }
+
+    /// set the max time difference between the newest points of scans merged together
+    pub fn set_max_time_offset(&mut self, max_time_offset: Duration) {
+        self.max_time_offset = max_time_offset;
+    }
+
+    /// names of the LIDARs, in the order of `MergedPoint::source`
+    pub fn names(&self) -> Vec<&str> {
+        self.lidars.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    /// latest scan of a LIDAR
+    pub fn latest_scan(&self, index: usize) -> Option<&TimedScan> {
+        self.lidars.get(index).and_then(|l| l.latest.as_ref())
+    }
+
+    /// wait for a new set of time aligned scans and merge them
+    pub fn grab_merged_scan(&mut self) -> Result<MergedScan> {
+        self.grab_merged_scan_with_timeout(Duration::from_secs(5))
+    }
+
+    /// wait for a new set of time aligned scans and merge them, with timeout
+    pub fn grab_merged_scan_with_timeout(&mut self, timeout: Duration) -> Result<MergedScan> {
+        let deadline = Instant::now() + timeout;
+
+        // LIDARs whose thread exited don't notify any more
+        self.collect()?;
+
+        while !self.is_aligned() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RposError::OperationTimeout.into());
+            }
+
+            match self.events.recv_timeout(deadline - now) {
+                Ok(_) => self.collect()?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(RposError::OperationTimeout.into());
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(RposError::OperationFail {
+                        description: "all LIDAR threads exited".to_owned(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        for lidar in self.lidars.iter_mut() {
+            lidar.fresh = false;
+        }
+
+        let scans: Vec<(&SensorMounting, &TimedScan)> = self
+            .lidars
+            .iter()
+            .filter_map(|l| l.latest.as_ref().map(|scan| (&l.mounting, scan)))
+            .collect();
+
+        Ok(MergedScan::merge(&scans))
+    }
+
+    /// take the scans and errors waiting in the slots of all LIDARs
+    ///
+    /// Notifications may be dropped while the queue is full, so every slot is checked.
+    fn collect(&mut self) -> Result<()> {
+        for lidar in self.lidars.iter_mut() {
+            let mut slot = match lidar.slot.lock() {
+                Ok(slot) => slot,
+                Err(_) => continue,
+            };
+
+            match slot.take() {
+                Some(Published::Scan(scan)) => {
+                    lidar.latest = Some(scan);
+                    lidar.fresh = true;
+                }
+                Some(Published::Error(err)) => return Err(lidar_failure(&lidar.name, &err)),
+                Some(Published::Exited(err)) => {
+                    let failure = lidar_failure(&lidar.name, &err);
+                    *slot = Some(Published::Exited(err));
+                    return Err(failure);
+                }
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// all LIDARs have a new scan and the scans are close enough in time
+    fn is_aligned(&self) -> bool {
+        if self.lidars.is_empty() || self.lidars.iter().any(|l| !l.fresh) {
+            return false;
+        }
+
+        let ends: Vec<Instant> = self
+            .lidars
+            .iter()
+            .filter_map(|l| l.latest.as_ref().and_then(|s| s.end_time()))
+            .collect();
+
+        match (ends.iter().min(), ends.iter().max()) {
+            (Some(&min), Some(&max)) => max - min <= self.max_time_offset,
+            // empty scans have no time, don't block on them
+            _ => true,
+        }
+    }
+}
+
+impl Drop for MultiLidar {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        for lidar in self.lidars.iter_mut() {
+            if let Some(thread) = lidar.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn lidar_failure(name: &str, err: &Error) -> Error {
+    RposError::OperationFail { description: format!("{}: {}", name, err) }.into()
+}
+
+/// put a scan or error into the slot of a LIDAR, replacing an uncollected scan, and notify the manager
+///
+/// Returns false once the manager is gone.
+fn publish(slot: &Slot, sender: &SyncSender<usize>, index: usize, published: Published) -> bool {
+    if let Ok(mut slot) = slot.lock() {
+        // an uncollected error is kept, it explains why the scans stopped
+        if !matches!(*slot, Some(Published::Error(_)) | Some(Published::Exited(_))) {
+            *slot = Some(published);
+        }
+    }
+
+    !matches!(sender.try_send(index), Err(TrySendError::Disconnected(_)))
+}
+
+fn start_device(device: &mut RplidarDevice<dyn LidarStream>, options: &ScanOptions, start_motor: bool) -> Result<()> {
+    if start_motor {
+        device.start_motor()?;
+    }
+
+    device.start_scan_with_options(options)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{SimulatedLidar, SimulatorConfig};
+
+    fn point(degree: f32, range: f32) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: 10,
+            flag: 0,
+        };
+        p.set_angle(degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    fn lidar(end_time: Instant, fresh: bool) -> LidarHandle {
+        LidarHandle {
+            name: String::from("lidar"),
+            mounting: SensorMounting::new(0f64, 0f64, 0f64, 0f64),
+            slot: Slot::default(),
+            latest: Some(TimedScan {
+                points: vec![point(0f32, 1f32)],
+                timestamps: vec![end_time],
+            }),
+            fresh,
+            thread: None,
+        }
+    }
+
+    #[test]
+    fn alignment() {
+        let now = Instant::now();
+        let (_, events) = sync_channel(1);
+        let mut multi_lidar = MultiLidar {
+            lidars: vec![lidar(now, true), lidar(now + Duration::from_millis(30), true)],
+            events,
+            stop: Arc::new(AtomicBool::new(false)),
+            max_time_offset: Duration::from_millis(50),
+        };
+        assert!(multi_lidar.is_aligned());
+
+        multi_lidar.lidars[1].fresh = false;
+        assert!(!multi_lidar.is_aligned());
+
+        multi_lidar.lidars[1] = lidar(now + Duration::from_millis(80), true);
+        assert!(!multi_lidar.is_aligned());
+
+        multi_lidar.set_max_time_offset(Duration::from_millis(100));
+        assert!(multi_lidar.is_aligned());
+    }
+
+    /// serial port of an unplugged LIDAR
+    struct Unplugged;
+
+    impl Read for Unplugged {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    impl Write for Unplugged {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn report_lidar_failing_to_start() {
+        let mounting = SensorMounting::new(0f64, 0f64, 0f64, 0f64);
+        let mut multi_lidar = MultiLidar::start(vec![
+            LidarConfig::new("front", Box::new(SimulatedLidar::new(SimulatorConfig::default()).unwrap()), mounting),
+            LidarConfig::new("rear", Box::new(Unplugged), mounting),
+        ])
+        .unwrap();
+
+        // the LIDAR stays reported after its thread exited
+        for _ in 0..3 {
+            let err = multi_lidar.grab_merged_scan_with_timeout(Duration::from_secs(2)).unwrap_err();
+            match err.downcast_ref::<RposError>() {
+                Some(RposError::OperationFail { description }) => assert!(description.starts_with("rear: ")),
+                _ => panic!("unexpected error: {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn grab_from_concurrent_lidars() {
+        let config = |name: &str, yaw: f64| {
+            LidarConfig::new(
+                name,
+                Box::new(SimulatedLidar::new(SimulatorConfig::default()).unwrap()),
+                SensorMounting::new(0f64, 0f64, 0f64, yaw),
+            )
+        };
+
+        let mut multi_lidar =
+            MultiLidar::start(vec![config("front", 0f64), config("rear", std::f64::consts::PI)]).unwrap();
+        assert_eq!(multi_lidar.names(), vec!["front", "rear"]);
+
+        for _ in 0..3 {
+            let merged = multi_lidar.grab_merged_scan_with_timeout(Duration::from_secs(5)).unwrap();
+            assert!(merged.points.iter().any(|p| p.source == 0));
+            assert!(merged.points.iter().any(|p| p.source == 1));
+
+            let front = multi_lidar.latest_scan(0).and_then(|s| s.end_time()).unwrap();
+            let rear = multi_lidar.latest_scan(1).and_then(|s| s.end_time()).unwrap();
+            let offset = if front > rear { front - rear } else { rear - front };
+            assert!(offset <= DEFAULT_MAX_TIME_OFFSET);
+        }
+    }
+
+    #[test]
+    fn merge_in_robot_frame() {
+        let now = Instant::now();
+        let front = SensorMounting::new(0.5f64, 0f64, 0f64, 0f64);
+        let rear = SensorMounting::new(-0.5f64, 0f64, 0f64, std::f64::consts::PI);
+
+        let front_scan = TimedScan {
+            points: vec![point(0f32, 1f32), point(90f32, 0f32)],
+            timestamps: vec![now, now],
+        };
+        let rear_scan = TimedScan {
+            points: vec![point(0f32, 2f32)],
+            timestamps: vec![now + Duration::from_millis(10)],
+        };
+
+        let merged = MergedScan::merge(&[(&front, &front_scan), (&rear, &rear_scan)]);
+
+        assert_eq!(merged.points.len(), 2);
+        assert_eq!(merged.timestamp, now + Duration::from_millis(10));
+        assert!((merged.points[0].position.x - 1.5f64).abs() < 1e-3);
+        assert!((merged.points[1].position.x + 2.5f64).abs() < 1e-3);
+        assert_eq!(merged.points[1].source, 1);
+
+        let scan = merged.to_laser_scan(&LaserScanBuilder::new().bin_count(4));
+        assert!((scan.ranges[0] - 1.5f32).abs() < 1e-3);
+        assert!((scan.ranges[2] - 2.5f32).abs() < 1e-3);
+    }
+}
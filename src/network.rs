@@ -0,0 +1,170 @@
+//! Network LIDARs
+//!
+//! `NetworkConfig` describes how to reach a network LIDAR (e.g. S and T series), over
+//! UDP or TCP, and connects a `NetworkTransport` for `RplidarDevice`.
+//!
+//! # Example
+//! ```ignore
+//! let config = NetworkConfig::parse("udp://192.168.11.2:8089")?;
+//! let mut rplidar = RplidarDevice::with_stream(Box::new(config.connect()?));
+//! ```
+
+use super::errors::*;
+use super::tcp::TcpTransport;
+use super::udp::UdpTransport;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+/// Default UDP port of network LIDARs
+pub const RPLIDAR_DEFAULT_UDP_PORT: u16 = 8089;
+
+/// Default TCP port of network LIDARs and serial-to-TCP adapters
+pub const RPLIDAR_DEFAULT_TCP_PORT: u16 = 20108;
+
+/// Default timeout of TCP connects
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Transport protocol of a network LIDAR
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkProtocol {
+    Udp,
+    Tcp,
+}
+
+/// How to reach a network LIDAR
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    /// UDP or TCP
+    pub protocol: NetworkProtocol,
+
+    /// Address of the LIDAR
+    pub device_addr: SocketAddr,
+
+    /// Local address to bind UDP sockets to, any local port by default
+    pub local_addr: Option<SocketAddr>,
+
+    /// Timeout of TCP connects
+    pub connect_timeout: Duration,
+}
+
+impl NetworkConfig {
+    /// LIDAR at `device_addr` over UDP
+    pub fn udp<A: ToSocketAddrs>(device_addr: A) -> Result<NetworkConfig> {
+        NetworkConfig::new(NetworkProtocol::Udp, device_addr)
+    }
+
+    /// LIDAR at `device_addr` over TCP
+    pub fn tcp<A: ToSocketAddrs>(device_addr: A) -> Result<NetworkConfig> {
+        NetworkConfig::new(NetworkProtocol::Tcp, device_addr)
+    }
+
+    fn new<A: ToSocketAddrs>(protocol: NetworkProtocol, device_addr: A) -> Result<NetworkConfig> {
+        let device_addr = match device_addr.to_socket_addrs()?.next() {
+            Some(addr) => addr,
+            None => return Err(RposError::OperationFail { description: "no device address".to_owned() }.into()),
+        };
+
+        Ok(NetworkConfig { protocol, device_addr, local_addr: None, connect_timeout: DEFAULT_CONNECT_TIMEOUT })
+    }
+
+    /// parse `udp://host[:port]` or `tcp://host[:port]`, with the default port if omitted
+    pub fn parse(url: &str) -> Result<NetworkConfig> {
+        let (protocol, address, default_port) = if let Some(address) = url.strip_prefix("udp://") {
+            (NetworkProtocol::Udp, address, RPLIDAR_DEFAULT_UDP_PORT)
+        } else if let Some(address) = url.strip_prefix("tcp://") {
+            (NetworkProtocol::Tcp, address, RPLIDAR_DEFAULT_TCP_PORT)
+        } else {
+            return Err(RposError::OperationFail { description: format!("not a udp:// or tcp:// address: {}", url) }.into());
+        };
+
+        // IPv6 addresses with port are bracketed, e.g. [fe80::1]:8089
+        let has_port = match address.rfind(']') {
+            Some(bracket) => address[bracket..].contains(':'),
+            None => address.contains(':') && address.matches(':').count() == 1,
+        };
+
+        if has_port {
+            NetworkConfig::new(protocol, address)
+        } else {
+            NetworkConfig::new(protocol, (address.trim_start_matches('[').trim_end_matches(']'), default_port))
+        }
+    }
+
+    /// bind UDP sockets to `local_addr`, e.g. to choose the interface
+    pub fn with_local_addr(mut self, local_addr: SocketAddr) -> NetworkConfig {
+        self.local_addr = Some(local_addr);
+        self
+    }
+
+    /// timeout of TCP connects
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> NetworkConfig {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// connect the transport
+    pub fn connect(&self) -> Result<NetworkTransport> {
+        Ok(match self.protocol {
+            NetworkProtocol::Udp => NetworkTransport::Udp(match self.local_addr {
+                Some(local_addr) => UdpTransport::bind(local_addr, self.device_addr)?,
+                None => UdpTransport::connect(self.device_addr)?,
+            }),
+            NetworkProtocol::Tcp => {
+                NetworkTransport::Tcp(TcpTransport::connect_timeout(self.device_addr, self.connect_timeout)?)
+            }
+        })
+    }
+}
+
+/// UDP or TCP transport to a network LIDAR
+#[derive(Debug)]
+pub enum NetworkTransport {
+    Udp(UdpTransport),
+    Tcp(TcpTransport),
+}
+
+impl Read for NetworkTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            NetworkTransport::Udp(transport) => transport.read(buf),
+            NetworkTransport::Tcp(transport) => transport.read(buf),
+        }
+    }
+}
+
+impl Write for NetworkTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            NetworkTransport::Udp(transport) => transport.write(buf),
+            NetworkTransport::Tcp(transport) => transport.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            NetworkTransport::Udp(transport) => transport.flush(),
+            NetworkTransport::Tcp(transport) => transport.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_network_config() {
+        let config = NetworkConfig::parse("udp://192.168.11.2").unwrap();
+        assert_eq!(config.protocol, NetworkProtocol::Udp);
+        assert_eq!(config.device_addr, "192.168.11.2:8089".parse().unwrap());
+
+        let config = NetworkConfig::parse("tcp://192.168.0.7:1234").unwrap();
+        assert_eq!(config.protocol, NetworkProtocol::Tcp);
+        assert_eq!(config.device_addr, "192.168.0.7:1234".parse().unwrap());
+
+        assert_eq!(NetworkConfig::parse("udp://[::1]").unwrap().device_addr, "[::1]:8089".parse().unwrap());
+        assert_eq!(NetworkConfig::parse("udp://[::1]:9000").unwrap().device_addr, "[::1]:9000".parse().unwrap());
+        assert!(NetworkConfig::parse("/dev/ttyUSB0").is_err());
+    }
+}
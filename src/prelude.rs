@@ -1,65 +1,34 @@
-use std::f32::consts::PI;
-use super::answers::RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
-use std::cmp::Ordering;
-
-/// Scan point in a particular laser scan
-#[derive(Debug, Clone, Eq)]
-pub struct ScanPoint {
-    pub angle_z_q14: u16,
-    pub dist_mm_q2: u32,
-    pub quality: u8,
-    pub flag: u8,
-}
-
-impl ScanPoint {
-    pub fn angle(&self) -> f32 {
-        return (self.angle_z_q14 as f32) / 16384f32 / 2f32 * PI;
-    }
-
-    pub fn set_angle(&mut self, angle:f32) {
-        self.angle_z_q14 = (angle * 16384f32 * 2f32 / PI) as u16;
-    }
-
-    pub fn distance(&self) -> f32 {
-        return (self.dist_mm_q2 as f32) / 4000f32;
-    }
+pub use rplidar_core::{Health, ScanOptions, ScanPoint};
+use rplidar_core::ScanModeInfo;
+use std::time::Instant;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-    pub fn set_distance(&mut self, dist: f32) {
-        self.dist_mm_q2 = (dist * 4000f32) as u32;
-    }
-
-    pub fn is_sync(&self) -> bool {
-        return (self.flag & RPLIDAR_RESP_HQ_FLAG_SYNCBIT) == RPLIDAR_RESP_HQ_FLAG_SYNCBIT;
-    }
-
-    pub fn is_valid(&self) -> bool {
-        return self.quality != 0 && self.dist_mm_q2 != 0;
-    }
-}
+/// Scan with the time each point was measured
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedScan {
+    /// Points of the scan
+    pub points: Vec<ScanPoint>,
 
-impl Ord for ScanPoint {
-    fn cmp(&self, other: &ScanPoint) -> Ordering {
-        self.angle_z_q14.cmp(&other.angle_z_q14)
-    }
+    /// Estimated measurement time of each point
+    pub timestamps: Vec<Instant>,
 }
 
-impl PartialOrd for ScanPoint {
-    fn partial_cmp(&self, other: &ScanPoint) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl TimedScan {
+    /// time of the first point
+    pub fn start_time(&self) -> Option<Instant> {
+        self.timestamps.first().cloned()
     }
-}
 
-impl PartialEq for ScanPoint {
-    fn eq(&self, other: &ScanPoint) -> bool {
-        self.angle_z_q14 == other.angle_z_q14
-        && self.dist_mm_q2 == other.dist_mm_q2
-        && self.quality == other.quality
-        && self.flag == other.flag
+    /// time of the last point
+    pub fn end_time(&self) -> Option<Instant> {
+        self.timestamps.last().cloned()
     }
 }
 
 /// Description of a specific scan mode
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScanMode {
     /// The scan mode id
     pub id: u16,
@@ -67,7 +36,7 @@ pub struct ScanMode {
     /// Microseconds per measurement sample
     pub us_per_sample: f32,
 
-    /// Max distance of this measurement mode
+    /// Max distance of this measurement mode (meters)
     pub max_distance: f32,
 
     /// The answer command value of this scan mode (mainly used to decode messages)
@@ -77,61 +46,59 @@ pub struct ScanMode {
     pub name: String,
 }
 
-/// Scan options
-#[derive(Debug, Clone, PartialEq)]
-pub struct ScanOptions {
-    /// Specify this field to force use specific scan mode
-    pub scan_mode: Option<u16>,
+impl From<ScanModeInfo> for ScanMode {
+    fn from(info: ScanModeInfo) -> ScanMode {
+        ScanMode {
+            id: info.id,
+            us_per_sample: info.us_per_sample,
+            max_distance: info.max_distance,
+            ans_type: info.ans_type,
+            name: info.name().to_owned(),
+        }
+    }
+}
 
-    /// Make LIDAR scan regardless of it's spinning or not
-    pub force_scan: bool,
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use rplidar_core::answers::RplidarResponseDeviceInfo;
+    use rpos_drv::Message;
 
-    /// Parameters sent to LIDAR. Please use 0 for now
-    pub options: u32,
-}
+    #[test]
+    fn serde_round_trip() {
+        let point = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 47, flag: 1 };
+        let json = serde_json::to_string(&point).unwrap();
+        assert_eq!(serde_json::from_str::<ScanPoint>(&json).unwrap(), point);
+        assert_eq!(bincode::deserialize::<ScanPoint>(&bincode::serialize(&point).unwrap()).unwrap(), point);
 
-impl ScanOptions {
-    /// default options
-    pub fn default() -> ScanOptions {
-        ScanOptions {
-            scan_mode: None,
-            force_scan: false,
-            options: 0,
-        }
-    }
+        let health = Health::Warning(3);
+        assert_eq!(serde_json::from_str::<Health>(&serde_json::to_string(&health).unwrap()).unwrap(), health);
 
-    /// with specific mode
-    pub fn with_mode(scan_mode: u16) -> ScanOptions {
-        ScanOptions {
-            scan_mode: Some(scan_mode),
-            force_scan: false,
-            options: 0,
-        }
+        let msg = Message::with_data(0x82, &[1, 2, 3]);
+        assert_eq!(bincode::deserialize::<Message>(&bincode::serialize(&msg).unwrap()).unwrap(), msg);
     }
 
-    /// force scan
-    pub fn force_scan() -> ScanOptions {
-        ScanOptions {
-            scan_mode: None,
-            force_scan: true,
-            options: 0,
-        }
-    }
+    #[test]
+    fn serde_scan_options_defaults() {
+        let options: ScanOptions = serde_json::from_str(r#"{ "scan_mode": 3 }"#).unwrap();
+        assert_eq!(options, ScanOptions::with_mode(3));
 
-    /// force scan with mode
-    pub fn force_scan_with_mode(scan_mode: u16) -> ScanOptions {
-        ScanOptions {
-            scan_mode: Some(scan_mode),
-            force_scan: true,
-            options: 0,
-        }
+        let options: ScanOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, ScanOptions::default());
     }
-}
 
-/// Health status of device
-#[derive(Debug, Clone, PartialEq)]
-pub enum Health {
-    Healthy,
-    Warning(u16),
-    Error(u16)
+    #[test]
+    fn serde_packed_device_info() {
+        let info = RplidarResponseDeviceInfo {
+            model: 0x18,
+            firmware_version: 0x0118,
+            hardware_version: 5,
+            serialnum: [7u8; 16],
+        };
+
+        let json = serde_json::to_value(info).unwrap();
+        assert_eq!(json["firmware_version"], 0x0118);
+        assert_eq!(serde_json::from_value::<RplidarResponseDeviceInfo>(json).unwrap(), info);
+        assert_eq!(bincode::deserialize::<RplidarResponseDeviceInfo>(&bincode::serialize(&info).unwrap()).unwrap(), info);
+    }
 }
@@ -1,177 +1,63 @@
-use super::checksum::Checksum;
-use byteorder::{ByteOrder, LittleEndian};
-use rpos_drv::{Message, ProtocolDecoder, ProtocolEncoder};
-use std::cmp::min;
+use rplidar_core::{encode_command, encoded_command_size, AnswerDecoder, AnswerSizeLimits};
+use rpos_drv::{BufferedProtocolDecoder, Message, MessageRef, ProtocolDecoder, ProtocolEncoder};
 use std::io::Write;
 
 use super::errors::*;
 
-const RPLIDAR_CMD_SYNC_BYTE: u8 = 0xA5;
-const RPLIDAR_CMDFLAG_HAS_PAYLOAD: u8 = 0x80;
-
-const RPLIDAR_ANS_SYNC_BYTES: [u8; 2] = [0xA5, 0x5A];
-
-const RPLIDAR_ANS_PKTFLAG_LOOP: u8 = 0x1;
-
-const RPLIDAR_ANS_HEADER_SIZE_MASK: u32 = 0x3FFFFFFF;
-const RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT: usize = 30;
-
-/// The size of RPLIDAR protocol answer header (not including the two sync bytes)
-const RPLIDAR_ANS_HEADER_SIZE: usize = 5;
-
-#[derive(Debug, Clone, PartialEq)]
-enum DecodeStatus {
-    WaitSyncByte(usize),
-    WaitAnsHeader,
-    ReceiveResponse,
-}
-
 /// The implementation of RPLIDAR host protocol
+///
+/// Wraps the decoder and encoder of `rplidar_core` into `rpos_drv` messages.
 #[derive(Debug, Clone, PartialEq)]
 pub struct RplidarHostProtocol {
-    status: DecodeStatus,
-    ans_header: Vec<u8>,
-    ans_flag: u8,
-    response_size: usize,
-    decoding_msg: Message, // decode_buffer: RingByteBuffer
+    decoder: AnswerDecoder<Vec<u8>>,
 }
 
 impl RplidarHostProtocol {
     pub fn new() -> RplidarHostProtocol {
         RplidarHostProtocol {
-            status: DecodeStatus::WaitSyncByte(0),
-            ans_header: Vec::new(),
-            ans_flag: 0,
-            response_size: 0,
-            decoding_msg: Message::new(0),
-        }
-    }
-
-    fn start_wait_sync_bytes(&mut self, sync_byte_index: usize) {
-        self.status = DecodeStatus::WaitSyncByte(sync_byte_index);
-        self.ans_header.clear();
-        self.ans_flag = 0;
-        self.response_size = 0;
-    }
-
-    fn start_wait_ans_header(&mut self) {
-        self.status = DecodeStatus::WaitAnsHeader;
-        self.ans_header.clear();
-        self.ans_flag = 0;
-        self.response_size = 0;
-    }
-
-    fn start_receive_response(&mut self) {
-        self.status = DecodeStatus::ReceiveResponse;
-        self.decoding_msg.data.clear();
-    }
-
-    fn decode_sync_bytes(&mut self, buf: &[u8]) -> Result<usize> {
-        if let DecodeStatus::WaitSyncByte(i) = self.status {
-            if buf[0] != RPLIDAR_ANS_SYNC_BYTES[i] {
-                self.start_wait_sync_bytes(0);
-                return Ok(1);
-            } else if i == RPLIDAR_ANS_SYNC_BYTES.len() - 1 {
-                self.start_wait_ans_header();
-                return Ok(1);
-            } else {
-                self.start_wait_sync_bytes(i + 1);
-                return Ok(1);
-            }
-        } else {
-            return Err(RposError::ProtocolError {
-                description: "sync byte status error".to_owned()
-            }.into());
+            decoder: AnswerDecoder::new(Vec::new()),
         }
     }
 
-    fn decode_ans_header(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
-        let bytes_to_read = RPLIDAR_ANS_HEADER_SIZE - self.ans_header.len();
-        let bytes_actual_read = min(bytes_to_read, buf.len());
-        self.ans_header
-            .extend_from_slice(&buf[0..bytes_actual_read]);
-
-        if bytes_to_read == bytes_actual_read {
-            self.decode_ans_header_metadata();
-            if self.response_size == 0 {
-                if (self.ans_flag & RPLIDAR_ANS_PKTFLAG_LOOP) == RPLIDAR_ANS_PKTFLAG_LOOP {
-                    return Err(RposError::ProtocolError { description: "received loop answer with no response size".to_owned() }.into());
-                } else {
-                    let answer = Ok((bytes_actual_read, Some(self.decoding_msg.clone())));
-                    self.reset_decoder();
-                    return answer;
-                }
-            } else {
-                self.start_receive_response();
-                return Ok((bytes_actual_read, None));
-            }
-        } else {
-            return Ok((bytes_actual_read, None));
-        }
+    /// set the largest plausible size of each answer type, larger answer headers are dropped
+    pub fn set_answer_size_limits(&mut self, limits: AnswerSizeLimits) {
+        self.decoder.set_answer_size_limits(limits);
     }
 
-    fn decode_response(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
-        let bytes_to_read = self.response_size - self.decoding_msg.data.len();
-        let bytes_actual_read = min(bytes_to_read, buf.len());
-        self.decoding_msg
-            .data
-            .extend_from_slice(&buf[0..bytes_actual_read]);
-
-        if self.decoding_msg.data.len() == self.response_size {
-            let answer = Ok((bytes_actual_read, Some(self.decoding_msg.clone())));
-            if self.ans_flag & RPLIDAR_ANS_PKTFLAG_LOOP == RPLIDAR_ANS_PKTFLAG_LOOP {
-                self.start_receive_response();
-            } else {
-                self.start_wait_sync_bytes(0);
-            }
-            return answer;
-        } else {
-            return Ok((bytes_actual_read, None));
-        }
+    /// number of answer headers dropped for announcing implausibly large answers
+    pub fn rejected_headers(&self) -> usize {
+        self.decoder.rejected_headers()
     }
 
-    /// when we finished reading the first five bytes of answer header, we will know the message type and payload size of answer header
-    fn decode_ans_header_metadata(&mut self) {
-        self.decoding_msg = Message::new(self.ans_header[4]);
-        let size_q30_subtype = LittleEndian::read_u32(&self.ans_header[0..4]);
-        self.ans_flag = (size_q30_subtype >> RPLIDAR_ANS_HEADER_SUBTYPE_SHIFT as u32) as u8;
-        self.response_size = (size_q30_subtype & RPLIDAR_ANS_HEADER_SIZE_MASK) as usize;
+    /// shift the completed loop answer by one byte, to find the boundaries of out of step answers
+    pub fn realign(&mut self) {
+        self.decoder.realign();
     }
 }
 
 impl ProtocolDecoder for RplidarHostProtocol {
     /// Decode bytes and return consumed bytes and message
     fn decode(&mut self, buf: &[u8]) -> Result<(usize, Option<Message>)> {
-        let mut i = 0;
-
-        while i < buf.len() {
-            match self.status {
-                DecodeStatus::WaitSyncByte(_) => {
-                    i += self.decode_sync_bytes(&buf[i..buf.len()])?;
-                }
-                DecodeStatus::WaitAnsHeader => {
-                    let (read, msg) = self.decode_ans_header(&buf[i..buf.len()])?;
-                    if let Some(_) = msg {
-                        return Ok((i + read, msg));
-                    }
-                    i += read;
-                }
-                DecodeStatus::ReceiveResponse => {
-                    let (read, msg) = self.decode_response(&buf[i..buf.len()])?;
-                    if let Some(_) = msg {
-                        return Ok((i + read, msg));
-                    }
-                    i += read;
-                }
-            }
-        }
-
-        return Ok((i, None));
+        let (read, _) = self.decode_buffered(buf)?;
+        Ok((read, self.message().map(|msg| msg.to_message())))
     }
 
     /// Reset the decoder status
     fn reset_decoder(&mut self) {
-        self.start_wait_sync_bytes(0);
+        self.decoder.reset();
+    }
+}
+
+impl BufferedProtocolDecoder for RplidarHostProtocol {
+    /// Decode bytes and return consumed bytes and whether an answer is completed
+    fn decode_buffered(&mut self, buf: &[u8]) -> Result<(usize, bool)> {
+        let (read, answer) = self.decoder.decode(buf).map_err(from_core_error)?;
+        Ok((read, answer.is_some()))
+    }
+
+    /// The completed answer, valid until the next decode
+    fn message(&self) -> Option<MessageRef<'_>> {
+        self.decoder.answer().map(|answer| MessageRef { cmd: answer.ans_type, data: answer.data })
     }
 }
 
@@ -179,53 +65,12 @@ impl ProtocolEncoder for RplidarHostProtocol {
     /// Encode message into byte array
     /// Always encode commands
     fn encode(&mut self, msg: &Message, bytes: &mut [u8]) -> Result<usize> {
-        let estimated_encoded_size = self.estimate_encoded_size(msg)?;
-
-        if estimated_encoded_size > bytes.len() {
-            return Err(RposError::BufferTooSmall.into());
-        }
-
-        if msg.data.len() > 255 {
-            return Err(RposError::OperationFail { description: "payload too big".to_owned() }.into());
-        }
-
-        let cmd = if msg.data.len() != 0 {
-            msg.cmd | RPLIDAR_CMDFLAG_HAS_PAYLOAD
-        } else {
-            msg.cmd
-        };
-
-        bytes[0] = RPLIDAR_CMD_SYNC_BYTE;
-        bytes[1] = cmd;
-
-        if msg.data.len() != 0 {
-            let mut checksum = Checksum::new();
-
-            checksum.push_slice(&bytes[0..2]);
-            checksum.push(msg.data.len() as u8);
-            checksum.push_slice(&msg.data);
-
-            bytes[2] = msg.data.len() as u8;
-            bytes[3..3 + msg.data.len()].clone_from_slice(&msg.data);
-            bytes[3 + msg.data.len()] = checksum.checksum();
-
-            return Ok(4 + msg.data.len());
-        } else {
-            return Ok(2);
-        }
+        encode_command(msg.cmd, &msg.data, bytes).map_err(from_core_error)
     }
 
     /// Estimate encoded message size (must be greater than or equal to the actual encoded size)
     fn estimate_encoded_size(&mut self, msg: &Message) -> Result<usize> {
-        if msg.data.len() > 255 {
-            return Err(RposError::OperationFail { description: "payload too big".to_owned() }.into());
-        }
-
-        if msg.data.len() > 0 {
-            return Ok(4 + msg.data.len());
-        } else {
-            return Ok(2);
-        }
+        encoded_command_size(&msg.data).map_err(from_core_error)
     }
 
     /// Write message to `std::io::Write` targets
@@ -246,7 +91,7 @@ impl ProtocolEncoder for RplidarHostProtocol {
 #[cfg(test)]
 mod tests {
 
-    use rpos_drv::{Message, ProtocolEncoder, Result};
+    use rpos_drv::{BufferedProtocolDecoder, Message, MessageRef, ProtocolDecoder, ProtocolEncoder, Result};
 
     fn encode<T: ProtocolEncoder>(protocol: &mut T, msg: &Message) -> Result<Vec<u8>> {
         let encoded_bytes = protocol.estimate_encoded_size(&msg)?;
@@ -275,4 +120,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn protocol_decode() {
+        let mut protocol = super::RplidarHostProtocol::new();
+        let bytes = [0xA5, 0x5A, 0x02, 0x00, 0x00, 0x40, 0x81, 1, 2, 3, 4];
+
+        assert_eq!(protocol.decode_buffered(&bytes[..8]).unwrap(), (8, false));
+        assert_eq!(protocol.message(), None);
+
+        assert_eq!(protocol.decode_buffered(&bytes[8..]).unwrap(), (1, true));
+        assert_eq!(protocol.message(), Some(MessageRef { cmd: 0x81, data: &[1, 2] }));
+
+        // loop answers continue without header
+        assert_eq!(protocol.decode(&bytes[9..]).unwrap(), (2, Some(Message::with_data(0x81, &[3, 4]))));
+    }
+
 }
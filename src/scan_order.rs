@@ -0,0 +1,257 @@
+//! Order scan points by angle
+//!
+//! Points of a scan are received in acquisition order: angles increase clockwise
+//! and wrap around at 2π somewhere in the scan. Invalid points may carry bogus
+//! angles. Ordering a scan:
+//!
+//! 1. unwraps the angles of valid points along the acquisition order,
+//! 2. assigns angles to invalid points by interpolating between their valid
+//!    neighbours (or extrapolating with the average angular step at both ends),
+//! 3. wraps all angles back into `[0, 2π)` and sorts the points by angle.
+//!
+//! A scan without any valid point gets evenly spaced angles starting at zero.
+
+use super::prelude::ScanPoint;
+use std::f32::consts::PI;
+
+const PI2: f32 = PI * 2f32;
+
+/// `angle_z_q14` value of a full turn
+const ANGLE_Z_Q14_FULL_TURN: u32 = 1 << 16;
+
+/// set angle of the point, wrapping it into `[0, 2π)`
+fn set_wrapped_angle(point: &mut ScanPoint, angle: f32) {
+    let turns = angle / PI2;
+    let fraction = turns - turns.floor();
+    let q = (fraction * ANGLE_Z_Q14_FULL_TURN as f32).round() as u32;
+    point.angle_z_q14 = (q % ANGLE_Z_Q14_FULL_TURN) as u16;
+}
+
+/// difference from `prev` to `cur` in `(-π, π]`, so a decrease close to a full turn is a wrap around
+fn angle_step(prev: f32, cur: f32) -> f32 {
+    let mut diff = cur - prev;
+    if diff > PI {
+        diff -= PI2;
+    } else if diff <= -PI {
+        diff += PI2;
+    }
+    diff
+}
+
+/// assign angles to invalid points of a scan in acquisition order, without reordering
+pub fn fill_invalid_angles(scan: &mut [ScanPoint]) {
+    let len = scan.len();
+    if len == 0 {
+        return;
+    }
+
+    let default_inc = PI2 / (len as f32);
+
+    let first = match scan.iter().position(|p| p.is_valid()) {
+        Some(first) => first,
+        None => {
+            for (i, point) in scan.iter_mut().enumerate() {
+                set_wrapped_angle(point, (i as f32) * default_inc);
+            }
+            return;
+        }
+    };
+
+    let first_angle = scan[first].angle();
+    let mut prev_index = first;
+    let mut prev_raw = first_angle;
+    let mut prev_unwrapped = first_angle;
+
+    for i in first + 1..len {
+        if !scan[i].is_valid() {
+            continue;
+        }
+
+        let raw = scan[i].angle();
+        let unwrapped = prev_unwrapped + angle_step(prev_raw, raw);
+
+        let gap = (i - prev_index) as f32;
+        for (j, point) in scan.iter_mut().enumerate().take(i).skip(prev_index + 1) {
+            let ratio = ((j - prev_index) as f32) / gap;
+            set_wrapped_angle(point, prev_unwrapped + (unwrapped - prev_unwrapped) * ratio);
+        }
+
+        prev_index = i;
+        prev_raw = raw;
+        prev_unwrapped = unwrapped;
+    }
+
+    let (last, last_unwrapped) = (prev_index, prev_unwrapped);
+
+    let inc = if last > first && last_unwrapped > first_angle {
+        (last_unwrapped - first_angle) / ((last - first) as f32)
+    } else {
+        default_inc
+    };
+
+    for (i, point) in scan.iter_mut().enumerate().take(first) {
+        set_wrapped_angle(point, first_angle - ((first - i) as f32) * inc);
+    }
+
+    for (i, point) in scan.iter_mut().enumerate().skip(last + 1) {
+        set_wrapped_angle(point, last_unwrapped + ((i - last) as f32) * inc);
+    }
+}
+
+/// order the scan in place, without allocation
+pub fn order_scan(scan: &mut [ScanPoint]) {
+    fill_invalid_angles(scan);
+    scan.sort_unstable_by_key(|p| p.angle_z_q14);
+}
+
+/// write the ordered scan into `out`, reusing its buffer
+pub fn order_scan_into(scan: &[ScanPoint], out: &mut Vec<ScanPoint>) {
+    out.clear();
+    out.extend_from_slice(scan);
+    order_scan(out);
+}
+
+/// ordered copy of the scan
+pub fn ordered_scan(scan: &[ScanPoint]) -> Vec<ScanPoint> {
+    let mut out = Vec::with_capacity(scan.len());
+    order_scan_into(scan, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn point(degree: f32, range: f32) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: if range > 0f32 { 10 } else { 0 },
+            flag: 0,
+        };
+        set_wrapped_angle(&mut p, degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    fn degrees(scan: &[ScanPoint]) -> Vec<f32> {
+        scan.iter().map(|p| p.angle().to_degrees().round() % 360f32).collect()
+    }
+
+    #[test]
+    fn wrap_around() {
+        let mut scan = vec![
+            point(350f32, 1f32),
+            point(355f32, 0f32),
+            point(0f32, 1f32),
+            point(123f32, 0f32),
+            point(10f32, 1f32),
+        ];
+        order_scan(&mut scan);
+
+        assert_eq!(degrees(&scan), vec![0f32, 5f32, 10f32, 350f32, 355f32]);
+    }
+
+    #[test]
+    fn head_and_tail() {
+        let mut scan = vec![
+            point(0f32, 0f32),
+            point(0f32, 0f32),
+            point(10f32, 1f32),
+            point(20f32, 1f32),
+            point(0f32, 0f32),
+        ];
+        fill_invalid_angles(&mut scan);
+
+        // the head wraps below zero instead of being clamped
+        assert_eq!(degrees(&scan), vec![350f32, 0f32, 10f32, 20f32, 30f32]);
+    }
+
+    #[test]
+    fn no_valid_points() {
+        let mut scan: Vec<ScanPoint> = (0..4).map(|_| point(77f32, 0f32)).collect();
+        order_scan(&mut scan);
+
+        assert_eq!(degrees(&scan), vec![0f32, 90f32, 180f32, 270f32]);
+    }
+
+    #[test]
+    fn compatible_sort_scan() {
+        let mut scan = vec![point(10f32, 0f32), point(5f32, 1f32)];
+        crate::utils::sort_scan(&mut scan).unwrap();
+        assert_eq!(degrees(&scan), vec![5f32, 185f32]);
+
+        let mut empty: Vec<ScanPoint> = (0..2).map(|_| point(0f32, 0f32)).collect();
+        assert!(crate::utils::sort_scan(&mut empty).is_ok());
+    }
+
+    /// a synthetic scan starting at `start` with `len` evenly spaced points, `holes` marks invalid points
+    fn synthetic_scan(start: f32, len: usize, holes: &[bool]) -> (Vec<ScanPoint>, Vec<f32>) {
+        let inc = 360f32 / (len as f32);
+        let angles: Vec<f32> = (0..len).map(|i| (start + inc * i as f32) % 360f32).collect();
+        let scan = angles
+            .iter()
+            .enumerate()
+            .map(|(i, &a)| {
+                if holes[i % holes.len()] {
+                    point(a + 100f32, 0f32)
+                } else {
+                    point(a, 1f32 + i as f32)
+                }
+            })
+            .collect();
+        (scan, angles)
+    }
+
+    fn arbitrary_point() -> impl Strategy<Value = ScanPoint> {
+        (any::<u16>(), 0u32..40000, any::<u8>(), 0u8..2).prop_map(|(angle_z_q14, dist_mm_q2, quality, flag)| ScanPoint {
+            angle_z_q14,
+            dist_mm_q2,
+            quality,
+            flag,
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn ordering_keeps_points(scan in prop::collection::vec(arbitrary_point(), 0..500)) {
+            let ordered = ordered_scan(&scan);
+
+            prop_assert_eq!(ordered.len(), scan.len());
+            prop_assert!(ordered.windows(2).all(|w| w[0].angle_z_q14 <= w[1].angle_z_q14));
+
+            let key = |p: &ScanPoint| (p.dist_mm_q2, p.quality, p.flag);
+            let mut before: Vec<_> = scan.iter().map(key).collect();
+            let mut after: Vec<_> = ordered.iter().map(key).collect();
+            before.sort();
+            after.sort();
+            prop_assert_eq!(before, after);
+
+            // valid points keep their angles
+            let mut valid_before: Vec<_> = scan.iter().filter(|p| p.is_valid()).map(|p| (p.angle_z_q14, key(p))).collect();
+            let mut valid_after: Vec<_> = ordered.iter().filter(|p| p.is_valid()).map(|p| (p.angle_z_q14, key(p))).collect();
+            valid_before.sort();
+            valid_after.sort();
+            prop_assert_eq!(valid_before, valid_after);
+        }
+
+        #[test]
+        fn interpolated_angles_are_close(
+            start in 0f32..360f32,
+            len in 8usize..2000,
+            holes in prop::collection::vec(prop::bool::weighted(0.3f64), 1..20),
+        ) {
+            let (mut scan, angles) = synthetic_scan(start, len, &holes);
+            fill_invalid_angles(&mut scan);
+
+            // all points invalid gives evenly spaced angles from zero instead
+            prop_assume!(scan.iter().any(|p| p.is_valid()));
+
+            for (p, &expected) in scan.iter().zip(angles.iter()) {
+                let diff = angle_step(expected.to_radians(), p.angle()).abs();
+                prop_assert!(diff < 0.01f32, "expected {} got {}", expected, p.angle().to_degrees());
+            }
+        }
+    }
+}
@@ -0,0 +1,561 @@
+//! Simulated LIDAR
+//!
+//! `SimulatedLidar` is the device side of the protocol: it decodes the commands written
+//! to it and answers like an RPLIDAR in a rectangular room, including measurements paced
+//! in real time. It is a `Read + Write` stream, so it can be used with `RplidarDevice`
+//! directly, or exposed to other programs, e.g. through a pseudo terminal by `rplidar-sim`.
+//!
+//! | Scan mode    | Answer type | Sample duration |
+//! | ------------ | ----------- | --------------- |
+//! | 0 `Standard` | legacy      | 500 us          |
+//! | 1 `Express`  | capsuled    | 250 us          |
+//!
+//! # Example
+//! ```ignore
+//! let lidar = SimulatedLidar::new(SimulatorConfig::default())?;
+//! let mut rplidar = RplidarDevice::with_stream(Box::new(lidar));
+//! ```
+
+use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+use rplidar_core::answers::*;
+use rplidar_core::cmds::*;
+use rplidar_core::{encode_answer_header, Checksum, CommandDecoder, RPLIDAR_ANS_HEADER_LEN};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Samples per capsule
+const CAPSULE_SAMPLES: u64 = 32;
+
+/// Size of a capsule
+const CAPSULE_SIZE: usize = 84;
+
+/// Size of a legacy measurement node
+const NODE_SIZE: usize = 5;
+
+/// Quality of the simulated samples
+const SAMPLE_QUALITY: u8 = 47;
+
+/// How long reads wait for data before timing out, like serial ports
+const READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SimulatedMode {
+    id: u16,
+    name: &'static str,
+    us_per_sample: u32,
+    max_distance: f32,
+    ans_type: u8,
+}
+
+const SIMULATED_MODES: [SimulatedMode; 2] = [
+    SimulatedMode { id: 0, name: "Standard", us_per_sample: 500, max_distance: 12f32, ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT },
+    SimulatedMode { id: 1, name: "Express", us_per_sample: 250, max_distance: 12f32, ans_type: RPLIDAR_ANS_TYPE_MEASUREMENT_CAPSULED },
+];
+
+/// Typical scan mode of the simulated LIDAR
+const TYPICAL_MODE: u16 = 1;
+
+/// Identity and surroundings of a simulated LIDAR
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorConfig {
+    pub model: u8,
+    pub firmware_version: u16,
+    pub hardware_version: u8,
+    pub serialnum: [u8; 16],
+
+    /// Whether it has an accessory board with motor control (`set_motor_pwm`)
+    pub motor_ctrl_support: bool,
+
+    /// Rotations per second, positive
+    pub scan_frequency: f32,
+
+    /// Width (left to right) and depth (back to front) of the room around the LIDAR, in meters
+    pub room_size: (f32, f32),
+}
+
+impl Default for SimulatorConfig {
+    /// an A2 with firmware 1.29 in the middle of a 4 x 6 m room
+    fn default() -> SimulatorConfig {
+        SimulatorConfig {
+            model: 0x18,
+            firmware_version: 0x011D,
+            hardware_version: 5,
+            serialnum: *b"SIMULATED-RPLDR\0",
+            motor_ctrl_support: true,
+            scan_frequency: 10f32,
+            room_size: (4f32, 6f32),
+        }
+    }
+}
+
+impl SimulatorConfig {
+    /// check that a LIDAR can be simulated with this config
+    pub fn validate(&self) -> Result<()> {
+        if self.scan_frequency > 0f32 && self.scan_frequency.is_finite() {
+            Ok(())
+        } else {
+            Err(RposError::OperationFail {
+                description: format!("scan frequency must be positive, not {}", self.scan_frequency),
+            }
+            .into())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SimulatedScan {
+    mode: SimulatedMode,
+    started_at: Instant,
+    samples_sent: u64,
+}
+
+/// A simulated LIDAR, answering the commands written to it
+#[derive(Debug)]
+pub struct SimulatedLidar {
+    config: SimulatorConfig,
+    decoder: CommandDecoder,
+    output: VecDeque<u8>,
+    scan: Option<SimulatedScan>,
+    motor_pwm: u16,
+}
+
+impl SimulatedLidar {
+    /// a simulated LIDAR, fails if the config is invalid
+    pub fn new(config: SimulatorConfig) -> Result<SimulatedLidar> {
+        config.validate()?;
+
+        Ok(SimulatedLidar {
+            config,
+            decoder: CommandDecoder::new(),
+            output: VecDeque::new(),
+            scan: None,
+            motor_pwm: 0,
+        })
+    }
+
+    /// the simulated LIDAR
+    pub fn config(&self) -> &SimulatorConfig {
+        &self.config
+    }
+
+    /// whether it is scanning
+    pub fn is_scanning(&self) -> bool {
+        self.scan.is_some()
+    }
+
+    /// the motor PWM set through the accessory board
+    pub fn motor_pwm(&self) -> u16 {
+        self.motor_pwm
+    }
+
+    /// receive bytes from the host
+    pub fn receive(&mut self, bytes: &[u8]) {
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let (read, command) = self.decoder.decode(&bytes[i..]);
+            i += read;
+
+            if let Some(command) = command {
+                let (cmd, payload) = (command.cmd, command.payload.to_vec());
+                self.on_command(cmd, &payload);
+            }
+        }
+    }
+
+    /// bytes to send to the host at `now`, returns the number of bytes copied into `buf`
+    pub fn transmit(&mut self, now: Instant, buf: &mut [u8]) -> usize {
+        self.generate_measurements(now);
+
+        let len = buf.len().min(self.output.len());
+        for (dest, byte) in buf.iter_mut().zip(self.output.drain(..len)) {
+            *dest = byte;
+        }
+        len
+    }
+
+    fn on_command(&mut self, cmd: u8, payload: &[u8]) {
+        match cmd {
+            RPLIDAR_CMD_STOP => {
+                self.scan = None;
+                self.output.clear();
+            }
+            RPLIDAR_CMD_RESET => {
+                self.scan = None;
+                self.output.clear();
+                self.decoder.reset();
+                self.motor_pwm = 0;
+            }
+            RPLIDAR_CMD_SCAN | RPLIDAR_CMD_FORCE_SCAN => self.start_scan(SIMULATED_MODES[0]),
+            RPLIDAR_CMD_EXPRESS_SCAN if !payload.is_empty() => match payload[0] {
+                // 0 is the legacy express scan, answered with capsules as well
+                0 | 1 => self.start_scan(SIMULATED_MODES[1]),
+                _ => {}
+            },
+            RPLIDAR_CMD_GET_DEVICE_INFO => {
+                let mut data = [0u8; 20];
+                data[0] = self.config.model;
+                LittleEndian::write_u16(&mut data[1..3], self.config.firmware_version);
+                data[3] = self.config.hardware_version;
+                data[4..20].copy_from_slice(&self.config.serialnum);
+                self.answer(RPLIDAR_ANS_TYPE_DEVINFO, &data);
+            }
+            RPLIDAR_CMD_GET_DEVICE_HEALTH => self.answer(RPLIDAR_ANS_TYPE_DEVHEALTH, &[RPLIDAR_HEALTH_STATUS_OK, 0, 0]),
+            RPLIDAR_CMD_GET_SAMPLERATE => {
+                let mut data = [0u8; 4];
+                LittleEndian::write_u16(&mut data[0..2], SIMULATED_MODES[0].us_per_sample as u16);
+                LittleEndian::write_u16(&mut data[2..4], SIMULATED_MODES[1].us_per_sample as u16);
+                self.answer(RPLIDAR_ANS_TYPE_SAMPLE_RATE, &data);
+            }
+            RPLIDAR_CMD_GET_LIDAR_CONF if payload.len() >= 4 => {
+                let data = lidar_conf(payload);
+                self.answer(RPLIDAR_ANS_TYPE_GET_LIDAR_CONF, &data);
+            }
+            RPLIDAR_CMD_GET_ACC_BOARD_FLAG => {
+                let flag = if self.config.motor_ctrl_support { RPLIDAR_RESP_ACC_BOARD_FLAG_MOTOR_CTRL_SUPPORT_MASK } else { 0 };
+                let mut data = [0u8; 4];
+                LittleEndian::write_u32(&mut data, flag);
+                self.answer(RPLIDAR_ANS_TYPE_ACC_BOARD_FLAG, &data);
+            }
+            RPLIDAR_CMD_SET_MOTOR_PWM if payload.len() == 2 && self.config.motor_ctrl_support => {
+                self.motor_pwm = LittleEndian::read_u16(payload);
+            }
+            _ => {}
+        }
+    }
+
+    fn answer(&mut self, ans_type: u8, data: &[u8]) {
+        self.header(ans_type, data.len(), false);
+        self.output.extend(data.iter());
+    }
+
+    fn header(&mut self, ans_type: u8, size: usize, is_loop: bool) {
+        let mut header = [0u8; RPLIDAR_ANS_HEADER_LEN];
+        if encode_answer_header(ans_type, size, is_loop, &mut header).is_ok() {
+            self.output.extend(header.iter());
+        }
+    }
+
+    fn start_scan(&mut self, mode: SimulatedMode) {
+        let size = if mode.ans_type == RPLIDAR_ANS_TYPE_MEASUREMENT { NODE_SIZE } else { CAPSULE_SIZE };
+        self.header(mode.ans_type, size, true);
+        self.scan = Some(SimulatedScan { mode, started_at: Instant::now(), samples_sent: 0 });
+    }
+
+    /// samples per rotation in `mode`
+    fn samples_per_rotation(&self, mode: &SimulatedMode) -> u64 {
+        ((1_000_000f32 / (self.config.scan_frequency * mode.us_per_sample as f32)) as u64).max(1)
+    }
+
+    /// angle (degrees) and distance (mm) of a sample
+    fn sample(&self, mode: &SimulatedMode, index: u64) -> (f32, f32) {
+        let per_rotation = self.samples_per_rotation(mode);
+        let angle = (index % per_rotation) as f32 * 360f32 / per_rotation as f32;
+        let distance = room_distance(self.config.room_size, angle.to_radians());
+
+        if distance <= mode.max_distance {
+            (angle, distance * 1000f32)
+        } else {
+            (angle, 0f32)
+        }
+    }
+
+    fn generate_measurements(&mut self, now: Instant) {
+        let mut scan = match self.scan {
+            Some(scan) => scan,
+            None => return,
+        };
+
+        let elapsed_us = now.saturating_duration_since(scan.started_at).as_micros() as u64;
+        let due = elapsed_us / scan.mode.us_per_sample as u64;
+
+        // a real LIDAR doesn't wait for slow hosts, drop what the host missed
+        let per_rotation = self.samples_per_rotation(&scan.mode);
+        if due > scan.samples_sent.saturating_add(per_rotation) {
+            scan.samples_sent = due - per_rotation;
+        }
+
+        if scan.mode.ans_type == RPLIDAR_ANS_TYPE_MEASUREMENT {
+            while scan.samples_sent < due {
+                let node = self.node(&scan.mode, scan.samples_sent);
+                self.output.extend(node.iter());
+                scan.samples_sent += 1;
+            }
+        } else {
+            while scan.samples_sent + CAPSULE_SAMPLES <= due {
+                let capsule = self.capsule(&scan.mode, scan.samples_sent, scan.samples_sent == 0);
+                self.output.extend(capsule.iter());
+                scan.samples_sent += CAPSULE_SAMPLES;
+            }
+        }
+
+        self.scan = Some(scan);
+    }
+
+    /// legacy measurement node
+    fn node(&self, mode: &SimulatedMode, index: u64) -> [u8; NODE_SIZE] {
+        let (angle, distance) = self.sample(mode, index);
+        let sync = index % self.samples_per_rotation(mode) == 0;
+
+        let mut node = [0u8; NODE_SIZE];
+        node[0] = (SAMPLE_QUALITY << RPLIDAR_RESP_MEASUREMENT_QUALITY_SHIFT) | if sync { 0b01 } else { 0b10 };
+        LittleEndian::write_u16(&mut node[1..3], (((angle * 64f32) as u16) << RPLIDAR_RESP_MEASUREMENT_ANGLE_SHIFT) | 1);
+        LittleEndian::write_u16(&mut node[3..5], (distance * 4f32) as u16);
+        node
+    }
+
+    /// express capsule of 32 samples, without angle compensation
+    fn capsule(&self, mode: &SimulatedMode, first: u64, start: bool) -> [u8; CAPSULE_SIZE] {
+        let mut capsule = [0u8; CAPSULE_SIZE];
+
+        let (start_angle, _) = self.sample(mode, first);
+        let start_flag = if start { 0x8000 } else { 0 };
+        LittleEndian::write_u16(&mut capsule[2..4], ((start_angle * 64f32) as u16) | start_flag);
+
+        for (i, cabin) in capsule[4..].chunks_mut(5).enumerate() {
+            let (_, distance_1) = self.sample(mode, first + 2 * i as u64);
+            let (_, distance_2) = self.sample(mode, first + 2 * i as u64 + 1);
+            LittleEndian::write_u16(&mut cabin[0..2], ((distance_1 * 4f32) as u16) & 0xfffc);
+            LittleEndian::write_u16(&mut cabin[2..4], ((distance_2 * 4f32) as u16) & 0xfffc);
+        }
+
+        let mut checksum = Checksum::new();
+        checksum.push_slice(&capsule[2..]);
+        capsule[0] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_1 << 4) | (checksum.checksum() & 0xf);
+        capsule[1] = (RPLIDAR_RESP_MEASUREMENT_EXP_SYNC_2 << 4) | (checksum.checksum() >> 4);
+        capsule
+    }
+}
+
+/// distance (m) from the center of a `(width, depth)` room to its walls, `angle` clockwise from the front
+fn room_distance(room_size: (f32, f32), angle: f32) -> f32 {
+    let (sin, cos) = angle.sin_cos();
+    let to_side = if sin.abs() > f32::EPSILON { room_size.0 / 2f32 / sin.abs() } else { f32::INFINITY };
+    let to_front = if cos.abs() > f32::EPSILON { room_size.1 / 2f32 / cos.abs() } else { f32::INFINITY };
+    to_side.min(to_front)
+}
+
+/// answer of `RPLIDAR_CMD_GET_LIDAR_CONF`, the config type followed by the value
+fn lidar_conf(payload: &[u8]) -> Vec<u8> {
+    let config_type = LittleEndian::read_u32(&payload[0..4]);
+    let mode = if payload.len() >= 6 { LittleEndian::read_u16(&payload[4..6]) } else { 0 };
+    let mode = SIMULATED_MODES.iter().find(|m| m.id == mode);
+
+    let mut data = payload[0..4].to_vec();
+    let mut value = [0u8; 4];
+
+    match (config_type, mode) {
+        (RPLIDAR_CONF_SCAN_MODE_COUNT, _) => {
+            LittleEndian::write_u16(&mut value, SIMULATED_MODES.len() as u16);
+            data.extend_from_slice(&value[0..2]);
+        }
+        (RPLIDAR_CONF_SCAN_MODE_TYPICAL, _) => {
+            LittleEndian::write_u16(&mut value, TYPICAL_MODE);
+            data.extend_from_slice(&value[0..2]);
+        }
+        (RPLIDAR_CONF_SCAN_MODE_US_PER_SAMPLE, Some(mode)) => {
+            LittleEndian::write_u32(&mut value, mode.us_per_sample << 8);
+            data.extend_from_slice(&value);
+        }
+        (RPLIDAR_CONF_SCAN_MODE_MAX_DISTANCE, Some(mode)) => {
+            LittleEndian::write_u32(&mut value, (mode.max_distance * 256f32) as u32);
+            data.extend_from_slice(&value);
+        }
+        (RPLIDAR_CONF_SCAN_MODE_ANS_TYPE, Some(mode)) => data.push(mode.ans_type),
+        (RPLIDAR_CONF_SCAN_MODE_NAME, Some(mode)) => {
+            data.extend_from_slice(mode.name.as_bytes());
+            data.push(0);
+        }
+        _ => {}
+    }
+
+    data
+}
+
+impl Read for SimulatedLidar {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.transmit(Instant::now(), buf) {
+            0 => {
+                sleep(READ_TIMEOUT);
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            len => Ok(len),
+        }
+    }
+}
+
+impl Write for SimulatedLidar {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RplidarDevice, ScanOptions};
+    use rpos_drv::{FaultConfig, FaultSwitch, FaultyStream};
+
+    #[test]
+    fn reject_non_positive_frequency() {
+        for &scan_frequency in &[0f32, -10f32, f32::NAN, f32::INFINITY] {
+            let config = SimulatorConfig { scan_frequency, ..SimulatorConfig::default() };
+            assert!(config.validate().is_err());
+            assert!(SimulatedLidar::new(config).is_err());
+        }
+
+        // slow but valid, the samples of a rotation don't overflow
+        let config = SimulatorConfig { scan_frequency: 1e-30f32, ..SimulatorConfig::default() };
+        let mut rplidar = RplidarDevice::with_stream(Box::new(SimulatedLidar::new(config).unwrap()));
+        rplidar.start_scan_with_options(&ScanOptions::with_mode(0)).unwrap();
+        assert!(rplidar.grab_scan_with_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    fn check_room_scan(scan: &[crate::ScanPoint]) {
+        assert!(scan.len() > 100);
+
+        for point in scan.iter().filter(|p| p.is_valid()) {
+            let expected = room_distance((4f32, 6f32), point.angle());
+            assert!((point.distance() - expected).abs() < 0.05, "{} m at {} rad, expected {} m", point.distance(), point.angle(), expected);
+        }
+    }
+
+    #[test]
+    fn simulated_device() {
+        let mut rplidar = RplidarDevice::with_stream(Box::new(SimulatedLidar::new(SimulatorConfig::default()).unwrap()));
+
+        let info = rplidar.get_device_info().unwrap();
+        assert_eq!({ info.serialnum }, *b"SIMULATED-RPLDR\0");
+        assert!(rplidar.check_motor_ctrl_support().unwrap());
+
+        let modes = rplidar.get_all_supported_scan_modes().unwrap();
+        assert_eq!(modes.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(), ["Standard", "Express"]);
+        assert_eq!(rplidar.get_typical_scan_mode().unwrap(), 1);
+
+        assert_eq!(rplidar.start_scan().unwrap().name, "Express");
+        rplidar.grab_scan().unwrap();
+        check_room_scan(&rplidar.grab_scan().unwrap());
+    }
+
+    #[test]
+    fn simulated_legacy_scan() {
+        let mut rplidar = RplidarDevice::with_stream(Box::new(SimulatedLidar::new(SimulatorConfig::default()).unwrap()));
+
+        assert_eq!(rplidar.start_scan_with_options(&ScanOptions::with_mode(0)).unwrap().name, "Standard");
+        rplidar.grab_scan().unwrap();
+
+        let scan = rplidar.grab_scan().unwrap();
+        assert_eq!(scan.len(), 200);
+        check_room_scan(&scan);
+    }
+
+    fn noisy_device(seed: u64, config: FaultConfig) -> (RplidarDevice<FaultyStream<SimulatedLidar>>, FaultSwitch) {
+        let stream = FaultyStream::new(SimulatedLidar::new(SimulatorConfig::default()).unwrap(), seed, config);
+        let switch = stream.switch();
+        (RplidarDevice::with_stream(Box::new(stream)), switch)
+    }
+
+    /// start scanning in `mode`, retrying lost commands a bounded number of times
+    fn start_through_noise(rplidar: &mut RplidarDevice<FaultyStream<SimulatedLidar>>, mode: u16, seed: u64) {
+        let mode = ScanOptions::with_mode(mode);
+        let attempts = (1..=20)
+            .find(|_| rplidar.start_scan_with_options_and_timeout(&mode, Duration::from_millis(20)).is_ok());
+        assert!(attempts.is_some(), "seed {}: scan not started", seed);
+    }
+
+    #[test]
+    fn commands_through_noise() {
+        for seed in 0..4 {
+            let config = FaultConfig { max_delay: Duration::from_millis(2), ..FaultConfig::uniform(0.005) };
+            let (mut rplidar, switch) = noisy_device(seed, config);
+
+            // some answers are lost or damaged, but the channel doesn't get stuck
+            let answered = (0..40)
+                .filter(|_| rplidar.get_device_info_with_timeout(Duration::from_millis(20)).is_ok())
+                .count();
+            assert!(answered > 20, "seed {}: {} of 40 answered", seed, answered);
+
+            switch.disable();
+            let _ = rplidar.get_device_health_with_timeout(Duration::from_millis(20));
+            assert_eq!({ rplidar.get_device_info().unwrap().serialnum }, *b"SIMULATED-RPLDR\0");
+        }
+    }
+
+    #[test]
+    fn scan_through_noise() {
+        for (seed, mode) in [(1, 0), (2, 1), (3, 0), (4, 1)] {
+            // lost or duplicated bytes misalign express capsules, which are not resynchronized yet
+            let config = FaultConfig {
+                corrupt_rate: 0.0002,
+                delay_rate: 0.01,
+                max_delay: Duration::from_millis(2),
+                short_read_rate: 0.1,
+                timeout_rate: 0.01,
+                interrupt_rate: 0.01,
+                split_write_rate: 0.1,
+                ..FaultConfig::default()
+            };
+            let (mut rplidar, switch) = noisy_device(seed, config);
+
+            // commands may be lost, retry until the scan runs
+            start_through_noise(&mut rplidar, mode, seed);
+
+            let scans = (0..10).filter(|_| rplidar.grab_scan_with_timeout(Duration::from_millis(300)).is_ok()).count();
+            assert!(scans > 5, "seed {}: {} of 10 scans", seed, scans);
+
+            // damaged bytes leave no trace once the noise is gone
+            switch.disable();
+            rplidar.grab_scan().unwrap();
+            rplidar.grab_scan().unwrap();
+            check_room_scan(&rplidar.grab_scan().unwrap());
+        }
+    }
+
+    #[test]
+    fn legacy_scan_through_lost_bytes() {
+        for seed in 0..4 {
+            let config = FaultConfig { corrupt_rate: 0.0005, drop_rate: 0.0005, duplicate_rate: 0.0005, ..FaultConfig::default() };
+            let (mut rplidar, switch) = noisy_device(seed, config);
+
+            start_through_noise(&mut rplidar, 0, seed);
+
+            // legacy nodes are realigned after lost bytes
+            let scans = (0..10).filter(|_| rplidar.grab_scan_with_timeout(Duration::from_millis(300)).is_ok()).count();
+            assert!(scans > 5, "seed {}: {} of 10 scans", seed, scans);
+            assert!(rplidar.invalid_nodes() > 0, "seed {}: no invalid nodes", seed);
+
+            switch.disable();
+            rplidar.grab_scan().unwrap();
+            rplidar.grab_scan().unwrap();
+            check_room_scan(&rplidar.grab_scan().unwrap());
+        }
+    }
+
+    #[test]
+    fn pace_measurements() {
+        let mut lidar = SimulatedLidar::new(SimulatorConfig::default()).unwrap();
+        lidar.receive(&[0xA5, RPLIDAR_CMD_SCAN]);
+
+        let started_at = lidar.scan.unwrap().started_at;
+        let mut buf = [0u8; 4096];
+
+        // the answer header, then one node per 500 us
+        assert_eq!(lidar.transmit(started_at, &mut buf), RPLIDAR_ANS_HEADER_LEN);
+        assert_eq!(lidar.transmit(started_at + Duration::from_millis(5), &mut buf), 10 * NODE_SIZE);
+
+        // at most a rotation of backlog
+        assert_eq!(lidar.transmit(started_at + Duration::from_secs(10), &mut buf), 200 * NODE_SIZE);
+
+        lidar.receive(&[0xA5, RPLIDAR_CMD_STOP]);
+        assert!(!lidar.is_scanning());
+        assert_eq!(lidar.transmit(started_at + Duration::from_secs(20), &mut buf), 0);
+    }
+}
@@ -0,0 +1,58 @@
+//! `std` transport and clock of the core driver under `RplidarDevice`
+
+use rplidar_core::{Clock, Transport};
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// `std::io` stream as transport of `rplidar_core::RplidarDriver`
+///
+/// Read timeouts and interrupted reads are reported as no data, like `RingByteBuffer` does.
+#[derive(Debug)]
+pub(crate) struct StreamTransport<T: ?Sized> {
+    stream: Box<T>,
+}
+
+impl<T: ?Sized> StreamTransport<T> {
+    pub(crate) fn new(stream: Box<T>) -> StreamTransport<T> {
+        StreamTransport { stream }
+    }
+}
+
+impl<T: ?Sized + Read + Write> Transport for StreamTransport<T> {
+    type Error = io::Error;
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.read(buf) {
+            Err(err) if err.kind() == io::ErrorKind::TimedOut || err.kind() == io::ErrorKind::Interrupted => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Clock of `Instant`s, counting microseconds since the clock was created
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InstantClock {
+    origin: Instant,
+}
+
+impl InstantClock {
+    pub(crate) fn new() -> InstantClock {
+        InstantClock { origin: Instant::now() }
+    }
+
+    /// the instant of a reading of the clock
+    pub(crate) fn instant(&self, now_us: u64) -> Instant {
+        self.origin + Duration::from_micros(now_us)
+    }
+}
+
+impl Clock for InstantClock {
+    fn now_us(&self) -> u64 {
+        self.origin.elapsed().as_micros() as u64
+    }
+}
@@ -0,0 +1,132 @@
+//! TCP transport
+//!
+//! Connects to LIDARs behind serial-to-TCP bridges (e.g. ser2net, LIDARs with
+//! ethernet adapters) or to `rplidar-proxy`.
+//!
+//! # Example
+//! ```ignore
+//! let transport = TcpTransport::connect("192.168.11.2:20108")?;
+//! let mut rplidar = RplidarDevice::with_stream(Box::new(transport));
+//! ```
+
+use super::errors::*;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Read timeout, the channel polls the stream for new data
+const TCP_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// TCP stream to a LIDAR, reporting read timeouts as `TimedOut` on every platform
+///
+/// Unix reports them as `WouldBlock`, which the channel treats as an error.
+#[derive(Debug)]
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// connect to `addr`
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpTransport> {
+        TcpTransport::from_stream(TcpStream::connect(addr)?)
+    }
+
+    /// connect to `addr` with timeout
+    pub fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> Result<TcpTransport> {
+        let mut last_err = None;
+
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return TcpTransport::from_stream(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        match last_err {
+            Some(err) => Err(err.into()),
+            None => Err(RposError::OperationFail { description: "no address to connect".to_owned() }.into()),
+        }
+    }
+
+    /// use a connected stream
+    pub fn from_stream(stream: TcpStream) -> Result<TcpTransport> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(TCP_READ_TIMEOUT))?;
+        Ok(TcpTransport { stream })
+    }
+
+    /// the underlying stream
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// get back the underlying stream
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.read(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Err(io::ErrorKind::TimedOut.into()),
+            result => result,
+        }
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RplidarDevice;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn device_info_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let lidar = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut cmd = [0u8; 2];
+            stream.read_exact(&mut cmd).unwrap();
+            assert_eq!(cmd, [0xA5, 0x50]);
+
+            let mut answer = vec![0xA5, 0x5A, 20, 0, 0, 0, 0x04, 0x18, 0x19, 0x01, 5];
+            answer.extend_from_slice(&[0xAB; 16]);
+            stream.write_all(&answer).unwrap();
+        });
+
+        let transport = TcpTransport::connect(addr).unwrap();
+        let mut rplidar = RplidarDevice::with_stream(Box::new(transport));
+
+        let info = rplidar.get_device_info().unwrap();
+        assert_eq!({ info.model }, 0x18);
+        assert_eq!({ info.firmware_version }, 0x0119);
+        assert_eq!({ info.serialnum }, [0xAB; 16]);
+
+        lidar.join().unwrap();
+    }
+
+    #[test]
+    fn read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut transport = TcpTransport::connect(listener.local_addr().unwrap()).unwrap();
+        let _peer = listener.accept().unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(transport.read(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,265 @@
+//! Coordinate transforms from the polar frame of the LIDAR into the robot frame
+//!
+//! RPLIDAR reports angles increasing clockwise when viewed from the top of the
+//! sensor. The robot frame used here is right handed: x points forward, y points
+//! to the left and angles increase counterclockwise.
+//!
+//! # Example
+//! ```ignore
+//! // LIDAR mounted upside down, 20cm in front of the robot center, facing backwards
+//! let mounting = SensorMounting::new(0.2f64, 0f64, 0.3f64, std::f64::consts::PI)
+//!     .with_upside_down(true);
+//!
+//! for p in mounting.points_f32(&rplidar.grab_scan()?) {
+//!     println!("{} {}", p.x, p.y);
+//! }
+//! ```
+
+use super::prelude::ScanPoint;
+use std::marker::PhantomData;
+use std::slice::Iter;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Mounting of a LIDAR on the robot
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SensorMounting {
+    /// Offset of the sensor along the robot x axis (meters)
+    pub x: f64,
+
+    /// Offset of the sensor along the robot y axis (meters)
+    pub y: f64,
+
+    /// Height of the scan plane above the robot origin (meters)
+    pub z: f64,
+
+    /// Rotation of the sensor front relative to the robot x axis (radians, counterclockwise)
+    pub yaw: f64,
+
+    /// The sensor is mounted upside down, so its angles increase counterclockwise
+    pub upside_down: bool,
+
+    /// Correction added to every measured angle before conversion (radians, in the
+    /// direction of the sensor angles), used when the zero angle of the sensor is
+    /// not aligned with its mounting marks
+    pub angle_offset: f64,
+}
+
+/// A point in the robot frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Point2D<T> {
+    pub x: T,
+    pub y: T,
+}
+
+/// Floating point types of cartesian points
+pub trait Coordinate: Copy {
+    /// convert from `f64`
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Coordinate for f32 {
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+}
+
+impl Coordinate for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+}
+
+impl SensorMounting {
+    /// sensor located at the robot origin, facing forward
+    pub fn identity() -> SensorMounting {
+        SensorMounting::new(0f64, 0f64, 0f64, 0f64)
+    }
+
+    /// create a mounting with offset and yaw
+    pub fn new(x: f64, y: f64, z: f64, yaw: f64) -> SensorMounting {
+        SensorMounting {
+            x,
+            y,
+            z,
+            yaw,
+            upside_down: false,
+            angle_offset: 0f64,
+        }
+    }
+
+    /// set if the sensor is mounted upside down
+    pub fn with_upside_down(mut self, upside_down: bool) -> SensorMounting {
+        self.upside_down = upside_down;
+        self
+    }
+
+    /// set the zero angle correction
+    pub fn with_angle_offset(mut self, angle_offset: f64) -> SensorMounting {
+        self.angle_offset = angle_offset;
+        self
+    }
+
+    /// transform a polar measurement (sensor angle in radians, distance in meters) into robot frame `(x, y)`
+    pub fn transform_polar(&self, angle: f64, distance: f64) -> (f64, f64) {
+        let angle = angle + self.angle_offset;
+
+        // sensor angles increase clockwise, unless the sensor is upside down
+        let sensor_x = distance * angle.cos();
+        let sensor_y = if self.upside_down {
+            distance * angle.sin()
+        } else {
+            -distance * angle.sin()
+        };
+
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+
+        (
+            self.x + cos_yaw * sensor_x - sin_yaw * sensor_y,
+            self.y + sin_yaw * sensor_x + cos_yaw * sensor_y,
+        )
+    }
+
+    /// transform a robot frame point `(x, y)` back into sensor polar `(angle, distance)`,
+    /// with angle in `[0, 2π)`
+    pub fn to_sensor_polar(&self, x: f64, y: f64) -> (f64, f64) {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (dx, dy) = (x - self.x, y - self.y);
+
+        let sensor_x = cos_yaw * dx + sin_yaw * dy;
+        let sensor_y = -sin_yaw * dx + cos_yaw * dy;
+
+        let angle = if self.upside_down {
+            sensor_y.atan2(sensor_x)
+        } else {
+            (-sensor_y).atan2(sensor_x)
+        } - self.angle_offset;
+
+        let pi2 = 2f64 * std::f64::consts::PI;
+        let angle = ((angle % pi2) + pi2) % pi2;
+
+        (angle, sensor_x.hypot(sensor_y))
+    }
+
+    /// transform a scan point into robot frame `[x, y, z]` (meters)
+    pub fn transform(&self, point: &ScanPoint) -> [f64; 3] {
+        let (x, y) = self.transform_polar(point.angle() as f64, point.distance() as f64);
+        [x, y, self.z]
+    }
+
+    /// transform a scan point into robot frame
+    pub fn to_robot_frame<T: Coordinate>(&self, point: &ScanPoint) -> Point2D<T> {
+        let (x, y) = self.transform_polar(point.angle() as f64, point.distance() as f64);
+        Point2D {
+            x: T::from_f64(x),
+            y: T::from_f64(y),
+        }
+    }
+
+    /// bearing of a sensor angle in robot frame (radians, counterclockwise, in `(-π, π]`)
+    pub fn robot_bearing(&self, angle: f64) -> f64 {
+        let (x, y) = self.transform_polar(angle, 1f64);
+        (y - self.y).atan2(x - self.x)
+    }
+
+    /// iterate valid points of a scan in robot frame
+    pub fn points<'a, T: Coordinate>(&self, scan: &'a [ScanPoint]) -> CartesianPoints<'a, T> {
+        CartesianPoints {
+            mounting: *self,
+            iter: scan.iter(),
+            _coordinate: PhantomData,
+        }
+    }
+
+    /// iterate valid points of a scan in robot frame with `f32` coordinates
+    pub fn points_f32<'a>(&self, scan: &'a [ScanPoint]) -> CartesianPoints<'a, f32> {
+        self.points(scan)
+    }
+
+    /// iterate valid points of a scan in robot frame with `f64` coordinates
+    pub fn points_f64<'a>(&self, scan: &'a [ScanPoint]) -> CartesianPoints<'a, f64> {
+        self.points(scan)
+    }
+}
+
+impl Default for SensorMounting {
+    fn default() -> SensorMounting {
+        SensorMounting::identity()
+    }
+}
+
+/// Iterator of valid scan points converted into robot frame
+#[derive(Debug, Clone)]
+pub struct CartesianPoints<'a, T> {
+    mounting: SensorMounting,
+    iter: Iter<'a, ScanPoint>,
+    _coordinate: PhantomData<T>,
+}
+
+impl<'a, T: Coordinate> Iterator for CartesianPoints<'a, T> {
+    type Item = Point2D<T>;
+
+    fn next(&mut self) -> Option<Point2D<T>> {
+        let mounting = self.mounting;
+        self.iter
+            .by_ref()
+            .find(|p| p.is_valid())
+            .map(|p| mounting.to_robot_frame(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn point(degree: f32, range: f32) -> ScanPoint {
+        let mut p = ScanPoint {
+            angle_z_q14: 0,
+            dist_mm_q2: 0,
+            quality: 10,
+            flag: 0,
+        };
+        p.set_angle(degree.to_radians());
+        p.set_distance(range);
+        p
+    }
+
+    fn assert_point(p: Point2D<f64>, x: f64, y: f64) {
+        assert!((p.x - x).abs() < 1e-3 && (p.y - y).abs() < 1e-3, "{:?} != ({}, {})", p, x, y);
+    }
+
+    #[test]
+    fn clockwise_angles() {
+        let points: Vec<Point2D<f64>> = SensorMounting::identity()
+            .points_f64(&[point(0f32, 1f32), point(90f32, 2f32), point(45f32, 0f32)])
+            .collect();
+
+        assert_eq!(points.len(), 2);
+        assert_point(points[0], 1f64, 0f64);
+        assert_point(points[1], 0f64, -2f64);
+    }
+
+    #[test]
+    fn upside_down_with_offset() {
+        let mounting = SensorMounting::new(0.5f64, 0.1f64, 0f64, FRAC_PI_2)
+            .with_upside_down(true)
+            .with_angle_offset(-FRAC_PI_2);
+
+        // the measured 90 degrees is corrected to sensor front, which faces robot left
+        assert_point(mounting.to_robot_frame(&point(90f32, 1f32)), 0.5f64, 1.1f64);
+        // upside down, sensor angle 90 + offset -90 + 90 points to sensor left, robot back
+        assert_point(mounting.to_robot_frame(&point(180f32, 1f32)), -0.5f64, 0.1f64);
+
+        assert!((mounting.robot_bearing(PI) - PI).abs() < 1e-6);
+
+        let p: Point2D<f32> = mounting.points_f32(&[point(90f32, 1f32)]).next().unwrap();
+        assert!((p.y - 1.1f32).abs() < 1e-3);
+
+        let (angle, distance) = mounting.to_sensor_polar(-0.5f64, 0.1f64);
+        assert!((angle - PI).abs() < 1e-6);
+        assert!((distance - 1f64).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,191 @@
+//! UDP transport
+//!
+//! Network LIDARs (e.g. S and T series) speak the same protocol over UDP. `UdpTransport`
+//! turns the datagrams into a byte stream, so answers split over several datagrams
+//! are decoded as usual.
+//!
+//! # Example
+//! ```ignore
+//! let transport = UdpTransport::connect("192.168.11.2:8089")?;
+//! let mut rplidar = RplidarDevice::with_stream(Box::new(transport));
+//! ```
+
+use super::errors::*;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Read timeout, the channel polls the stream for new data
+const UDP_READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Largest datagram received
+const UDP_MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// Largest datagram sent, fits into one ethernet frame
+const UDP_MAX_SEND_SIZE: usize = 1472;
+
+/// UDP socket connected to a LIDAR, reading and writing like a byte stream
+///
+/// Received datagrams are concatenated, writes are buffered and sent as datagrams on `flush`
+/// (the channel flushes every command).
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    received: Vec<u8>,
+    received_len: usize,
+    read_pos: usize,
+    pending: Vec<u8>,
+}
+
+impl UdpTransport {
+    /// send to and receive from `device_addr`, from any local port
+    pub fn connect<A: ToSocketAddrs>(device_addr: A) -> Result<UdpTransport> {
+        let device_addr = resolve(device_addr)?;
+        let local_addr: SocketAddr = if device_addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        UdpTransport::bind(local_addr, device_addr)
+    }
+
+    /// send to and receive from `device_addr`, from `local_addr`
+    pub fn bind<A: ToSocketAddrs, B: ToSocketAddrs>(local_addr: A, device_addr: B) -> Result<UdpTransport> {
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(resolve(device_addr)?)?;
+        UdpTransport::from_socket(socket)
+    }
+
+    /// use a connected socket
+    pub fn from_socket(socket: UdpSocket) -> Result<UdpTransport> {
+        socket.set_read_timeout(Some(UDP_READ_TIMEOUT))?;
+        Ok(UdpTransport {
+            socket,
+            received: vec![0u8; UDP_MAX_DATAGRAM_SIZE],
+            received_len: 0,
+            read_pos: 0,
+            pending: Vec::new(),
+        })
+    }
+
+    /// the underlying socket
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// receive the next datagram into the read buffer
+    fn receive(&mut self) -> io::Result<()> {
+        self.read_pos = 0;
+        self.received_len = 0;
+
+        match self.socket.recv(&mut self.received) {
+            Ok(len) => {
+                self.received_len = len;
+                Ok(())
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Err(io::ErrorKind::TimedOut.into()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn resolve<A: ToSocketAddrs>(addr: A) -> Result<SocketAddr> {
+    match addr.to_socket_addrs()?.next() {
+        Some(addr) => Ok(addr),
+        None => Err(RposError::OperationFail { description: "no address to connect".to_owned() }.into()),
+    }
+}
+
+impl Read for UdpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.read_pos == self.received_len {
+            self.receive()?;
+        }
+
+        let len = buf.len().min(self.received_len - self.read_pos);
+        buf[..len].copy_from_slice(&self.received[self.read_pos..self.read_pos + len]);
+        self.read_pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for UdpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for datagram in self.pending.chunks(UDP_MAX_SEND_SIZE) {
+            self.socket.send(datagram)?;
+        }
+
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RplidarDevice;
+    use std::thread;
+
+    #[test]
+    fn device_info_over_udp() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let device_addr = device.local_addr().unwrap();
+
+        // stand-in device answering in two datagrams
+        let lidar = thread::spawn(move || {
+            let mut cmd = [0u8; 16];
+            let (len, host) = device.recv_from(&mut cmd).unwrap();
+            assert_eq!(cmd[..len], [0xA5, 0x50]);
+
+            device.send_to(&[0xA5, 0x5A, 20, 0, 0, 0, 0x04, 0x61, 0x19], host).unwrap();
+            let mut rest = vec![0x01, 7];
+            rest.extend_from_slice(&[0xCD; 16]);
+            device.send_to(&rest, host).unwrap();
+        });
+
+        let transport = UdpTransport::connect(device_addr).unwrap();
+        let mut rplidar = RplidarDevice::with_stream(Box::new(transport));
+
+        let info = rplidar.get_device_info().unwrap();
+        assert_eq!({ info.model }, 0x61);
+        assert_eq!({ info.firmware_version }, 0x0119);
+        assert_eq!({ info.serialnum }, [0xCD; 16]);
+
+        lidar.join().unwrap();
+    }
+
+    #[test]
+    fn read_datagram_in_pieces() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut transport = UdpTransport::connect(device.local_addr().unwrap()).unwrap();
+
+        let mut buf = [0u8; 4];
+        assert_eq!(transport.read(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        transport.write_all(&[1, 2]).unwrap();
+        transport.write_all(&[3]).unwrap();
+        transport.flush().unwrap();
+
+        let mut datagram = [0u8; 16];
+        let (len, host) = device.recv_from(&mut datagram).unwrap();
+        assert_eq!(datagram[..len], [1, 2, 3]);
+
+        device.send_to(&[1, 2, 3, 4, 5, 6], host).unwrap();
+        let len = loop {
+            match transport.read(&mut buf) {
+                Err(ref err) if err.kind() == io::ErrorKind::TimedOut => continue,
+                result => break result.unwrap(),
+            }
+        };
+        assert_eq!(len, 4);
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert_eq!(transport.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [5, 6]);
+    }
+}
@@ -0,0 +1,352 @@
+//! Web viewer served by the driver process
+//!
+//! `WebViewer` serves a bundled HTML page drawing the latest scan on a canvas, with
+//! range rings and device info, and streams scans to it over a WebSocket. The page
+//! has no external dependencies, so it works on a LAN without internet, e.g. from a
+//! tablet next to the robot.
+//!
+//! | Path    | Content                                  |
+//! | ------- | ---------------------------------------- |
+//! | `/`     | the viewer page                          |
+//! | `/info` | device info as JSON                      |
+//! | `/ws`   | WebSocket streaming `info` and `scan` messages |
+//!
+//! # Example
+//! ```ignore
+//! let viewer = WebViewer::bind("0.0.0.0:8080")?;
+//! println!("open http://{}/", viewer.local_addr());
+//!
+//! // grab scans and publish them until an error other than a timeout
+//! viewer.stream(&mut rplidar)?;
+//! ```
+
+use super::errors::*;
+use super::prelude::{Health, ScanMode, ScanPoint};
+use super::{RplidarDevice, RplidarResponseDeviceInfo};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+/// The viewer page
+const INDEX_HTML: &str = include_str!("web.html");
+
+/// Scans queued per client, newer scans are dropped while a client is behind
+const CLIENT_QUEUE_DEPTH: usize = 2;
+
+/// Longest request header accepted
+const MAX_REQUEST_HEADER_SIZE: usize = 8192;
+
+/// Time for a client to send its request header, idle connections don't hold a thread
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct Shared {
+    info: Mutex<Option<Arc<String>>>,
+    clients: Mutex<Vec<SyncSender<Arc<String>>>>,
+    stopped: AtomicBool,
+}
+
+impl Shared {
+    /// send a message to every client, forgetting disconnected ones
+    fn broadcast(&self, message: Arc<String>) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|client| match client.try_send(message.clone()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+/// HTTP + WebSocket server streaming scans to the bundled viewer page
+#[derive(Debug)]
+pub struct WebViewer {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+impl WebViewer {
+    /// start serving on `addr`, port 0 picks a free port
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<WebViewer> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+
+        let accept_shared = shared.clone();
+        thread::spawn(move || accept_loop(listener, accept_shared));
+
+        Ok(WebViewer { local_addr, shared })
+    }
+
+    /// address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// number of connected WebSocket clients
+    pub fn client_count(&self) -> usize {
+        self.shared.clients.lock().unwrap().len()
+    }
+
+    /// publish device info, health and scan mode, sent to clients when they connect
+    pub fn publish_device_info(&self, info: &RplidarResponseDeviceInfo, health: &Health, scan_mode: Option<&ScanMode>) {
+        let firmware_version = info.firmware_version;
+        let serial_number: String = { info.serialnum }.iter().map(|b| format!("{:02X}", b)).collect();
+        let health = match health {
+            Health::Healthy => "healthy".to_owned(),
+            Health::Warning(code) => format!("warning {:04X}", code),
+            Health::Error(code) => format!("error {:04X}", code),
+        };
+        let scan_mode = scan_mode.map_or(Value::Null, |mode| {
+            json!({
+                "id": mode.id,
+                "name": mode.name,
+                "max_distance": mode.max_distance,
+                "us_per_sample": mode.us_per_sample,
+            })
+        });
+
+        let message = Arc::new(
+            json!({
+                "type": "info",
+                "model": info.model,
+                "firmware_version": format!("{}.{:02}", firmware_version >> 8, firmware_version & 0xff),
+                "hardware_version": info.hardware_version,
+                "serial_number": serial_number,
+                "health": health,
+                "scan_mode": scan_mode,
+            })
+            .to_string(),
+        );
+
+        *self.shared.info.lock().unwrap() = Some(message.clone());
+        self.shared.broadcast(message);
+    }
+
+    /// publish a scan, valid points are sent as `[angle (rad), distance (m), quality]`
+    pub fn publish_scan(&self, scan: &[ScanPoint]) {
+        let points: Vec<Value> = scan
+            .iter()
+            .filter(|p| p.is_valid())
+            .map(|p| json!([p.angle(), p.distance(), p.quality]))
+            .collect();
+
+        let message = json!({ "type": "scan", "points": points }).to_string();
+        self.shared.broadcast(Arc::new(message));
+    }
+
+    /// start scanning and publish scans until an error other than a timeout
+    pub fn stream<T: ?Sized + Read + Write>(&self, device: &mut RplidarDevice<T>) -> Result<()> {
+        let info = device.get_device_info()?;
+        let health = device.get_device_health()?;
+        let scan_mode = device.start_scan()?;
+        self.publish_device_info(&info, &health, Some(&scan_mode));
+
+        loop {
+            match device.grab_scan() {
+                Ok(scan) => self.publish_scan(&scan),
+                Err(err) => match err.downcast_ref::<RposError>() {
+                    Some(RposError::OperationTimeout) => continue,
+                    _ => return Err(err),
+                },
+            }
+        }
+    }
+}
+
+impl Drop for WebViewer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.shared.clients.lock().unwrap().clear();
+
+        // wake up the accept loop so it sees the flag
+        let _ = TcpStream::connect(wake_addr(self.local_addr));
+    }
+}
+
+/// address to connect to a listener on `local_addr`, unspecified addresses (e.g. 0.0.0.0) can't be connected to on Windows
+fn wake_addr(local_addr: SocketAddr) -> SocketAddr {
+    let mut addr = local_addr;
+
+    if addr.ip().is_unspecified() {
+        match addr {
+            SocketAddr::V4(_) => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+            SocketAddr::V6(_) => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+        }
+    }
+
+    addr
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        if shared.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Ok(stream) = stream {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let _ = serve(stream, &shared);
+            });
+        }
+    }
+}
+
+/// the parts of a request the server needs
+#[derive(Debug, Default)]
+struct Request {
+    path: String,
+    websocket_key: Option<String>,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_HEADER_SIZE as u64));
+    let mut request = Request::default();
+    let mut line = String::new();
+
+    reader.read_line(&mut line)?;
+    request.path = line.split_whitespace().nth(1).unwrap_or("/").to_owned();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                request.websocket_key = Some(value.trim().to_owned());
+            }
+        }
+    }
+
+    Ok(request)
+}
+
+fn respond(mut stream: TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn serve(stream: TcpStream, shared: &Shared) -> Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let request = read_request(&stream)?;
+
+    match (request.path.as_str(), request.websocket_key) {
+        ("/ws", Some(key)) => serve_websocket(stream, shared, &key),
+        ("/", _) | ("/index.html", _) => respond(stream, "200 OK", "text/html; charset=utf-8", INDEX_HTML),
+        ("/info", _) => {
+            let info = shared.info.lock().unwrap().clone();
+            let body = info.map_or_else(|| "null".to_owned(), |info| info.to_string());
+            respond(stream, "200 OK", "application/json", &body)
+        }
+        _ => respond(stream, "404 Not Found", "text/plain", "not found"),
+    }
+}
+
+fn serve_websocket(mut stream: TcpStream, shared: &Shared, key: &str) -> Result<()> {
+    let (sender, messages): (_, Receiver<Arc<String>>) = sync_channel(CLIENT_QUEUE_DEPTH);
+
+    // register before answering, so nothing published after the handshake is missed
+    if let Some(ref info) = *shared.info.lock().unwrap() {
+        let _ = sender.try_send(info.clone());
+    }
+    shared.clients.lock().unwrap().push(sender);
+
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    )?;
+    stream.flush()?;
+
+    let mut websocket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    // ends when the viewer is dropped or the client is gone
+    for message in messages.iter() {
+        if websocket.send(Message::text(message.as_str())).is_err() {
+            break;
+        }
+    }
+
+    let _ = websocket.close(None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serve_page_and_stream_scans() {
+        let viewer = WebViewer::bind("127.0.0.1:0").unwrap();
+        let addr = viewer.local_addr();
+
+        let mut http = TcpStream::connect(addr).unwrap();
+        http.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut page = String::new();
+        http.read_to_string(&mut page).unwrap();
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(page.contains("<canvas"));
+
+        let info = RplidarResponseDeviceInfo { model: 0x18, firmware_version: 0x0119, hardware_version: 5, serialnum: [0; 16] };
+        viewer.publish_device_info(&info, &Health::Healthy, None);
+
+        let (mut client, _) = tungstenite::connect(format!("ws://{}/ws", addr)).unwrap();
+        assert_eq!(viewer.client_count(), 1);
+
+        let info: Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+        assert_eq!(info["type"], "info");
+        assert_eq!(info["firmware_version"], "1.25");
+
+        let valid = ScanPoint { angle_z_q14: 16384, dist_mm_q2: 4000, quality: 47, flag: 1 };
+        let invalid = ScanPoint { angle_z_q14: 0, dist_mm_q2: 0, quality: 0, flag: 0 };
+        viewer.publish_scan(&[valid, invalid]);
+
+        let scan: Value = serde_json::from_str(client.read().unwrap().to_text().unwrap()).unwrap();
+        assert_eq!(scan["type"], "scan");
+        assert_eq!(scan["points"].as_array().unwrap().len(), 1);
+        assert_eq!(scan["points"][0][1], 1.0);
+    }
+
+    #[test]
+    fn close_idle_connections() {
+        let viewer = WebViewer::bind("127.0.0.1:0").unwrap();
+        let mut idle = TcpStream::connect(viewer.local_addr()).unwrap();
+        idle.set_read_timeout(Some(REQUEST_TIMEOUT * 2)).unwrap();
+
+        // the server gives up on the request and closes the connection
+        let mut answer = Vec::new();
+        assert_eq!(idle.read_to_end(&mut answer).unwrap(), 0);
+    }
+
+    #[test]
+    fn wake_up_on_loopback() {
+        assert_eq!(wake_addr("0.0.0.0:8080".parse().unwrap()), "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(wake_addr("[::]:8080".parse().unwrap()), "[::1]:8080".parse().unwrap());
+        assert_eq!(wake_addr("192.168.1.2:8080".parse().unwrap()), "192.168.1.2:8080".parse().unwrap());
+
+        // the accept loop ends and closes the listener
+        let viewer = WebViewer::bind("0.0.0.0:0").unwrap();
+        let addr = wake_addr(viewer.local_addr());
+        drop(viewer);
+        thread::sleep(Duration::from_millis(100));
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}